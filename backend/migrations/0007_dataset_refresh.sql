@@ -0,0 +1,7 @@
+-- one row per dataset that gets overwritten every time the dataset is upserted
+CREATE TABLE IF NOT EXISTS DatasetRefresh(
+    page VARCHAR(64) PRIMARY KEY,
+    source VARCHAR(16) NOT NULL,
+    row_count INT NOT NULL,
+    refreshed_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,26 @@
+-- same idea as 0006 but for ammo, every sort column paired with item_id for keyset pagination
+-- item_id sorting and tasks which are always ordered by _id are covered by the primary keys
+CREATE INDEX idx_ammo_accuracy_modifier_id ON Ammo (accuracy_modifier, item_id);
+CREATE INDEX idx_ammo_ammo_type_id ON Ammo (ammo_type, item_id);
+CREATE INDEX idx_ammo_caliber_id ON Ammo (caliber, item_id);
+CREATE INDEX idx_ammo_armor_damage_id ON Ammo (armor_damage, item_id);
+CREATE INDEX idx_ammo_fragmentation_chance_id ON Ammo (fragmentation_chance, item_id);
+CREATE INDEX idx_ammo_damage_id ON Ammo (damage, item_id);
+CREATE INDEX idx_ammo_heavy_bleed_modifier_id ON Ammo (heavy_bleed_modifier, item_id);
+CREATE INDEX idx_ammo_initial_speed_id ON Ammo (initial_speed, item_id);
+CREATE INDEX idx_ammo_light_bleed_modifier_id ON Ammo (light_bleed_modifier, item_id);
+CREATE INDEX idx_ammo_penetration_chance_id ON Ammo (penetration_chance, item_id);
+CREATE INDEX idx_ammo_penetration_power_id ON Ammo (penetration_power, item_id);
+CREATE INDEX idx_ammo_penetration_power_deviation_id ON Ammo (penetration_power_deviation, item_id);
+CREATE INDEX idx_ammo_projectile_count_id ON Ammo (projectile_count, item_id);
+CREATE INDEX idx_ammo_recoil_modifier_id ON Ammo (recoil_modifier, item_id);
+CREATE INDEX idx_ammo_ricochet_chance_id ON Ammo (ricochet_chance, item_id);
+CREATE INDEX idx_ammo_stack_max_size_id ON Ammo (stack_max_size, item_id);
+CREATE INDEX idx_ammo_stamina_burn_per_damage_id ON Ammo (stamina_burn_per_damage, item_id);
+CREATE INDEX idx_ammo_tracer_id ON Ammo (tracer, item_id);
+CREATE INDEX idx_ammo_tracer_color_id ON Ammo (tracer_color, item_id);
+CREATE INDEX idx_ammo_weight_id ON Ammo (weight, item_id);
+
+-- 0006 indexed the flea profit column under the trader profit name
+DROP INDEX IF EXISTS idx_item_buy_from_trader_instant_profit_id;
+CREATE INDEX idx_item_buy_from_trader_instant_profit_id ON Item (buy_from_trader_instant_profit, _id);
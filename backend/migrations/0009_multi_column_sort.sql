@@ -0,0 +1,3 @@
+-- the normalized column:direction list from the sort param, empty means sort_by and sort_asc are used
+ALTER TABLE ItemQueryParams ADD COLUMN IF NOT EXISTS sort VARCHAR(512) DEFAULT '' NOT NULL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS sort VARCHAR(512) DEFAULT '' NOT NULL;
@@ -0,0 +1,17 @@
+-- item filters a device last used, NULL bounds and is_flea mean that filter is off
+ALTER TABLE ItemQueryParams ADD COLUMN IF NOT EXISTS min_base_price INT;
+ALTER TABLE ItemQueryParams ADD COLUMN IF NOT EXISTS max_base_price INT;
+ALTER TABLE ItemQueryParams ADD COLUMN IF NOT EXISTS min_flea_price INT;
+ALTER TABLE ItemQueryParams ADD COLUMN IF NOT EXISTS max_flea_price INT;
+ALTER TABLE ItemQueryParams ADD COLUMN IF NOT EXISTS min_per_slot INT;
+ALTER TABLE ItemQueryParams ADD COLUMN IF NOT EXISTS max_per_slot INT;
+ALTER TABLE ItemQueryParams ADD COLUMN IF NOT EXISTS min_buy_from_flea_instant_profit INT;
+ALTER TABLE ItemQueryParams ADD COLUMN IF NOT EXISTS max_buy_from_flea_instant_profit INT;
+ALTER TABLE ItemQueryParams ADD COLUMN IF NOT EXISTS min_buy_from_trader_instant_profit INT;
+ALTER TABLE ItemQueryParams ADD COLUMN IF NOT EXISTS max_buy_from_trader_instant_profit INT;
+ALTER TABLE ItemQueryParams ADD COLUMN IF NOT EXISTS min_slots INT;
+ALTER TABLE ItemQueryParams ADD COLUMN IF NOT EXISTS max_slots INT;
+ALTER TABLE ItemQueryParams ADD COLUMN IF NOT EXISTS min_avg_24h_price INT;
+ALTER TABLE ItemQueryParams ADD COLUMN IF NOT EXISTS max_avg_24h_price INT;
+ALTER TABLE ItemQueryParams ADD COLUMN IF NOT EXISTS is_flea BOOL;
+ALTER TABLE ItemQueryParams ADD COLUMN IF NOT EXISTS sellable_to VARCHAR(64) DEFAULT 'any' NOT NULL;
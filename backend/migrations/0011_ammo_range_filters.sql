@@ -0,0 +1,32 @@
+-- ammo filters a device last used, NULL bounds and tracer mean that filter is off
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS min_accuracy_modifier REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS max_accuracy_modifier REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS min_armor_damage INT;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS max_armor_damage INT;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS min_fragmentation_chance REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS max_fragmentation_chance REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS max_damage INT;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS min_heavy_bleed_modifier REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS max_heavy_bleed_modifier REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS max_initial_speed REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS min_light_bleed_modifier REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS max_light_bleed_modifier REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS min_penetration_chance REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS max_penetration_chance REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS max_penetration_power INT;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS min_penetration_power_deviation REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS max_penetration_power_deviation REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS min_projectile_count INT;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS max_projectile_count INT;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS min_recoil_modifier REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS max_recoil_modifier REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS min_ricochet_chance REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS max_ricochet_chance REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS min_stack_max_size INT;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS max_stack_max_size INT;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS min_stamina_burn_per_damage REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS max_stamina_burn_per_damage REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS min_weight REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS max_weight REAL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS tracer BOOL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS caliber TEXT[] DEFAULT '{}' NOT NULL;
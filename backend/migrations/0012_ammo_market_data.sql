@@ -0,0 +1,3 @@
+-- whether a device wants item market data embedded in ammo and the trader level prices respect
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS embed_item BOOL DEFAULT FALSE NOT NULL;
+ALTER TABLE AmmoQueryParams ADD COLUMN IF NOT EXISTS trader_level INT;
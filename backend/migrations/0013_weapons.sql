@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS Weapon (
+    _id                CHAR(24)     PRIMARY KEY,
+    weapon_name        VARCHAR(255) NOT NULL,
+    short_name         VARCHAR(128) NOT NULL,
+    caliber            TEXT         NOT NULL,
+    fire_rate          INT          NOT NULL DEFAULT 0,
+    ergonomics         REAL         NOT NULL DEFAULT 0,
+    recoil_vertical    INT          NOT NULL DEFAULT 0,
+    recoil_horizontal  INT          NOT NULL DEFAULT 0
+);
+
+-- compatible ammo is looked up by caliber, the Ammo side uses idx_ammo_caliber_id from 0008
+CREATE INDEX IF NOT EXISTS weapon_caliber_idx ON Weapon (caliber);
@@ -0,0 +1,20 @@
+CREATE TABLE IF NOT EXISTS Armor (
+    _id               CHAR(24)     PRIMARY KEY,
+    armor_name        VARCHAR(255) NOT NULL,
+    short_name        VARCHAR(128) NOT NULL,
+    -- armor, armorplate or helmet
+    armor_type        VARCHAR(16)  NOT NULL,
+    armor_class       INT          NOT NULL DEFAULT 0,
+    durability        REAL         NOT NULL DEFAULT 0,
+    material          VARCHAR(32)  NOT NULL DEFAULT '',
+    blunt_throughput  REAL         NOT NULL DEFAULT 0,
+    ricochet_chance   REAL         NOT NULL DEFAULT 0,
+    zones             TEXT[]       NOT NULL DEFAULT '{}',
+    speed_penalty     REAL         NOT NULL DEFAULT 0,
+    turn_penalty      REAL         NOT NULL DEFAULT 0,
+    ergo_penalty      REAL         NOT NULL DEFAULT 0,
+    weight            REAL         NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS armor_class_idx ON Armor (armor_class);
+CREATE INDEX IF NOT EXISTS armor_type_idx ON Armor (armor_type);
@@ -0,0 +1,5 @@
+-- the api gives every requirement a list of statuses the prerequisite can be in like complete or active,
+-- they used to be joined into one string which made them easy to mistake for plain completion
+ALTER TABLE TaskRequirement ADD COLUMN IF NOT EXISTS statuses TEXT[] DEFAULT '{}' NOT NULL;
+UPDATE TaskRequirement SET statuses = string_to_array(replace(lower(status), ' ', ''), ',');
+ALTER TABLE TaskRequirement DROP COLUMN IF EXISTS status;
@@ -0,0 +1,9 @@
+-- problems found in the task requirements after every task upsert, the whole table is replaced each time
+CREATE TABLE IF NOT EXISTS TaskGraphIssue(
+    id SERIAL PRIMARY KEY,
+    -- dangling_requirement, cycle or orphaned_task
+    kind VARCHAR(32) NOT NULL,
+    task_id CHAR(24) NOT NULL,
+    related_task_ids TEXT[] DEFAULT '{}' NOT NULL,
+    detected_at TIMESTAMPTZ NOT NULL
+);
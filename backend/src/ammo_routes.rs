@@ -1,19 +1,44 @@
+use crate::armor::{AmmoEffectiveness, Armor, ArmorMaterial, MAX_ARMOR_CLASS, MIN_ARMOR_CLASS};
+use crate::armor_routes::get_armor_piece;
+use crate::filters::{list_cache_key, push_range, range_cache_key, text_cache_key};
+use crate::pagination::{
+    Cursor, SortKey, cached_count, parse_sort, push_keyset_condition, push_order_by,
+    resolve_keyset, sort_to_string,
+};
+use crate::query_types::ErrorBody;
+use crate::telemetry::db_span;
+use crate::validation::{AMMO_EFFECTIVENESS_FIELD_RULES, AMMO_FIELD_RULES, validate_strict};
 use crate::{
     api_routers::Device,
-    database_types::{Ammo, DeviceAmmoQueryParams},
+    database_types::{Ammo, AmmoItem, BuyFor, DeviceAmmoQueryParams},
     init_app_state::{AMMO_UNIQUE_CACHE_PREFIX, AppState},
     query_types::{
         AmmoQueryParams, AmmoStats,
-        AppError::{self, BadRequest},
-        AppErrorHandling,
+        AppError::{self, Unauthorized},
+        AppErrorHandling, ArmorQueryParams, VALID_AMMO_SORT_BY,
     },
 };
-use axum::{Json, extract::State};
+use ahash::AHashMap as HashMap;
+use axum::{
+    Json,
+    extract::{RawQuery, State},
+};
 use axum_extra::extract::Query;
-use sqlx::{PgPool, types::Uuid};
+use serde::Serialize;
+use sqlx::{PgPool, Postgres, QueryBuilder, types::Uuid};
 use std::time::Instant;
+use tracing::Instrument;
 
 // gives data on different interesting stats about the data stored
+#[utoipa::path(
+    get,
+    path = "/stats",
+    tag = "ammo",
+    responses(
+        (status = 200, body = AmmoStats),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
 pub async fn ammo_stats(State(app_state): State<AppState>) -> Result<Json<AmmoStats>, AppError> {
     let time_in_seconds = app_state
         .next_ammo_call_timer
@@ -31,6 +56,7 @@ pub async fn ammo_stats(State(app_state): State<AppState>) -> Result<Json<AmmoSt
 
     let ammo_count = sqlx::query_scalar!("SELECT COUNT(*) FROM Ammo")
         .fetch_one(&app_state.pgpool)
+        .instrument(db_span("Ammo Stats"))
         .await
         .bad_sql("Ammo Stats")?
         .unwrap_or(0);
@@ -47,67 +73,372 @@ pub async fn ammo_stats(State(app_state): State<AppState>) -> Result<Json<AmmoSt
     }))
 }
 
+// sorting by price is sorting by the cheapest offer the trader level allows, ammo with no such
+// offer sorts as the most expensive so the order stays stable across pages
+fn ammo_column_sql(column: &str, trader_level: Option<i32>) -> String {
+    let price = format!(
+        "(SELECT MIN(b.price_rub) FROM BuyFor b WHERE b.item_id = Ammo.item_id AND b.min_trader_level <= {})",
+        trader_level.unwrap_or(i32::MAX)
+    );
+    match column {
+        "price" => format!("COALESCE({price}, {})", i32::MAX),
+        "price_per_penetration" => format!(
+            "COALESCE({price}::REAL / NULLIF(penetration_power, 0), {})",
+            i32::MAX
+        ),
+        _ => column.to_string(),
+    }
+}
+
+// fills in the item of every row, names and offers are separate queries like get_items does
+async fn embed_items(
+    pgpool: &PgPool,
+    ammo: &mut [Ammo],
+    trader_level: Option<i32>,
+) -> Result<(), AppError> {
+    let ids: Vec<String> = ammo.iter().map(|x| x.item_id.clone()).collect();
+
+    let names: HashMap<String, (String, String)> = sqlx::query!(
+        "SELECT _id, item_name, short_name FROM Item WHERE _id = ANY($1)",
+        &ids
+    )
+    .fetch_all(pgpool)
+    .instrument(db_span("Ammo Items"))
+    .await
+    .bad_sql("Ammo Items")?
+    .into_iter()
+    .map(|x| (x._id, (x.item_name, x.short_name)))
+    .collect();
+
+    let buys = sqlx::query_as!(BuyFor, "SELECT * FROM BuyFor WHERE item_id = ANY($1)", &ids)
+        .fetch_all(pgpool)
+        .instrument(db_span("Ammo Item Buys"))
+        .await
+        .bad_sql("Ammo Item Buys")?;
+
+    let mut buys_by_id: HashMap<&str, Vec<&BuyFor>> = HashMap::new();
+    for buy in &buys {
+        buys_by_id
+            .entry(buy.item_id.as_str())
+            .or_default()
+            .push(buy);
+    }
+
+    for row in ammo {
+        let Some((item_name, short_name)) = names.get(&row.item_id) else {
+            continue;
+        };
+        let offers = buys_by_id
+            .get(row.item_id.as_str())
+            .map_or(&[][..], Vec::as_slice);
+
+        row.item = Some(Box::new(AmmoItem {
+            item_name: item_name.clone(),
+            short_name: short_name.clone(),
+            cheapest_buy: offers
+                .iter()
+                .filter(|x| trader_level.is_none_or(|level| x.min_trader_level <= level))
+                .min_by_key(|x| x.price_rub)
+                .map(|x| (*x).clone()),
+            flea_price: offers
+                .iter()
+                .find(|x| x.trader_name.eq_ignore_ascii_case("flea market"))
+                .map(|x| x.price_rub),
+        }));
+    }
+
+    Ok(())
+}
+
+// every filter that changes which rows come back, sort and paging are added by the callers
+pub fn ammo_filters_cache_key(query_parms: &AmmoQueryParams) -> String {
+    format!(
+        "{}r{}r{}r{}r{}r{}r{}r{}r{}r{}r{}r{}r{}r{}r{}r{}r{}t{:?}c{}s{}",
+        text_cache_key(&query_parms.ammo_type),
+        range_cache_key(query_parms.min_armor_damage, query_parms.max_armor_damage),
+        range_cache_key(Some(query_parms.damage), query_parms.max_damage),
+        range_cache_key(
+            Some(query_parms.penetration_power),
+            query_parms.max_penetration_power
+        ),
+        range_cache_key(
+            query_parms.min_projectile_count,
+            query_parms.max_projectile_count
+        ),
+        range_cache_key(
+            query_parms.min_stack_max_size,
+            query_parms.max_stack_max_size
+        ),
+        range_cache_key(
+            query_parms.min_accuracy_modifier,
+            query_parms.max_accuracy_modifier
+        ),
+        range_cache_key(
+            query_parms.min_fragmentation_chance,
+            query_parms.max_fragmentation_chance
+        ),
+        range_cache_key(
+            query_parms.min_heavy_bleed_modifier,
+            query_parms.max_heavy_bleed_modifier
+        ),
+        range_cache_key(
+            Some(query_parms.initial_speed),
+            query_parms.max_initial_speed
+        ),
+        range_cache_key(
+            query_parms.min_light_bleed_modifier,
+            query_parms.max_light_bleed_modifier
+        ),
+        range_cache_key(
+            query_parms.min_penetration_chance,
+            query_parms.max_penetration_chance
+        ),
+        range_cache_key(
+            query_parms.min_penetration_power_deviation,
+            query_parms.max_penetration_power_deviation
+        ),
+        range_cache_key(
+            query_parms.min_recoil_modifier,
+            query_parms.max_recoil_modifier
+        ),
+        range_cache_key(
+            query_parms.min_ricochet_chance,
+            query_parms.max_ricochet_chance
+        ),
+        range_cache_key(
+            query_parms.min_stamina_burn_per_damage,
+            query_parms.max_stamina_burn_per_damage
+        ),
+        range_cache_key(query_parms.min_weight, query_parms.max_weight),
+        query_parms.tracer,
+        list_cache_key(&query_parms.caliber),
+        text_cache_key(&query_parms.search),
+    )
+}
+
+// the WHERE shared by the ammo query and its count so both see the same filter set
+fn push_ammo_filters(qb: &mut QueryBuilder<'_, Postgres>, query_parms: &AmmoQueryParams) {
+    let search = &query_parms.search;
+    qb.push("WHERE (")
+        .push_bind(search.clone())
+        .push(" = '' OR caliber ILIKE ")
+        .push_bind(format!("%{search}%"))
+        .push(" OR caliber % ")
+        .push_bind(search.clone())
+        .push(") AND ammo_type ILIKE ")
+        .push_bind(format!("%{}%", query_parms.ammo_type))
+        .push(" ");
+
+    for (column, min, max) in [
+        (
+            "armor_damage",
+            query_parms.min_armor_damage,
+            query_parms.max_armor_damage,
+        ),
+        ("damage", Some(query_parms.damage), query_parms.max_damage),
+        (
+            "penetration_power",
+            Some(query_parms.penetration_power),
+            query_parms.max_penetration_power,
+        ),
+        (
+            "projectile_count",
+            query_parms.min_projectile_count,
+            query_parms.max_projectile_count,
+        ),
+        (
+            "stack_max_size",
+            query_parms.min_stack_max_size,
+            query_parms.max_stack_max_size,
+        ),
+    ] {
+        push_range(qb, column, min, max);
+    }
+
+    for (column, min, max) in [
+        (
+            "accuracy_modifier",
+            query_parms.min_accuracy_modifier,
+            query_parms.max_accuracy_modifier,
+        ),
+        (
+            "fragmentation_chance",
+            query_parms.min_fragmentation_chance,
+            query_parms.max_fragmentation_chance,
+        ),
+        (
+            "heavy_bleed_modifier",
+            query_parms.min_heavy_bleed_modifier,
+            query_parms.max_heavy_bleed_modifier,
+        ),
+        (
+            "initial_speed",
+            Some(query_parms.initial_speed),
+            query_parms.max_initial_speed,
+        ),
+        (
+            "light_bleed_modifier",
+            query_parms.min_light_bleed_modifier,
+            query_parms.max_light_bleed_modifier,
+        ),
+        (
+            "penetration_chance",
+            query_parms.min_penetration_chance,
+            query_parms.max_penetration_chance,
+        ),
+        (
+            "penetration_power_deviation",
+            query_parms.min_penetration_power_deviation,
+            query_parms.max_penetration_power_deviation,
+        ),
+        (
+            "recoil_modifier",
+            query_parms.min_recoil_modifier,
+            query_parms.max_recoil_modifier,
+        ),
+        (
+            "ricochet_chance",
+            query_parms.min_ricochet_chance,
+            query_parms.max_ricochet_chance,
+        ),
+        (
+            "stamina_burn_per_damage",
+            query_parms.min_stamina_burn_per_damage,
+            query_parms.max_stamina_burn_per_damage,
+        ),
+        ("weight", query_parms.min_weight, query_parms.max_weight),
+    ] {
+        push_range(qb, column, min, max);
+    }
+
+    if let Some(tracer) = query_parms.tracer {
+        qb.push("AND tracer = ").push_bind(tracer).push(" ");
+    }
+
+    if !query_parms.caliber.is_empty() {
+        qb.push("AND caliber = ANY(")
+            .push_bind(query_parms.caliber.clone())
+            .push(") ");
+    }
+}
+
+// total ammo for a filter set so pages can show how many there are
+pub async fn count_ammo(
+    app_state: &AppState,
+    query_parms: &AmmoQueryParams,
+) -> Result<i64, AppError> {
+    let cache_key = format!(
+        "{}count{}",
+        AMMO_UNIQUE_CACHE_PREFIX,
+        ammo_filters_cache_key(query_parms),
+    );
+
+    let mut qb: QueryBuilder<'_, Postgres> = QueryBuilder::new("SELECT COUNT(*) FROM Ammo ");
+    push_ammo_filters(&mut qb, query_parms);
+
+    cached_count(
+        app_state,
+        cache_key,
+        AMMO_UNIQUE_CACHE_PREFIX,
+        qb,
+        "Ammo Count",
+    )
+    .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "ammo",
+    params(Device, AmmoQueryParams),
+    responses(
+        (status = 200, body = Vec<Ammo>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
 pub async fn get_ammo(
     device: Device,
     Query(query_parms): Query<AmmoQueryParams>,
+    RawQuery(raw_query): RawQuery,
     State(app_state): State<AppState>,
 ) -> Result<Json<Vec<Ammo>>, AppError> {
+    let sort = query_parms.sort_keys();
     let AmmoQueryParams {
-        search,
-        sort_by,
-        sort_asc,
-        damage,
-        penetration_power,
-        initial_speed,
-        ammo_type,
+        embed_item,
+        trader_level,
         limit,
         offset,
+        cursor,
         save,
+        strict,
+        ..
     } = query_parms.clone();
 
+    if strict {
+        validate_strict(raw_query.as_deref(), &query_parms, AMMO_FIELD_RULES)?;
+    }
+
+    let filters_cache_key = ammo_filters_cache_key(&query_parms);
+    let cursor = cursor
+        .filter(|c| !c.is_empty())
+        .map(|c| Cursor::decode(&c, &sort, &filters_cache_key))
+        .transpose()?;
+    let offset = cursor.as_ref().map_or(offset, |c| c.offset);
+
     // save query
     if save && let Some(device_id) = device.0 {
-        save_ammo_query_parms(device_id, query_parms, app_state.pgpool.clone());
+        save_ammo_query_parms(device_id, query_parms.clone(), app_state.pgpool.clone());
     }
 
     // redis performance falls off at large amounts of items
-    let cache_key = format!(
-        "{}{}{}d{}p{}i{}{}l{}o{}{}",
-        AMMO_UNIQUE_CACHE_PREFIX,
-        if sort_asc { "1" } else { "0" },
-        sort_by,
-        damage,
-        penetration_power,
-        initial_speed,
-        ammo_type,
-        limit,
-        offset,
-        search,
-    );
+    let page_cache_key = |offset: u32| {
+        format!(
+            "{}{}{}e{}tl{:?}l{}o{}",
+            AMMO_UNIQUE_CACHE_PREFIX,
+            sort_to_string(&sort),
+            filters_cache_key,
+            embed_item,
+            trader_level,
+            limit,
+            offset,
+        )
+    };
+    let cache_key = page_cache_key(offset);
 
     if let Some(values) = app_state.cache.get_vec(&cache_key) {
         return Ok(Json(values));
     }
 
-    let sql = format!(
-        "SELECT * FROM Ammo 
-        WHERE ($1 = '' OR caliber ILIKE '%' || $1 || '%' OR caliber % $1) AND damage >= $2 AND penetration_power >= $3 AND initial_speed >= $4 AND ammo_type ILIKE $5 ORDER BY {} {}, item_id LIMIT $6 OFFSET $7",
-        sort_by,
-        if sort_asc { "ASC" } else { "DESC" },
-    );
+    let prev_cache_key = (offset >= limit).then(|| page_cache_key(offset - limit));
+    let keyset = resolve_keyset::<Ammo>(cursor, &app_state.cache, prev_cache_key, &sort);
 
-    let ammo = sqlx::query_as(&sql)
-        .bind(search)
-        .bind(damage)
-        .bind(penetration_power)
-        .bind(initial_speed)
-        .bind(format!("%{ammo_type}%"))
-        .bind(i64::from(limit))
-        .bind(i64::from(offset))
+    let mut qb: QueryBuilder<'_, Postgres> = QueryBuilder::new("SELECT * FROM Ammo ");
+    push_ammo_filters(&mut qb, &query_parms);
+
+    let column_sql = |column: &str| ammo_column_sql(column, trader_level);
+    let successful_keyset = keyset.is_some();
+    if let Some(keyset) = keyset {
+        push_keyset_condition(&mut qb, &sort, column_sql, "item_id", keyset);
+    }
+
+    push_order_by(&mut qb, &sort, column_sql, "item_id");
+    qb.push("LIMIT ").push_bind(i64::from(limit));
+
+    if !successful_keyset {
+        qb.push(" OFFSET ").push_bind(i64::from(offset));
+    }
+
+    let mut ammo: Vec<Ammo> = qb
+        .build_query_as()
         .fetch_all(&app_state.pgpool)
+        .instrument(db_span("Ammo"))
         .await
         .bad_sql("Ammo")?;
 
+    if embed_item {
+        embed_items(&app_state.pgpool, &mut ammo, trader_level).await?;
+    }
+
     let tokio_values = ammo.clone();
     tokio::spawn(async move {
         app_state
@@ -123,7 +454,9 @@ fn save_ammo_query_parms(device_id: Uuid, query_parms: AmmoQueryParams, pgpool:
     tokio::spawn(async move {
         let _ = sqlx::query!(
             "UPDATE AmmoQueryParams
-                    SET search = $2, sort_by = $3, sort_asc = $4, damage = $5, penetration_power = $6, initial_speed = $7, ammo_type = $8 WHERE id = $1",
+                    SET search = $2, sort_by = $3, sort_asc = $4, damage = $5, penetration_power = $6, initial_speed = $7, ammo_type = $8, sort = $9,
+                    min_accuracy_modifier = $10, max_accuracy_modifier = $11, min_armor_damage = $12, max_armor_damage = $13, min_fragmentation_chance = $14, max_fragmentation_chance = $15, max_damage = $16, min_heavy_bleed_modifier = $17, max_heavy_bleed_modifier = $18, max_initial_speed = $19, min_light_bleed_modifier = $20, max_light_bleed_modifier = $21, min_penetration_chance = $22, max_penetration_chance = $23, max_penetration_power = $24, min_penetration_power_deviation = $25, max_penetration_power_deviation = $26, min_projectile_count = $27, max_projectile_count = $28, min_recoil_modifier = $29, max_recoil_modifier = $30, min_ricochet_chance = $31, max_ricochet_chance = $32, min_stack_max_size = $33, max_stack_max_size = $34, min_stamina_burn_per_damage = $35, max_stamina_burn_per_damage = $36, min_weight = $37, max_weight = $38,
+                    tracer = $39, caliber = $40, embed_item = $41, trader_level = $42 WHERE id = $1",
             device_id,
             query_parms.search,
             query_parms.sort_by,
@@ -136,18 +469,62 @@ fn save_ammo_query_parms(device_id: Uuid, query_parms: AmmoQueryParams, pgpool:
             } else {
                 query_parms.ammo_type
             },
+            sort_to_string(&parse_sort(&query_parms.sort, VALID_AMMO_SORT_BY)),
+            query_parms.min_accuracy_modifier,
+            query_parms.max_accuracy_modifier,
+            query_parms.min_armor_damage,
+            query_parms.max_armor_damage,
+            query_parms.min_fragmentation_chance,
+            query_parms.max_fragmentation_chance,
+            query_parms.max_damage,
+            query_parms.min_heavy_bleed_modifier,
+            query_parms.max_heavy_bleed_modifier,
+            query_parms.max_initial_speed,
+            query_parms.min_light_bleed_modifier,
+            query_parms.max_light_bleed_modifier,
+            query_parms.min_penetration_chance,
+            query_parms.max_penetration_chance,
+            query_parms.max_penetration_power,
+            query_parms.min_penetration_power_deviation,
+            query_parms.max_penetration_power_deviation,
+            query_parms.min_projectile_count,
+            query_parms.max_projectile_count,
+            query_parms.min_recoil_modifier,
+            query_parms.max_recoil_modifier,
+            query_parms.min_ricochet_chance,
+            query_parms.max_ricochet_chance,
+            query_parms.min_stack_max_size,
+            query_parms.max_stack_max_size,
+            query_parms.min_stamina_burn_per_damage,
+            query_parms.max_stamina_burn_per_damage,
+            query_parms.min_weight,
+            query_parms.max_weight,
+            query_parms.tracer,
+            &query_parms.caliber,
+            query_parms.embed_item,
+            query_parms.trader_level,
         )
         .execute(&pgpool)
         .await;
     });
 }
 
+#[utoipa::path(
+    get,
+    path = "/query_parms",
+    tag = "ammo",
+    params(Device),
+    responses(
+        (status = 200, body = DeviceAmmoQueryParams),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
 pub async fn get_device_ammo_query_parms(
     device: Device,
     State(app_state): State<AppState>,
 ) -> Result<Json<DeviceAmmoQueryParams>, AppError> {
     if device.0.is_none() {
-        return Err(BadRequest("Endpoint Requires a device id".into()));
+        return Err(Unauthorized("Endpoint Requires a device id".into()));
     }
     let device_id = device.0.unwrap();
 
@@ -158,6 +535,7 @@ pub async fn get_device_ammo_query_parms(
         device_id
     )
     .execute(&mut *txn)
+    .instrument(db_span("Device Preferences"))
     .await
     .bad_sql("Device Preferences")?;
 
@@ -166,6 +544,7 @@ pub async fn get_device_ammo_query_parms(
         device_id
     )
     .execute(&mut *txn)
+    .instrument(db_span("AmmoQueryParams"))
     .await
     .bad_sql("AmmoQueryParams")?;
 
@@ -175,6 +554,7 @@ pub async fn get_device_ammo_query_parms(
         device_id
     )
     .fetch_one(&mut *txn)
+    .instrument(db_span("AmmoQueryParams"))
     .await
     .bad_sql("AmmoQueryParams")?;
 
@@ -183,6 +563,202 @@ pub async fn get_device_ammo_query_parms(
     Ok(Json(v))
 }
 
+#[utoipa::path(
+    get,
+    path = "/help",
+    tag = "ammo",
+    params(AmmoQueryParams),
+    responses((status = 200, body = AmmoQueryParams))
+)]
 pub async fn get_ammo_help(Query(query_parms): Query<AmmoQueryParams>) -> Json<AmmoQueryParams> {
     Json(query_parms)
 }
+
+// the rank column comes first so a cursor from one ranking can not be used with another
+pub fn effectiveness_sort_keys(
+    query_parms: &AmmoQueryParams,
+    armor_parms: &ArmorQueryParams,
+) -> Vec<SortKey> {
+    let mut sort = query_parms.sort_keys();
+    if !armor_parms.rank_by.is_empty() {
+        sort.insert(0, SortKey::new(&armor_parms.rank_by, armor_parms.rank_asc));
+    }
+    sort
+}
+
+// the armor being measured against changes the ranking as much as the ammo filters do
+pub fn effectiveness_filters_cache_key(
+    query_parms: &AmmoQueryParams,
+    armor_parms: &ArmorQueryParams,
+) -> String {
+    format!(
+        "{}a{:?}c{}d{:?}-{:?}m{}",
+        ammo_filters_cache_key(query_parms),
+        armor_parms.armor_id,
+        armor_parms.armor_class,
+        armor_parms.durability,
+        armor_parms.max_durability,
+        text_cache_key(&armor_parms.material.to_lowercase()),
+    )
+}
+
+// never killing within MAX_SHOTS ranks after every round that does
+fn rank_value(row: &AmmoEffectiveness, rank_by: &str) -> f64 {
+    let shots = |x: Option<u32>| x.map_or(f64::INFINITY, f64::from);
+    match rank_by {
+        "penetration_chance" => f64::from(row.penetration_chance),
+        "shots_to_penetrate" => f64::from(row.shots_to_penetrate),
+        "thorax_shots_to_kill" => shots(row.thorax_shots_to_kill),
+        "head_shots_to_kill" => shots(row.head_shots_to_kill),
+        _ => 0.0,
+    }
+}
+
+// every ammo row matching the filters measured against the armor, ranked but not paged
+async fn ranked_effectiveness(
+    app_state: &AppState,
+    query_parms: &AmmoQueryParams,
+    armor_parms: &ArmorQueryParams,
+    armor: &Armor,
+) -> Result<Vec<AmmoEffectiveness>, AppError> {
+    let sort = query_parms.sort_keys();
+    let cache_key = format!(
+        "{}effectiveness{}d{}-{}c{:?}b{}m{}r{}{}{}e{}tl{:?}",
+        AMMO_UNIQUE_CACHE_PREFIX,
+        armor.class.class,
+        armor.durability,
+        armor.class.max_durability,
+        armor.class.material,
+        armor.class.blunt_throughput,
+        armor_parms.rank_by.to_lowercase(),
+        armor_parms.rank_asc,
+        sort_to_string(&sort),
+        ammo_filters_cache_key(query_parms),
+        query_parms.embed_item,
+        query_parms.trader_level,
+    );
+
+    if let Some(values) = app_state.cache.get_vec(&cache_key) {
+        return Ok(values);
+    }
+
+    let mut qb: QueryBuilder<'_, Postgres> = QueryBuilder::new("SELECT * FROM Ammo ");
+    push_ammo_filters(&mut qb, query_parms);
+    push_order_by(
+        &mut qb,
+        &sort,
+        |column: &str| ammo_column_sql(column, query_parms.trader_level),
+        "item_id",
+    );
+
+    let mut ammo: Vec<Ammo> = qb
+        .build_query_as()
+        .fetch_all(&app_state.pgpool)
+        .instrument(db_span("Ammo Effectiveness"))
+        .await
+        .bad_sql("Ammo Effectiveness")?;
+
+    if query_parms.embed_item {
+        embed_items(&app_state.pgpool, &mut ammo, query_parms.trader_level).await?;
+    }
+
+    let mut rows: Vec<AmmoEffectiveness> = ammo.iter().map(|x| armor.effectiveness(x)).collect();
+
+    // a stable sort so ties keep the order from the query
+    let rank_by = armor_parms.rank_by.to_lowercase();
+    if !rank_by.is_empty() {
+        rows.sort_by(|a, b| {
+            let order = rank_value(a, &rank_by).total_cmp(&rank_value(b, &rank_by));
+            if armor_parms.rank_asc {
+                order
+            } else {
+                order.reverse()
+            }
+        });
+    }
+
+    let tokio_values = rows.clone();
+    let cache = app_state.cache.clone();
+    tokio::spawn(async move {
+        cache.insert_vec(cache_key, tokio_values, AMMO_UNIQUE_CACHE_PREFIX);
+    });
+
+    Ok(rows)
+}
+
+// both param structs are read from the same query string so strict checks them together
+#[derive(Serialize)]
+struct EffectivenessParams<'a> {
+    #[serde(flatten)]
+    ammo: &'a AmmoQueryParams,
+    #[serde(flatten)]
+    armor: &'a ArmorQueryParams,
+}
+
+// how every ammo matching the ammo filters does against one armor, see armor.rs for the model
+// the query params are not saved since they belong to the ammo page
+#[utoipa::path(
+    get,
+    path = "/effectiveness",
+    tag = "ammo",
+    params(AmmoQueryParams, ArmorQueryParams),
+    responses(
+        (status = 200, body = Vec<AmmoEffectiveness>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_ammo_effectiveness(
+    Query(query_parms): Query<AmmoQueryParams>,
+    Query(armor_parms): Query<ArmorQueryParams>,
+    RawQuery(raw_query): RawQuery,
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<AmmoEffectiveness>>, AppError> {
+    if query_parms.strict {
+        validate_strict(
+            raw_query.as_deref(),
+            &EffectivenessParams {
+                ammo: &query_parms,
+                armor: &armor_parms,
+            },
+            AMMO_EFFECTIVENESS_FIELD_RULES,
+        )?;
+    }
+
+    let armor = match armor_parms.armor_id.as_deref().filter(|x| !x.is_empty()) {
+        Some(armor_id) => {
+            let piece = get_armor_piece(&app_state, armor_id).await?;
+            Armor::from_piece(&piece, armor_parms.durability).ok_or_else(|| {
+                AppError::BadRequest(format!("armor {armor_id} does not have an armor class"))
+            })?
+        }
+        None => Armor::new(
+            armor_parms.armor_class,
+            armor_parms.durability,
+            armor_parms.max_durability,
+            ArmorMaterial::from_name(&armor_parms.material),
+        )
+        .ok_or_else(|| {
+            AppError::BadRequest(format!(
+                "armor_class must be between {MIN_ARMOR_CLASS} and {MAX_ARMOR_CLASS}"
+            ))
+        })?,
+    };
+
+    let sort = effectiveness_sort_keys(&query_parms, &armor_parms);
+    let offset = match query_parms.cursor.as_deref() {
+        Some(cursor) if !cursor.is_empty() => {
+            let filters_cache_key = effectiveness_filters_cache_key(&query_parms, &armor_parms);
+            Cursor::decode(cursor, &sort, &filters_cache_key)?.offset
+        }
+        _ => query_parms.offset,
+    };
+
+    let rows = ranked_effectiveness(&app_state, &query_parms, &armor_parms, &armor).await?;
+
+    Ok(Json(
+        rows.into_iter()
+            .skip(offset as usize)
+            .take(query_parms.limit as usize)
+            .collect(),
+    ))
+}
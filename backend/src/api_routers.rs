@@ -1,34 +1,52 @@
-use crate::ammo_routes::{ammo_stats, get_ammo, get_ammo_help, get_device_ammo_query_parms};
+use crate::ammo_routes::get_ammo_help;
 use crate::caching::Cacheable;
-use crate::database_types::{Ammo, Item, ItemBase, ItemFromDB, Task, TaskBase, TaskFromDB};
-use crate::init_app_state::{
-    AMMO_UNIQUE_CACHE_PREFIX, AppState, ITEMS_UNIQUE_CACHE_PREFIX, TASKS_UNIQUE_CACHE_PREFIX,
+use crate::database_types::{
+    Ammo, AmmoItem, ArmorPiece, Item, ItemBase, ItemFromDB, Task, TaskBase, TaskFromDB,
 };
-use crate::item_routes::{
-    get_device_item_query_parms, get_item_history, get_items, get_items_help, item_stats,
-    items_from_db_to_items,
+use crate::init_app_state::{
+    AMMO_UNIQUE_CACHE_PREFIX, ARMOR_UNIQUE_CACHE_PREFIX, AppState, ITEMS_UNIQUE_CACHE_PREFIX,
+    TASKS_UNIQUE_CACHE_PREFIX,
 };
+use crate::item_routes::{get_items_help, items_from_db_to_items};
 use crate::query_types::{AmmoQueryParams, ItemQueryParams, TaskQueryParams};
-use crate::query_types::{
-    AppError, AppError::UninitalizedDatabase, AppErrorHandling, IdsQueryParams,
-};
-use crate::task_routes::{
-    clear_completed_tasks, get_adj_list, get_completed_tasks, get_device_task_query_parms,
-    get_required_items, get_tasks, get_tasks_base, get_tasks_help, set_completed_task, task_stats,
-    tasks_from_db_to_tasks,
+use crate::query_types::{AppError, AppErrorHandling, ErrorBody, IdsQueryParams};
+use crate::task_routes::{get_tasks_help, tasks_from_db_to_tasks};
+use crate::telemetry::db_span;
+use crate::{
+    ammo_routes, app_metrics, armor_routes, health_routes, item_routes, task_routes, v2_routes,
+    weapon_routes,
 };
 use axum::extract::FromRequestParts;
 use axum::http::request::Parts;
-use axum::{Router, extract::State, response::Json, routing::get, routing::post};
+use axum::{Router, extract::State, response::Json, routing::get};
 use axum_extra::extract::Query;
 use serde::{Serialize, de::DeserializeOwned};
 use sqlx::types::Uuid;
 use std::sync::Arc;
 use std::time::Instant;
 use tokio::sync::RwLock;
-use tokio::try_join;
-
-const API_DOCUMENTATION: &str = r"all item routes beginning with /items
+use tracing::Instrument;
+use utoipa::openapi::Required;
+use utoipa::openapi::path::{Parameter, ParameterBuilder, ParameterIn};
+use utoipa::openapi::schema::{KnownFormat, ObjectBuilder, SchemaFormat, Type};
+use utoipa::{IntoParams, OpenApi};
+use utoipa_axum::{router::OpenApiRouter, routes};
+use utoipa_scalar::{Scalar, Servable};
+
+const API_DOCUMENTATION: &str = r"the openapi spec is at /openapi.json and can be browsed at /docs
+every route below is also served under /v1 and /v2 has the same routes with object shaped responses
+v2 list pages return a total and a next_cursor that can be passed back as cursor to get the next page
+items and ammo accept sort=column:asc,column:desc to order by several columns instead of sort_by and sort_asc
+
+health routes
+/healthz
+/readyz
+/health/details
+
+admin routes
+/admin/task_graph_issues
+
+all item routes beginning with /items
 /
 /stats
 /history
@@ -39,10 +57,13 @@ const API_DOCUMENTATION: &str = r"all item routes beginning with /items
 all task routes beginning with /tasks
 /
 /base
+/available
 /stats
 /ids
 /get_required_items
+/{id}/path
 /adj_list
+/graph
 /get_completed
 /set_complete
 /clear_completed_tasks
@@ -51,48 +72,48 @@ all task routes beginning with /tasks
 
 all ammo routes beginning with /ammo
 /
+/effectiveness
 /stats
 /ids
 /query_parms
 /help
 
+all weapon routes beginning with /weapons
+/
+/calibers
+/{id}/ammo which takes the same params as /ammo
+
+all armor routes beginning with /armor
+/
+/ids
+/help
+
 the save parameter for each of the endpoints requires device id and it will save query params to database
+invalid query params are replaced with defaults unless strict=true is passed in which case a 400 is returned
+listing every invalid param along with the allowed values and the nearest matches
 ";
 
-// checks if the database is initalized
-async fn health(State(app_state): State<AppState>) -> Result<String, AppError> {
-    let (items_count, tasks_count, ammo_count) = try_join!(
-        sqlx::query_scalar!("SELECT COUNT(*) FROM Item").fetch_one(&app_state.pgpool),
-        sqlx::query_scalar!("SELECT COUNT(*) FROM Task").fetch_one(&app_state.pgpool),
-        sqlx::query_scalar!("SELECT COUNT(*) FROM Ammo").fetch_one(&app_state.pgpool)
+// human readable list of every route along with the default query params
+#[utoipa::path(
+    get,
+    path = "/",
+    responses((status = 200, body = String, content_type = "text/plain"))
+)]
+async fn api_documentation() -> String {
+    let item_help =
+        serde_json::to_string_pretty(&get_items_help(Query(ItemQueryParams::default())).await.0)
+            .unwrap_or_default();
+    let task_help =
+        serde_json::to_string_pretty(&get_tasks_help(Query(TaskQueryParams::default())).await.0)
+            .unwrap_or_default();
+    let ammo_help =
+        serde_json::to_string_pretty(&get_ammo_help(Query(AmmoQueryParams::default())).await.0)
+            .unwrap_or_default();
+
+    format!(
+        "{}\nitems default query params at /items\n{}\n\ntasks default query params at /tasks\n{}\n\nammo default query params at /ammo\n{}",
+        API_DOCUMENTATION, item_help, task_help, ammo_help
     )
-    .map_err(|_| UninitalizedDatabase(String::from("The Database has not yet been initalized")))?;
-
-    if items_count.unwrap_or(0) == 0
-        || tasks_count.unwrap_or(0) == 0
-        || ammo_count.unwrap_or(0) == 0
-    {
-        Err(UninitalizedDatabase(String::from(
-            "The Database has not yet been initalized",
-        )))
-    } else {
-        let item_help = serde_json::to_string_pretty(
-            &get_items_help(Query(ItemQueryParams::default())).await.0,
-        )
-        .unwrap_or_default();
-        let task_help = serde_json::to_string_pretty(
-            &get_tasks_help(Query(TaskQueryParams::default())).await.0,
-        )
-        .unwrap_or_default();
-        let ammo_help =
-            serde_json::to_string_pretty(&get_ammo_help(Query(AmmoQueryParams::default())).await.0)
-                .unwrap_or_default();
-
-        Ok(format!(
-            "{}\nitems default query params at /items\n{}\n\ntasks default query params at /tasks\n{}\n\nammo default query params at /ammo\n{}",
-            API_DOCUMENTATION, item_help, task_help, ammo_help
-        ))
-    }
 }
 
 pub trait Page: Send + Sync + Serialize + DeserializeOwned + Clone + 'static {
@@ -123,6 +144,7 @@ impl Page for Item {
             not_found_ids
         )
         .fetch_all(&mut *txn)
+        .instrument(db_span("Items by Ids"))
         .await
         .bad_sql("Items by Ids")?;
 
@@ -157,6 +179,7 @@ impl Page for ItemBase {
             not_found_ids
         )
         .fetch_all(pgpool)
+        .instrument(db_span("ItemBase by Ids"))
         .await
         .bad_sql("ItemBase by Ids")
     }
@@ -190,6 +213,7 @@ impl Page for Task {
             &not_found_ids
         )
         .fetch_all(&mut *txn)
+        .instrument(db_span("Tasks by Ids"))
         .await
         .bad_sql("Tasks by Ids")?;
 
@@ -223,6 +247,7 @@ impl Page for TaskBase {
             &not_found_ids
         )
         .fetch_all(pgpool)
+        .instrument(db_span("TaskBase by Ids"))
         .await
         .bad_sql("TaskBase by Ids")
     }
@@ -249,12 +274,14 @@ impl Page for Ammo {
         pgpool: &sqlx::PgPool,
         not_found_ids: &[String],
     ) -> Result<Vec<Self>, AppError> {
+        // the embedded item is only filled in by the ammo routes
         sqlx::query_as!(
             Ammo,
-            "SELECT * FROM Ammo WHERE item_id = ANY($1)",
+            r#"SELECT *, NULL::jsonb AS "item?: Box<AmmoItem>" FROM Ammo WHERE item_id = ANY($1)"#,
             &not_found_ids
         )
         .fetch_all(pgpool)
+        .instrument(db_span("Ammo by Ids"))
         .await
         .bad_sql("Ammo by Ids")
     }
@@ -276,6 +303,39 @@ impl Page for Ammo {
     }
 }
 
+impl Page for ArmorPiece {
+    async fn fetch_by_ids(
+        pgpool: &sqlx::PgPool,
+        not_found_ids: &[String],
+    ) -> Result<Vec<Self>, AppError> {
+        sqlx::query_as!(
+            ArmorPiece,
+            "SELECT * FROM Armor WHERE _id = ANY($1)",
+            not_found_ids
+        )
+        .fetch_all(pgpool)
+        .instrument(db_span("Armor by Ids"))
+        .await
+        .bad_sql("Armor by Ids")
+    }
+
+    fn id(&self) -> &str {
+        &self._id
+    }
+
+    fn get_app_state_timer(app_state: &AppState) -> Arc<RwLock<Instant>> {
+        app_state.next_armor_call_timer.clone()
+    }
+
+    fn unique_cache_key_prefix() -> char {
+        ARMOR_UNIQUE_CACHE_PREFIX
+    }
+
+    fn make_cache_key(id: &str) -> String {
+        Self::unique_cache_key_prefix().to_string() + id + "^"
+    }
+}
+
 pub async fn fetch_page_by_ids<T: Page + Cacheable>(
     app_state: &AppState,
     ids: Vec<String>,
@@ -330,6 +390,75 @@ async fn get_page_by_ids<T: Page + Cacheable>(
     Ok(Json(fetch_page_by_ids(&app_state, ids).await?))
 }
 
+// utoipa cannot document generic handlers so each page gets its own
+#[utoipa::path(
+    get,
+    path = "/ids",
+    tag = "items",
+    params(IdsQueryParams),
+    responses(
+        (status = 200, body = Vec<Item>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+async fn get_items_by_ids(
+    query: Query<IdsQueryParams>,
+    state: State<AppState>,
+) -> Result<Json<Vec<Item>>, AppError> {
+    get_page_by_ids(query, state).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/ids",
+    tag = "tasks",
+    params(IdsQueryParams),
+    responses(
+        (status = 200, body = Vec<Task>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+async fn get_tasks_by_ids(
+    query: Query<IdsQueryParams>,
+    state: State<AppState>,
+) -> Result<Json<Vec<Task>>, AppError> {
+    get_page_by_ids(query, state).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/ids",
+    tag = "ammo",
+    params(IdsQueryParams),
+    responses(
+        (status = 200, body = Vec<Ammo>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+async fn get_ammo_by_ids(
+    query: Query<IdsQueryParams>,
+    state: State<AppState>,
+) -> Result<Json<Vec<Ammo>>, AppError> {
+    get_page_by_ids(query, state).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/ids",
+    tag = "armor",
+    params(IdsQueryParams),
+    responses(
+        (status = 200, body = Vec<ArmorPiece>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+async fn get_armor_by_ids(
+    query: Query<IdsQueryParams>,
+    state: State<AppState>,
+) -> Result<Json<Vec<ArmorPiece>>, AppError> {
+    get_page_by_ids(query, state).await
+}
+
 pub struct Device(pub Option<Uuid>);
 
 impl<S> FromRequestParts<S> for Device
@@ -339,54 +468,215 @@ where
     type Rejection = AppError;
 
     async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, AppError> {
-        let device_id = parts
-            .headers
-            .get("x-device-id")
-            .and_then(|h| h.to_str().ok())
+        let Some(header) = parts.headers.get("x-device-id") else {
+            return Ok(Self(None));
+        };
+
+        let device_id = header
+            .to_str()
+            .ok()
             .and_then(|id_str| Uuid::parse_str(id_str).ok());
 
+        // a malformed device id is ignored like any other invalid param unless strict=true was passed
+        let strict = form_urlencoded::parse(parts.uri.query().unwrap_or_default().as_bytes())
+            .any(|(field, value)| field == "strict" && value == "true");
+        if device_id.is_none() && strict {
+            return Err(AppError::BadRequest(
+                "x-device-id must be a valid uuid".into(),
+            ));
+        }
+
         Ok(Self(device_id))
     }
 }
 
-fn items_router() -> Router<AppState> {
-    Router::new()
-        .route("/", get(get_items))
-        .route("/stats", get(item_stats))
-        .route("/history", get(get_item_history))
-        .route("/ids", get(get_page_by_ids::<Item>))
-        .route("/query_parms", get(get_device_item_query_parms))
-        .route("/help", get(get_items_help))
+impl IntoParams for Device {
+    fn into_params(_: impl Fn() -> Option<ParameterIn>) -> Vec<Parameter> {
+        vec![
+            ParameterBuilder::new()
+                .name("x-device-id")
+                .parameter_in(ParameterIn::Header)
+                .required(Required::False)
+                .description(Some("uuid used to save query params and completed tasks"))
+                .schema(Some(
+                    ObjectBuilder::new()
+                        .schema_type(Type::String)
+                        .format(Some(SchemaFormat::KnownFormat(KnownFormat::Uuid))),
+                ))
+                .build(),
+        ]
+    }
+}
+
+fn items_router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(item_routes::get_items))
+        .routes(routes!(item_routes::item_stats))
+        .routes(routes!(item_routes::get_item_history))
+        .routes(routes!(get_items_by_ids))
+        .routes(routes!(item_routes::get_device_item_query_parms))
+        .routes(routes!(item_routes::get_items_help))
 }
 
-fn tasks_router() -> Router<AppState> {
-    Router::new()
-        .route("/", get(get_tasks))
-        .route("/base", get(get_tasks_base))
-        .route("/stats", get(task_stats))
-        .route("/ids", get(get_page_by_ids::<Task>))
-        .route("/get_required_items", get(get_required_items))
-        .route("/adj_list", get(get_adj_list))
-        .route("/get_completed", get(get_completed_tasks))
-        .route("/set_complete", post(set_completed_task))
-        .route("/clear_completed_tasks", get(clear_completed_tasks))
-        .route("/query_parms", get(get_device_task_query_parms))
-        .route("/help", get(get_tasks_help))
+fn tasks_router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(task_routes::get_tasks))
+        .routes(routes!(task_routes::get_tasks_base))
+        .routes(routes!(task_routes::get_available_tasks))
+        .routes(routes!(task_routes::task_stats))
+        .routes(routes!(get_tasks_by_ids))
+        .routes(routes!(task_routes::get_required_items))
+        .routes(routes!(task_routes::get_task_path))
+        .routes(routes!(task_routes::get_adj_list))
+        .routes(routes!(task_routes::get_task_graph))
+        .routes(routes!(task_routes::get_completed_tasks))
+        .routes(routes!(task_routes::set_completed_task))
+        .routes(routes!(task_routes::clear_completed_tasks))
+        .routes(routes!(task_routes::get_device_task_query_parms))
+        .routes(routes!(task_routes::get_tasks_help))
 }
 
-fn ammo_router() -> Router<AppState> {
-    Router::new()
-        .route("/", get(get_ammo))
-        .route("/stats", get(ammo_stats))
-        .route("/ids", get(get_page_by_ids::<Ammo>))
-        .route("/query_parms", get(get_device_ammo_query_parms))
-        .route("/help", get(get_ammo_help))
+fn ammo_router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(ammo_routes::get_ammo))
+        .routes(routes!(ammo_routes::get_ammo_effectiveness))
+        .routes(routes!(ammo_routes::ammo_stats))
+        .routes(routes!(get_ammo_by_ids))
+        .routes(routes!(ammo_routes::get_device_ammo_query_parms))
+        .routes(routes!(ammo_routes::get_ammo_help))
 }
 
-pub fn api_router() -> Router<AppState> {
-    Router::new()
-        .route("/", get(health))
+fn weapons_router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(weapon_routes::get_weapons))
+        .routes(routes!(weapon_routes::get_calibers))
+        .routes(routes!(weapon_routes::get_weapon_ammo))
+}
+
+fn armor_router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(armor_routes::get_armor))
+        .routes(routes!(get_armor_by_ids))
+        .routes(routes!(armor_routes::get_armor_help))
+}
+
+fn admin_router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new().routes(routes!(task_routes::get_task_graph_issues))
+}
+
+// unknown routes get the same error body as everything else
+async fn route_not_found() -> AppError {
+    AppError::NotFound("Route does not exist see / for the available routes".into())
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "ItemsApp API"),
+    tags(
+        (name = "items", description = "Items along with their trader and flea prices"),
+        (name = "tasks", description = "Tasks and the completed tasks of a device"),
+        (name = "ammo", description = "Ammo stats"),
+        (name = "weapons", description = "Weapons and the ammo each of them fires"),
+        (name = "armor", description = "Body armor, plates and helmets"),
+        (name = "health", description = "Liveness, readiness and dataset freshness"),
+        (name = "admin", description = "Problems found in the ingested data"),
+        (name = "metrics", description = "Prometheus metrics")
+    )
+)]
+struct ApiDoc;
+
+// the original api which is also served without a prefix so the current frontend keeps working
+fn v1_router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(api_documentation))
         .nest("/items", items_router())
         .nest("/tasks", tasks_router())
         .nest("/ammo", ammo_router())
+        .nest("/weapons", weapons_router())
+        .nest("/armor", armor_router())
+}
+
+fn v2_items_router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(v2_routes::get_items_v2))
+        .routes(routes!(v2_routes::get_item_stats_v2))
+        .routes(routes!(v2_routes::get_items_by_ids_v2))
+        .routes(routes!(v2_routes::get_item_history_v2))
+        .routes(routes!(v2_routes::get_item_query_params_v2))
+}
+
+fn v2_tasks_router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(v2_routes::get_tasks_v2))
+        .routes(routes!(v2_routes::get_tasks_base_v2))
+        .routes(routes!(v2_routes::get_available_tasks_v2))
+        .routes(routes!(v2_routes::get_task_stats_v2))
+        .routes(routes!(v2_routes::get_tasks_by_ids_v2))
+        .routes(routes!(v2_routes::get_required_items_v2))
+        .routes(routes!(v2_routes::get_task_path_v2))
+        .routes(routes!(v2_routes::get_adj_list_v2))
+        .routes(routes!(v2_routes::get_task_graph_v2))
+        .routes(routes!(
+            v2_routes::get_completed_tasks_v2,
+            v2_routes::set_completed_task_v2,
+            v2_routes::clear_completed_tasks_v2
+        ))
+        .routes(routes!(v2_routes::get_task_query_params_v2))
+}
+
+fn v2_ammo_router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(v2_routes::get_ammo_v2))
+        .routes(routes!(v2_routes::get_ammo_effectiveness_v2))
+        .routes(routes!(v2_routes::get_ammo_stats_v2))
+        .routes(routes!(v2_routes::get_ammo_by_ids_v2))
+        .routes(routes!(v2_routes::get_ammo_query_params_v2))
+}
+
+fn v2_weapons_router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(v2_routes::get_weapons_v2))
+        .routes(routes!(v2_routes::get_calibers_v2))
+        .routes(routes!(v2_routes::get_weapon_ammo_v2))
+}
+
+fn v2_armor_router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .routes(routes!(v2_routes::get_armor_v2))
+        .routes(routes!(v2_routes::get_armor_by_ids_v2))
+}
+
+// object shaped responses and pagination envelopes
+fn v2_router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::new()
+        .nest("/items", v2_items_router())
+        .nest("/tasks", v2_tasks_router())
+        .nest("/ammo", v2_ammo_router())
+        .nest("/weapons", v2_weapons_router())
+        .nest("/armor", v2_armor_router())
+}
+
+// every route that gets served other than the docs themselves
+// routes must be added with routes! so they show up in the openapi spec
+// health, metrics and admin stay unversioned since probes and nginx rely on their paths
+pub fn documented_router() -> OpenApiRouter<AppState> {
+    OpenApiRouter::with_openapi(ApiDoc::openapi())
+        .routes(routes!(health_routes::healthz))
+        .routes(routes!(health_routes::readyz))
+        .routes(routes!(health_routes::health_details))
+        .routes(routes!(app_metrics::metrics_handler))
+        .nest("/admin", admin_router())
+        .merge(v1_router())
+        .nest("/v1", v1_router())
+        .nest("/v2", v2_router())
+}
+
+pub fn api_router() -> Router<AppState> {
+    let (router, openapi) = documented_router().split_for_parts();
+    let openapi_json = Json(openapi.clone());
+
+    router
+        .route("/openapi.json", get(move || async move { openapi_json }))
+        .merge(Scalar::with_url("/docs", openapi))
+        .fallback(route_not_found)
 }
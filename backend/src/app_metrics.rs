@@ -0,0 +1,102 @@
+use crate::init_app_state::AppState;
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::time::{Duration, Instant};
+
+const REQUEST_DURATION: &str = "http_request_duration_seconds";
+const INGESTION_DURATION: &str = "ingestion_duration_seconds";
+
+// most requests are cached so the buckets are skewed towards the low end
+const REQUEST_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+const INGESTION_BUCKETS: &[f64] = &[0.5, 1.0, 2.5, 5.0, 10.0, 20.0, 30.0, 60.0, 120.0];
+
+// installs the global recorder that every metrics macro in the app writes to
+pub fn install_recorder() -> anyhow::Result<PrometheusHandle> {
+    Ok(PrometheusBuilder::new()
+        .set_buckets_for_metric(Matcher::Full(REQUEST_DURATION.into()), REQUEST_BUCKETS)?
+        .set_buckets_for_metric(Matcher::Full(INGESTION_DURATION.into()), INGESTION_BUCKETS)?
+        .install_recorder()?)
+}
+
+// records latency and status of every request labeled by the route it matched
+pub async fn track_metrics(req: Request, next: Next) -> Response {
+    let start = Instant::now();
+    let path = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or_else(|| String::from("unmatched"), |x| x.as_str().to_owned());
+    let method = req.method().to_string();
+
+    let response = next.run(req).await;
+
+    let status = response.status().as_u16().to_string();
+    metrics::histogram!(REQUEST_DURATION, "method" => method.clone(), "path" => path.clone())
+        .record(start.elapsed());
+    metrics::counter!("http_requests_total", "method" => method, "path" => path, "status" => status)
+        .increment(1);
+
+    response
+}
+
+pub fn record_cache_lookup(hit: bool) {
+    metrics::counter!("app_cache_lookups_total", "result" => if hit { "hit" } else { "miss" })
+        .increment(1);
+}
+
+pub fn record_ingestion(page: &'static str, source: &'static str, duration: Duration, rows: usize) {
+    metrics::histogram!(INGESTION_DURATION, "dataset" => page, "source" => source).record(duration);
+
+    #[allow(clippy::cast_precision_loss)]
+    metrics::gauge!("ingestion_rows", "dataset" => page).set(rows as f64);
+}
+
+pub fn record_ingestion_failure(page: &'static str) {
+    metrics::counter!("ingestion_failures_total", "dataset" => page).increment(1);
+}
+
+#[allow(clippy::cast_precision_loss)]
+pub fn record_task_graph_issues(kind: &'static str, count: usize) {
+    metrics::gauge!("task_graph_issues", "kind" => kind).set(count as f64);
+}
+
+// gauges that only make sense as a point in time get sampled right before rendering
+#[allow(clippy::cast_precision_loss)]
+#[utoipa::path(
+    get,
+    path = "/metrics",
+    tag = "metrics",
+    responses((status = 200, body = String, content_type = "text/plain"))
+)]
+pub async fn metrics_handler(State(app_state): State<AppState>) -> String {
+    let pgpool = &app_state.pgpool;
+    metrics::gauge!("db_pool_connections").set(f64::from(pgpool.size()));
+    metrics::gauge!("db_pool_idle_connections").set(pgpool.num_idle() as f64);
+    metrics::gauge!("db_pool_max_connections")
+        .set(f64::from(pgpool.options().get_max_connections()));
+
+    metrics::gauge!("app_cache_entries").set(app_state.cache.entry_count() as f64);
+
+    for (page, timer) in [
+        ("items", &app_state.next_items_call_timer),
+        ("tasks", &app_state.next_tasks_call_timer),
+        ("ammo", &app_state.next_ammo_call_timer),
+        ("weapons", &app_state.next_weapons_call_timer),
+        ("armor", &app_state.next_armor_call_timer),
+    ] {
+        let secs = timer
+            .read()
+            .await
+            .saturating_duration_since(Instant::now())
+            .as_secs_f64();
+        metrics::gauge!("seconds_until_next_refresh", "dataset" => page).set(secs);
+    }
+
+    app_state.metrics.run_upkeep();
+    app_state.metrics.render()
+}
@@ -0,0 +1,265 @@
+use crate::database_types::{Ammo, ArmorPiece};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/*
+a rough model of how armor stops rounds based on the formulas the community has worked out
+armor classes 1 to 6 each stop rounds up to about class * 10 penetration power when undamaged,
+as durability drops the armor gets weaker and every hit takes durability away
+depending on the round and on how destructible the armor material is
+everything here is an estimate for comparing ammo against each other, not an exact simulation
+*/
+
+pub const MIN_ARMOR_CLASS: u8 = 1;
+pub const MAX_ARMOR_CLASS: u8 = 6;
+
+pub const THORAX_HP: f32 = 85.0;
+pub const HEAD_HP: f32 = 35.0;
+
+// anything that takes longer than this is treated as never getting through
+pub const MAX_SHOTS: u32 = 100;
+
+pub const VALID_ARMOR_MATERIALS: &[&str] = &[
+    "aramid",
+    "uhmwpe",
+    "combined",
+    "titan",
+    "aluminium",
+    "armored_steel",
+    "ceramic",
+    "glass",
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ArmorMaterial {
+    Aramid,
+    Uhmwpe,
+    Combined,
+    Titan,
+    Aluminium,
+    ArmoredSteel,
+    Ceramic,
+    Glass,
+}
+
+impl ArmorMaterial {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "aramid" => Some(Self::Aramid),
+            "uhmwpe" => Some(Self::Uhmwpe),
+            "combined" => Some(Self::Combined),
+            "titan" => Some(Self::Titan),
+            "aluminium" => Some(Self::Aluminium),
+            "armored_steel" => Some(Self::ArmoredSteel),
+            "ceramic" => Some(Self::Ceramic),
+            "glass" => Some(Self::Glass),
+            _ => None,
+        }
+    }
+
+    // how much durability the material loses per hit relative to the others
+    pub const fn destructibility(self) -> f32 {
+        match self {
+            Self::Aramid => 0.25,
+            Self::Uhmwpe => 0.45,
+            Self::Combined => 0.5,
+            Self::Titan => 0.55,
+            Self::Aluminium => 0.6,
+            Self::ArmoredSteel => 0.7,
+            Self::Ceramic | Self::Glass => 0.8,
+        }
+    }
+}
+
+// what a typical piece of armor of a class looks like, used when the caller does not say otherwise
+#[derive(Clone, Copy, Debug)]
+pub struct ArmorClass {
+    pub class: u8,
+    pub max_durability: f32,
+    pub material: ArmorMaterial,
+    // fraction of a round's damage that still goes through when it does not penetrate
+    pub blunt_throughput: f32,
+}
+
+pub const ARMOR_CLASSES: [ArmorClass; 6] = [
+    ArmorClass {
+        class: 1,
+        max_durability: 40.0,
+        material: ArmorMaterial::Aramid,
+        blunt_throughput: 0.4,
+    },
+    ArmorClass {
+        class: 2,
+        max_durability: 50.0,
+        material: ArmorMaterial::Aramid,
+        blunt_throughput: 0.33,
+    },
+    ArmorClass {
+        class: 3,
+        max_durability: 50.0,
+        material: ArmorMaterial::Aramid,
+        blunt_throughput: 0.28,
+    },
+    ArmorClass {
+        class: 4,
+        max_durability: 65.0,
+        material: ArmorMaterial::Uhmwpe,
+        blunt_throughput: 0.24,
+    },
+    ArmorClass {
+        class: 5,
+        max_durability: 70.0,
+        material: ArmorMaterial::Ceramic,
+        blunt_throughput: 0.17,
+    },
+    ArmorClass {
+        class: 6,
+        max_durability: 85.0,
+        material: ArmorMaterial::Combined,
+        blunt_throughput: 0.14,
+    },
+];
+
+#[derive(Clone, Copy, Debug)]
+pub struct Armor {
+    pub class: ArmorClass,
+    pub durability: f32,
+}
+
+impl Armor {
+    // None when the class is not one of 1 to 6
+    pub fn new(
+        class: u8,
+        durability: Option<f32>,
+        max_durability: Option<f32>,
+        material: Option<ArmorMaterial>,
+    ) -> Option<Self> {
+        let mut class = *ARMOR_CLASSES.iter().find(|x| x.class == class)?;
+        if let Some(max_durability) = max_durability {
+            class.max_durability = max_durability.max(1.0);
+        }
+        if let Some(material) = material {
+            class.material = material;
+        }
+
+        Some(Self {
+            durability: durability
+                .unwrap_or(class.max_durability)
+                .clamp(0.0, class.max_durability),
+            class,
+        })
+    }
+
+    // a piece from the armor table, None when it has no class like a rig with empty plate slots
+    pub fn from_piece(piece: &ArmorPiece, durability: Option<f32>) -> Option<Self> {
+        let mut armor = Self::new(
+            u8::try_from(piece.armor_class).ok()?,
+            durability,
+            Some(piece.durability),
+            ArmorMaterial::from_name(&piece.material),
+        )?;
+        armor.class.blunt_throughput = piece.blunt_throughput;
+
+        Some(armor)
+    }
+
+    // the penetration power the armor holds up against at a given durability
+    fn armor_value(&self, durability: f32) -> f32 {
+        let durability_percent = 100.0 * durability / self.class.max_durability;
+        (121.0 - 5000.0 / 2.0f32.mul_add(durability_percent, 45.0))
+            * f32::from(self.class.class)
+            * 10.0
+            / 100.0
+    }
+
+    pub fn penetration_chance(&self, penetration_power: f32, durability: f32) -> f32 {
+        let armor = self.armor_value(durability);
+        let chance = if armor >= penetration_power + 15.0 {
+            0.0
+        } else if armor >= penetration_power {
+            0.4 * (armor - penetration_power - 15.0).powi(2) / 100.0
+        } else {
+            (100.0 + penetration_power / 0.9f32.mul_add(armor, -penetration_power)) / 100.0
+        };
+
+        chance.clamp(0.0, 1.0)
+    }
+
+    // durability taken off by one projectile, penetrating or not
+    fn durability_loss(&self, ammo: &Ammo) -> f32 {
+        #[allow(clippy::cast_precision_loss)]
+        let penetration_power = ammo.penetration_power as f32;
+        #[allow(clippy::cast_precision_loss)]
+        let armor_damage = ammo.armor_damage as f32 / 100.0;
+        let class_power = f32::from(self.class.class) * 10.0;
+
+        penetration_power
+            * armor_damage
+            * self.class.material.destructibility()
+            * (penetration_power / class_power).clamp(0.6, 1.1)
+    }
+
+    /*
+    works with expected values shot by shot instead of simulating random rolls
+    so the same ammo and armor always give the same answer
+    shots_to_penetrate is the expected number of shots up to the first penetration
+    and the shots to kill are when the expected damage dealt reaches the hp of that part
+    */
+    pub fn effectiveness(&self, ammo: &Ammo) -> AmmoEffectiveness {
+        #[allow(clippy::cast_precision_loss)]
+        let (penetration_power, damage, projectiles) = (
+            ammo.penetration_power as f32,
+            ammo.damage as f32,
+            ammo.projectile_count.max(1) as f32,
+        );
+
+        let penetration_chance = self.penetration_chance(penetration_power, self.durability);
+
+        let mut durability = self.durability;
+        let mut not_penetrated = 1.0;
+        let mut shots_to_penetrate = 0.0;
+        let mut damage_dealt = 0.0;
+        let mut thorax_shots_to_kill = None;
+        let mut head_shots_to_kill = None;
+
+        for shot in 1..=MAX_SHOTS {
+            let chance = self.penetration_chance(penetration_power, durability);
+
+            // the chance this shot is reached without a penetration so far adds up to the expected count
+            shots_to_penetrate += not_penetrated;
+            not_penetrated *= (1.0 - chance).powf(projectiles);
+
+            damage_dealt +=
+                projectiles * damage * (1.0 - chance).mul_add(self.class.blunt_throughput, chance);
+            if head_shots_to_kill.is_none() && damage_dealt >= HEAD_HP {
+                head_shots_to_kill = Some(shot);
+            }
+            if thorax_shots_to_kill.is_none() && damage_dealt >= THORAX_HP {
+                thorax_shots_to_kill = Some(shot);
+            }
+
+            durability = projectiles
+                .mul_add(-self.durability_loss(ammo), durability)
+                .max(0.0);
+        }
+
+        AmmoEffectiveness {
+            ammo: ammo.clone(),
+            penetration_chance,
+            shots_to_penetrate,
+            thorax_shots_to_kill,
+            head_shots_to_kill,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct AmmoEffectiveness {
+    pub ammo: Ammo,
+    // chance for one projectile of the first shot to penetrate
+    pub penetration_chance: f32,
+    pub shots_to_penetrate: f32,
+    // None when the round does not get there within MAX_SHOTS shots
+    pub thorax_shots_to_kill: Option<u32>,
+    pub head_shots_to_kill: Option<u32>,
+}
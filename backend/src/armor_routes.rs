@@ -0,0 +1,254 @@
+use crate::api_routers::fetch_page_by_ids;
+use crate::database_types::ArmorPiece;
+use crate::filters::{list_cache_key, push_range, range_cache_key, text_cache_key};
+use crate::init_app_state::{ARMOR_UNIQUE_CACHE_PREFIX, AppState};
+use crate::pagination::{
+    Cursor, cached_count, push_keyset_condition, push_order_by, resolve_keyset, sort_to_string,
+};
+use crate::query_types::{AppError, AppErrorHandling, ArmorPieceQueryParams, ErrorBody};
+use crate::telemetry::db_span;
+use crate::validation::{ARMOR_FIELD_RULES, validate_strict};
+use axum::{
+    Json,
+    extract::{RawQuery, State},
+};
+use axum_extra::extract::Query;
+use sqlx::{Postgres, QueryBuilder};
+use tracing::Instrument;
+
+// armor type, material, zones, every stat range and the search, count_armor caches on this alone
+// since sorting and paging never change how many pieces there are
+pub fn armor_filters_cache_key(query_parms: &ArmorPieceQueryParams) -> String {
+    format!(
+        "{}m{}z{}r{}r{}r{}r{}r{}r{}r{}r{}s{}",
+        text_cache_key(&query_parms.armor_type),
+        text_cache_key(&query_parms.material),
+        list_cache_key(&query_parms.zone),
+        range_cache_key(query_parms.min_armor_class, query_parms.max_armor_class),
+        range_cache_key(query_parms.min_durability, query_parms.max_durability),
+        range_cache_key(
+            query_parms.min_blunt_throughput,
+            query_parms.max_blunt_throughput
+        ),
+        range_cache_key(
+            query_parms.min_ricochet_chance,
+            query_parms.max_ricochet_chance
+        ),
+        range_cache_key(query_parms.min_speed_penalty, query_parms.max_speed_penalty),
+        range_cache_key(query_parms.min_turn_penalty, query_parms.max_turn_penalty),
+        range_cache_key(query_parms.min_ergo_penalty, query_parms.max_ergo_penalty),
+        range_cache_key(query_parms.min_weight, query_parms.max_weight),
+        text_cache_key(&query_parms.search),
+    )
+}
+
+// search matches either name, type and material match exactly and a piece passes the zone
+// filter when it covers any one of the zones asked for
+fn push_armor_filters(qb: &mut QueryBuilder<'_, Postgres>, query_parms: &ArmorPieceQueryParams) {
+    let search = &query_parms.search;
+    qb.push("WHERE (")
+        .push_bind(search.clone())
+        .push(" = '' OR armor_name ILIKE ")
+        .push_bind(format!("%{search}%"))
+        .push(" OR short_name ILIKE ")
+        .push_bind(format!("%{search}%"))
+        .push(") ");
+
+    if !query_parms.armor_type.is_empty() {
+        qb.push("AND armor_type = ")
+            .push_bind(query_parms.armor_type.to_lowercase())
+            .push(" ");
+    }
+
+    if !query_parms.material.is_empty() {
+        qb.push("AND material = ")
+            .push_bind(query_parms.material.to_lowercase())
+            .push(" ");
+    }
+
+    // zone names are matched without case since the api capitalizes them
+    if !query_parms.zone.is_empty() {
+        qb.push("AND EXISTS (SELECT 1 FROM UNNEST(zones) z WHERE LOWER(z) = ANY(")
+            .push_bind(
+                query_parms
+                    .zone
+                    .iter()
+                    .map(|x| x.to_lowercase())
+                    .collect::<Vec<String>>(),
+            )
+            .push(")) ");
+    }
+
+    push_range(
+        qb,
+        "armor_class",
+        query_parms.min_armor_class,
+        query_parms.max_armor_class,
+    );
+
+    for (column, min, max) in [
+        (
+            "durability",
+            query_parms.min_durability,
+            query_parms.max_durability,
+        ),
+        (
+            "blunt_throughput",
+            query_parms.min_blunt_throughput,
+            query_parms.max_blunt_throughput,
+        ),
+        (
+            "ricochet_chance",
+            query_parms.min_ricochet_chance,
+            query_parms.max_ricochet_chance,
+        ),
+        (
+            "speed_penalty",
+            query_parms.min_speed_penalty,
+            query_parms.max_speed_penalty,
+        ),
+        (
+            "turn_penalty",
+            query_parms.min_turn_penalty,
+            query_parms.max_turn_penalty,
+        ),
+        (
+            "ergo_penalty",
+            query_parms.min_ergo_penalty,
+            query_parms.max_ergo_penalty,
+        ),
+        ("weight", query_parms.min_weight, query_parms.max_weight),
+    ] {
+        push_range(qb, column, min, max);
+    }
+}
+
+// how many pieces match, v2 reports it as the total next to each armor page
+pub async fn count_armor(
+    app_state: &AppState,
+    query_parms: &ArmorPieceQueryParams,
+) -> Result<i64, AppError> {
+    let cache_key = format!(
+        "{}count{}",
+        ARMOR_UNIQUE_CACHE_PREFIX,
+        armor_filters_cache_key(query_parms),
+    );
+
+    let mut qb: QueryBuilder<'_, Postgres> = QueryBuilder::new("SELECT COUNT(*) FROM Armor ");
+    push_armor_filters(&mut qb, query_parms);
+
+    cached_count(
+        app_state,
+        cache_key,
+        ARMOR_UNIQUE_CACHE_PREFIX,
+        qb,
+        "Armor Count",
+    )
+    .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "armor",
+    params(ArmorPieceQueryParams),
+    responses(
+        (status = 200, body = Vec<ArmorPiece>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_armor(
+    Query(query_parms): Query<ArmorPieceQueryParams>,
+    RawQuery(raw_query): RawQuery,
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<ArmorPiece>>, AppError> {
+    let sort = query_parms.sort_keys();
+    let ArmorPieceQueryParams {
+        limit,
+        offset,
+        cursor,
+        strict,
+        ..
+    } = query_parms.clone();
+
+    if strict {
+        validate_strict(raw_query.as_deref(), &query_parms, ARMOR_FIELD_RULES)?;
+    }
+
+    let filters_cache_key = armor_filters_cache_key(&query_parms);
+    let cursor = cursor
+        .filter(|c| !c.is_empty())
+        .map(|c| Cursor::decode(&c, &sort, &filters_cache_key))
+        .transpose()?;
+    let offset = cursor.as_ref().map_or(offset, |c| c.offset);
+
+    let page_cache_key = |offset: u32| {
+        format!(
+            "{}{}{}l{}o{}",
+            ARMOR_UNIQUE_CACHE_PREFIX,
+            sort_to_string(&sort),
+            filters_cache_key,
+            limit,
+            offset,
+        )
+    };
+    let cache_key = page_cache_key(offset);
+
+    if let Some(values) = app_state.cache.get_vec(&cache_key) {
+        return Ok(Json(values));
+    }
+
+    let prev_cache_key = (offset >= limit).then(|| page_cache_key(offset - limit));
+    let keyset = resolve_keyset::<ArmorPiece>(cursor, &app_state.cache, prev_cache_key, &sort);
+
+    let mut qb: QueryBuilder<'_, Postgres> = QueryBuilder::new("SELECT * FROM Armor ");
+    push_armor_filters(&mut qb, &query_parms);
+
+    let successful_keyset = keyset.is_some();
+    if let Some(keyset) = keyset {
+        push_keyset_condition(&mut qb, &sort, str::to_string, "_id", keyset);
+    }
+
+    push_order_by(&mut qb, &sort, str::to_string, "_id");
+    qb.push("LIMIT ").push_bind(i64::from(limit));
+
+    if !successful_keyset {
+        qb.push(" OFFSET ").push_bind(i64::from(offset));
+    }
+
+    let armor: Vec<ArmorPiece> = qb
+        .build_query_as()
+        .fetch_all(&app_state.pgpool)
+        .instrument(db_span("Armor"))
+        .await
+        .bad_sql("Armor")?;
+
+    let tokio_values = armor.clone();
+    tokio::spawn(async move {
+        app_state
+            .cache
+            .insert_vec(cache_key, tokio_values, ARMOR_UNIQUE_CACHE_PREFIX);
+    });
+
+    Ok(Json(armor))
+}
+
+pub async fn get_armor_piece(app_state: &AppState, id: &str) -> Result<ArmorPiece, AppError> {
+    fetch_page_by_ids::<ArmorPiece>(app_state, vec![id.to_string()])
+        .await?
+        .pop()
+        .ok_or_else(|| AppError::NotFound(format!("Armor {id} does not exist")))
+}
+
+#[utoipa::path(
+    get,
+    path = "/help",
+    tag = "armor",
+    params(ArmorPieceQueryParams),
+    responses((status = 200, body = ArmorPieceQueryParams))
+)]
+pub async fn get_armor_help(
+    Query(query_parms): Query<ArmorPieceQueryParams>,
+) -> Json<ArmorPieceQueryParams> {
+    Json(query_parms)
+}
@@ -1,5 +1,10 @@
-use crate::database_types::{Ammo, Item, ItemBase, SavedItemData, Task, TaskBase};
-use crate::query_types::AdjList;
+use crate::app_metrics::record_cache_lookup;
+use crate::armor::AmmoEffectiveness;
+use crate::database_types::{
+    Ammo, ArmorPiece, Item, ItemBase, SavedItemData, Task, TaskBase, Weapon,
+};
+use crate::query_types::{AdjList, TaskPath};
+use crate::task_graph::TaskGraph;
 use crate::task_routes::GrabIds;
 use dashmap::DashMap;
 use std::sync::Arc;
@@ -86,6 +91,11 @@ define_cache_types! {
     Item(Item),
     ItemBase(ItemBase),
     Ammo(Ammo),
+    AmmoEffectiveness(AmmoEffectiveness),
+    Weapon(Weapon),
+    ArmorPiece(ArmorPiece),
+    TaskPath(TaskPath),
+    TaskGraph(TaskGraph),
     Task(Task),
     TaskBase(TaskBase),
     SavedItemData(SavedItemData),
@@ -140,21 +150,31 @@ impl AppCache {
     where
         T: Cacheable + Clone + Send + Sync,
     {
-        let value = self.cache.get(key)?;
-        match value.value() {
+        let value = self.cache.get(key).and_then(|value| match value.value() {
             CacheValue::One(v) => T::from_cache_type(v),
             CacheValue::Vec(_) => None,
-        }
+        });
+
+        // an entry of the wrong type counts as a miss
+        record_cache_lookup(value.is_some());
+        value
     }
 
     pub fn get_vec<T>(&self, key: &str) -> Option<Vec<T>>
     where
         T: Cacheable + Clone + Send + Sync,
     {
-        match self.cache.get(key)?.value() {
+        let value = self.cache.get(key).and_then(|value| match value.value() {
             CacheValue::Vec(values) => values.iter().map(T::from_cache_type).collect(),
             CacheValue::One(_) => None,
-        }
+        });
+
+        record_cache_lookup(value.is_some());
+        value
+    }
+
+    pub fn entry_count(&self) -> usize {
+        self.cache.len()
     }
 
     pub fn invalidate_cache_prefix(&self, cache_prefix: char) {
@@ -0,0 +1,308 @@
+use crate::database_types::{self, ItemFromDB, TaskFromDB};
+use crate::deserialize_json_types::{Ammo, Armor, Item, Task, Weapon};
+use crate::init_app_state::{
+    AMMO_FILE, ARMOR_FILE, DEVICE_PREFERENCES_MAX_AGE, ITEMS_FILE, TASKS_FILE, WEAPONS_FILE,
+    connect_db, prune_devices, table_counts,
+};
+use crate::item_routes::items_from_db_to_items;
+use crate::snapshots::replay_snapshot;
+use crate::task_routes::tasks_from_db_to_tasks;
+use crate::upsert::Upsert;
+use anyhow::{Result, anyhow, bail};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::io::Write;
+use std::str::FromStr;
+use std::time::Duration;
+
+pub const USAGE: &str = r"usage:
+backend [serve]
+    starts the server
+backend migrate
+    runs any migrations that have not been applied
+backend import --dataset <items|tasks|ammo|weapons|armor> --file <path>
+    upserts a file in the same format as the most_recent_*.json files
+backend export --dataset <items|tasks|ammo|weapons|armor> [--file <path>]
+    writes the dataset as it is served by the api to a file or stdout,
+    this is not the most_recent_*.json format so an export can not be imported again
+backend refresh --dataset <items|tasks|ammo|weapons|armor>
+    pulls the dataset from the api and upserts it
+backend replay --dataset <items|tasks|ammo|weapons|armor> [--snapshot <path|latest>]
+    upserts an archived api response into the database, defaults to the latest snapshot
+backend prune-devices [--older-than <30d|12h|90m|60s>]
+    deletes the preferences of devices that have not visited recently, defaults to 30d
+backend check
+    verifies the database is reachable and every dataset has rows
+";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dataset {
+    Items,
+    Tasks,
+    Ammo,
+    Weapons,
+    Armor,
+}
+
+impl FromStr for Dataset {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_lowercase().as_str() {
+            "items" => Ok(Self::Items),
+            "tasks" => Ok(Self::Tasks),
+            "ammo" => Ok(Self::Ammo),
+            "weapons" => Ok(Self::Weapons),
+            "armor" => Ok(Self::Armor),
+            _ => Err(anyhow!(
+                "unknown dataset {s} expected one of items, tasks, ammo, weapons, armor"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Serve,
+    Migrate,
+    Import {
+        dataset: Dataset,
+        file: String,
+    },
+    Export {
+        dataset: Dataset,
+        file: Option<String>,
+    },
+    Refresh {
+        dataset: Dataset,
+    },
+    Replay {
+        dataset: Dataset,
+        snapshot: String,
+    },
+    PruneDevices {
+        older_than: Duration,
+    },
+    Check,
+}
+
+// grabs the value that comes after a --flag
+fn flag_value(args: &[String], flag: &str) -> Option<String> {
+    args.iter()
+        .position(|x| x == flag)
+        .and_then(|i| args.get(i + 1))
+        .cloned()
+}
+
+fn required_flag(args: &[String], flag: &str) -> Result<String> {
+    flag_value(args, flag).ok_or_else(|| anyhow!("{} requires {flag}\n{USAGE}", args[0]))
+}
+
+// parses durations like 30d 12h 90m or 60s, zero is rejected since pruning devices
+// older than nothing would delete every device
+pub fn parse_duration(s: &str) -> Result<Duration> {
+    let split = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (amount, unit) = s.split_at(split);
+    let amount: u64 = amount
+        .parse()
+        .map_err(|_| anyhow!("invalid duration {s} expected something like 30d"))?;
+
+    let multiplier = match unit {
+        "d" => 3600 * 24,
+        "h" => 3600,
+        "m" => 60,
+        "s" | "" => 1,
+        _ => bail!("invalid duration unit {unit} expected one of d, h, m, s"),
+    };
+    let secs = amount
+        .checked_mul(multiplier)
+        .ok_or_else(|| anyhow!("duration {s} is too large"))?;
+    if secs == 0 {
+        bail!("duration {s} must be greater than zero");
+    }
+
+    Ok(Duration::from_secs(secs))
+}
+
+pub fn parse_args(args: &[String]) -> Result<Command> {
+    let Some(subcommand) = args.first() else {
+        return Ok(Command::Serve);
+    };
+
+    match subcommand.as_str() {
+        "serve" => Ok(Command::Serve),
+        "migrate" => Ok(Command::Migrate),
+        "import" => Ok(Command::Import {
+            dataset: required_flag(args, "--dataset")?.parse()?,
+            file: required_flag(args, "--file")?,
+        }),
+        "export" => Ok(Command::Export {
+            dataset: required_flag(args, "--dataset")?.parse()?,
+            file: flag_value(args, "--file"),
+        }),
+        "refresh" => Ok(Command::Refresh {
+            dataset: required_flag(args, "--dataset")?.parse()?,
+        }),
+        "replay" => Ok(Command::Replay {
+            dataset: required_flag(args, "--dataset")?.parse()?,
+            snapshot: flag_value(args, "--snapshot").unwrap_or_else(|| "latest".into()),
+        }),
+        "prune-devices" => Ok(Command::PruneDevices {
+            older_than: flag_value(args, "--older-than")
+                .map(|x| parse_duration(&x))
+                .transpose()?
+                .unwrap_or(DEVICE_PREFERENCES_MAX_AGE),
+        }),
+        "check" => Ok(Command::Check),
+        "help" | "--help" | "-h" => bail!("{USAGE}"),
+        _ => bail!("unknown subcommand {subcommand}\n{USAGE}"),
+    }
+}
+
+async fn export_dataset(dataset: Dataset, pgpool: &PgPool) -> Result<String> {
+    let json = match dataset {
+        Dataset::Items => {
+            let mut txn = pgpool.begin().await?;
+            let items_from_db = sqlx::query_as::<_, ItemFromDB>("SELECT * FROM Item ORDER BY _id")
+                .fetch_all(&mut *txn)
+                .await?;
+            let items = items_from_db_to_items(items_from_db, txn)
+                .await
+                .map_err(|e| anyhow!("{e:?}"))?;
+            serde_json::to_string_pretty(&items)?
+        }
+        Dataset::Tasks => {
+            let mut txn = pgpool.begin().await?;
+            let tasks_from_db = sqlx::query_as::<_, TaskFromDB>("SELECT * FROM Task ORDER BY _id")
+                .fetch_all(&mut *txn)
+                .await?;
+            let tasks = tasks_from_db_to_tasks(tasks_from_db, txn)
+                .await
+                .map_err(|e| anyhow!("{e:?}"))?;
+            serde_json::to_string_pretty(&tasks)?
+        }
+        Dataset::Ammo => {
+            let ammo =
+                sqlx::query_as::<_, database_types::Ammo>("SELECT * FROM Ammo ORDER BY item_id")
+                    .fetch_all(pgpool)
+                    .await?;
+            serde_json::to_string_pretty(&ammo)?
+        }
+        Dataset::Weapons => {
+            let weapons =
+                sqlx::query_as::<_, database_types::Weapon>("SELECT * FROM Weapon ORDER BY _id")
+                    .fetch_all(pgpool)
+                    .await?;
+            serde_json::to_string_pretty(&weapons)?
+        }
+        Dataset::Armor => {
+            let armor =
+                sqlx::query_as::<_, database_types::ArmorPiece>("SELECT * FROM Armor ORDER BY _id")
+                    .fetch_all(pgpool)
+                    .await?;
+            serde_json::to_string_pretty(&armor)?
+        }
+    };
+
+    Ok(json)
+}
+
+#[derive(Serialize)]
+struct CheckReport {
+    items_count: i64,
+    tasks_count: i64,
+    ammo_count: i64,
+    weapons_count: i64,
+    armor_count: i64,
+}
+
+// runs every command that does not need the http server
+pub async fn run_command(command: Command, postgres_url: &str) -> Result<()> {
+    if command == Command::Serve {
+        return Ok(());
+    }
+
+    let pgpool = connect_db(postgres_url).await?;
+
+    match command {
+        Command::Serve => {}
+        Command::Migrate => {
+            // connect_db already ran the migrations
+            println!("migrations are up to date");
+        }
+        Command::Import { dataset, file } => {
+            match dataset {
+                Dataset::Items => Item::file_upsert(&file, &pgpool).await,
+                Dataset::Tasks => Task::file_upsert(&file, &pgpool).await,
+                Dataset::Ammo => Ammo::file_upsert(&file, &pgpool).await,
+                Dataset::Weapons => Weapon::file_upsert(&file, &pgpool).await,
+                Dataset::Armor => Armor::file_upsert(&file, &pgpool).await,
+            }
+            .map_err(|e| anyhow!("import failed with error {e}"))?;
+
+            println!("imported {dataset:?} from {file}");
+        }
+        Command::Export { dataset, file } => {
+            let json = export_dataset(dataset, &pgpool).await?;
+            if let Some(file) = file {
+                std::fs::write(&file, json)?;
+                println!("exported {dataset:?} to {file}");
+            } else {
+                std::io::stdout().write_all(json.as_bytes())?;
+            }
+        }
+        Command::Refresh { dataset } => {
+            match dataset {
+                Dataset::Items => Item::api_upsert(ITEMS_FILE, &pgpool).await,
+                Dataset::Tasks => Task::api_upsert(TASKS_FILE, &pgpool).await,
+                Dataset::Ammo => Ammo::api_upsert(AMMO_FILE, &pgpool).await,
+                Dataset::Weapons => Weapon::api_upsert(WEAPONS_FILE, &pgpool).await,
+                Dataset::Armor => Armor::api_upsert(ARMOR_FILE, &pgpool).await,
+            }
+            .map_err(|e| anyhow!("refresh failed with error {e}"))?;
+
+            println!("refreshed {dataset:?} from the api");
+        }
+        Command::Replay { dataset, snapshot } => {
+            let count = match dataset {
+                Dataset::Items => replay_snapshot::<Item>(&snapshot, &pgpool).await,
+                Dataset::Tasks => replay_snapshot::<Task>(&snapshot, &pgpool).await,
+                Dataset::Ammo => replay_snapshot::<Ammo>(&snapshot, &pgpool).await,
+                Dataset::Weapons => replay_snapshot::<Weapon>(&snapshot, &pgpool).await,
+                Dataset::Armor => replay_snapshot::<Armor>(&snapshot, &pgpool).await,
+            }
+            .map_err(|e| anyhow!("replay failed with error {e}"))?;
+
+            println!("replayed {count} {dataset:?} entries from {snapshot}");
+        }
+        Command::PruneDevices { older_than } => {
+            let rows_affected = prune_devices(&pgpool, older_than).await?;
+            println!("deleted {rows_affected} inactive devices");
+        }
+        Command::Check => {
+            let (items_count, tasks_count, ammo_count, weapons_count, armor_count) =
+                table_counts(&pgpool).await?;
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&CheckReport {
+                    items_count,
+                    tasks_count,
+                    ammo_count,
+                    weapons_count,
+                    armor_count,
+                })?
+            );
+
+            if items_count == 0
+                || tasks_count == 0
+                || ammo_count == 0
+                || weapons_count == 0
+                || armor_count == 0
+            {
+                bail!("The Database has not yet been initalized");
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,9 +1,14 @@
-use crate::query_types::VALID_ITEM_SORT_BY;
-use serde::{Deserialize, Serialize};
-use sqlx::types::Uuid;
+use crate::pagination::{SortKey, TASK_CURSOR_SORT_BY};
+use crate::query_types::{VALID_AMMO_SORT_BY, VALID_ARMOR_SORT_BY, VALID_ITEM_SORT_BY};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::Postgres;
+use sqlx::error::BoxDynError;
+use sqlx::postgres::{PgTypeInfo, PgValueRef};
 use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::{Json, Uuid};
+use utoipa::ToSchema;
 
-#[derive(Serialize, Deserialize, sqlx::FromRow, Clone, sqlx::Type)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, Clone, sqlx::Type, ToSchema)]
 pub struct BuyFor {
     #[serde(skip)]
     pub id: i32,
@@ -16,7 +21,7 @@ pub struct BuyFor {
     pub item_id: String,
 }
 
-#[derive(Serialize, Deserialize, sqlx::FromRow, Clone, sqlx::Type)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, Clone, sqlx::Type, ToSchema)]
 pub struct SellFor {
     #[serde(skip)]
     pub id: i32,
@@ -46,7 +51,7 @@ pub struct ItemFromDB {
     pub is_flea: bool,
 }
 
-#[derive(Serialize, Deserialize, sqlx::FromRow, Clone)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, Clone, ToSchema)]
 pub struct Item {
     pub _id: String,
     pub item_name: String,
@@ -66,15 +71,44 @@ pub struct Item {
     pub sells: Vec<SellFor>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum FieldValue {
     String(String),
     I32(i32),
     Float(f32),
+    Bool(bool),
 }
 
-impl Item {
-    pub fn struct_data_from_str(&self, name: &str) -> Option<FieldValue> {
+// the sort values and id of a row which the next page starts after
+pub type KeysetOffset = (Vec<FieldValue>, String);
+
+// anything paged with keyset pagination, the id breaks ties so every row has a stable position
+pub trait Keyset {
+    const VALID_SORT_BY: &'static [&'static str];
+
+    fn struct_data_from_str(&self, name: &str) -> Option<FieldValue>;
+
+    fn keyset_id(&self) -> &str;
+
+    fn get_keyset_offset(&self, sort: &[SortKey]) -> Option<KeysetOffset> {
+        let values = sort
+            .iter()
+            .map(|key| {
+                let column = Self::VALID_SORT_BY
+                    .iter()
+                    .find(|x| x.eq_ignore_ascii_case(&key.column))?;
+                self.struct_data_from_str(column)
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some((values, self.keyset_id().to_string()))
+    }
+}
+
+impl Keyset for Item {
+    const VALID_SORT_BY: &'static [&'static str] = VALID_ITEM_SORT_BY;
+
+    fn struct_data_from_str(&self, name: &str) -> Option<FieldValue> {
         match name.to_ascii_lowercase().as_str() {
             "_id" => Some(FieldValue::String(self._id.clone())),
             "item_name" => Some(FieldValue::String(self.item_name.clone())),
@@ -90,30 +124,86 @@ impl Item {
                 Some(FieldValue::I32(self.buy_from_trader_instant_profit))
             }
             "per_slot" => Some(FieldValue::I32(self.per_slot)),
+            // flea_market sorts on the joined BuyFor price
+            "flea_market" => self
+                .buys
+                .iter()
+                .find(|x| x.trader_name.eq_ignore_ascii_case("flea market"))
+                .map(|x| FieldValue::I32(x.price_rub)),
             _ => None,
         }
     }
 
-    pub fn get_keyset_offset(&self, sort_by: &str) -> Option<(FieldValue, String)> {
-        if sort_by.eq_ignore_ascii_case("flea_market") {
-            let val = self
-                .buys
-                .iter()
-                .find(|x| x.trader_name.eq_ignore_ascii_case("flea market"))?;
-            return Some((FieldValue::I32(val.price_rub), self._id.clone()));
+    fn keyset_id(&self) -> &str {
+        &self._id
+    }
+}
+
+impl Keyset for Task {
+    const VALID_SORT_BY: &'static [&'static str] = &[TASK_CURSOR_SORT_BY];
+
+    fn struct_data_from_str(&self, name: &str) -> Option<FieldValue> {
+        name.eq_ignore_ascii_case("_id")
+            .then(|| FieldValue::String(self._id.clone()))
+    }
+
+    fn keyset_id(&self) -> &str {
+        &self._id
+    }
+}
+
+impl Keyset for TaskBase {
+    const VALID_SORT_BY: &'static [&'static str] = &[TASK_CURSOR_SORT_BY];
+
+    fn struct_data_from_str(&self, name: &str) -> Option<FieldValue> {
+        name.eq_ignore_ascii_case("_id")
+            .then(|| FieldValue::String(self._id.clone()))
+    }
+
+    fn keyset_id(&self) -> &str {
+        &self._id
+    }
+}
+
+impl Keyset for Ammo {
+    const VALID_SORT_BY: &'static [&'static str] = VALID_AMMO_SORT_BY;
+
+    fn struct_data_from_str(&self, name: &str) -> Option<FieldValue> {
+        match name.to_ascii_lowercase().as_str() {
+            "accuracy_modifier" => Some(FieldValue::Float(self.accuracy_modifier)),
+            "ammo_type" => Some(FieldValue::String(self.ammo_type.clone())),
+            "caliber" => Some(FieldValue::String(self.caliber.clone())),
+            "armor_damage" => Some(FieldValue::I32(self.armor_damage)),
+            "fragmentation_chance" => Some(FieldValue::Float(self.fragmentation_chance)),
+            "damage" => Some(FieldValue::I32(self.damage)),
+            "heavy_bleed_modifier" => Some(FieldValue::Float(self.heavy_bleed_modifier)),
+            "initial_speed" => Some(FieldValue::Float(self.initial_speed)),
+            "light_bleed_modifier" => Some(FieldValue::Float(self.light_bleed_modifier)),
+            "penetration_chance" => Some(FieldValue::Float(self.penetration_chance)),
+            "penetration_power" => Some(FieldValue::I32(self.penetration_power)),
+            "penetration_power_deviation" => {
+                Some(FieldValue::Float(self.penetration_power_deviation))
+            }
+            "projectile_count" => Some(FieldValue::I32(self.projectile_count)),
+            "recoil_modifier" => Some(FieldValue::Float(self.recoil_modifier)),
+            "ricochet_chance" => Some(FieldValue::Float(self.ricochet_chance)),
+            "stack_max_size" => Some(FieldValue::I32(self.stack_max_size)),
+            "stamina_burn_per_damage" => Some(FieldValue::Float(self.stamina_burn_per_damage)),
+            "tracer" => Some(FieldValue::Bool(self.tracer)),
+            "tracer_color" => Some(FieldValue::String(self.tracer_color.clone())),
+            "weight" => Some(FieldValue::Float(self.weight)),
+            "item_id" => Some(FieldValue::String(self.item_id.clone())),
+            // price columns come from a subquery so they page by offset
+            _ => None,
         }
-        Some((
-            self.struct_data_from_str(
-                VALID_ITEM_SORT_BY
-                    .iter()
-                    .find(|x| x.eq_ignore_ascii_case(sort_by))?,
-            )?,
-            self._id.clone(),
-        ))
+    }
+
+    fn keyset_id(&self) -> &str {
+        &self.item_id
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow, ToSchema)]
 #[allow(unused)]
 pub struct ItemBase {
     pub _id: String,
@@ -143,7 +233,7 @@ impl From<ItemFromDB> for Item {
     }
 }
 
-#[derive(Serialize, Deserialize, sqlx::FromRow, Clone)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, Clone, ToSchema)]
 pub struct Objective {
     #[serde(skip)]
     #[allow(dead_code)]
@@ -163,16 +253,74 @@ pub struct NeededItemsDB {
     pub needed_item_ids: Vec<String>,
 }
 
-#[derive(Serialize, Deserialize, sqlx::FromRow, Clone)]
+#[derive(Serialize, Deserialize, sqlx::FromRow, Clone, ToSchema)]
 pub struct TaskRequirement {
     #[serde(skip)]
     #[allow(dead_code)]
     pub id: i32,
-    pub status: String,
+    // the states req_task_id can be in to satisfy the requirement, see RequirementKind
+    // served as the single comma separated status older clients expect, v2 adj_list has the kind
+    #[serde(
+        rename = "status",
+        serialize_with = "serialize_statuses",
+        deserialize_with = "deserialize_statuses"
+    )]
+    #[schema(value_type = String)]
+    pub statuses: Vec<String>,
     pub req_task_id: String,
     pub task_id: String,
 }
 
+fn serialize_statuses<S: Serializer>(
+    statuses: &[String],
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&statuses.join(","))
+}
+
+fn deserialize_statuses<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<String>, D::Error> {
+    Ok(String::deserialize(deserializer)?
+        .split(',')
+        .filter(|x| !x.is_empty())
+        .map(ToString::to_string)
+        .collect())
+}
+
+impl TaskRequirement {
+    pub fn kind(&self) -> RequirementKind {
+        RequirementKind::from_statuses(&self.statuses)
+    }
+}
+
+// what a prerequisite needs before the task that requires it unlocks
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum RequirementKind {
+    // the api lists complete or complete and failed, either way the prerequisite has to be done with
+    Finished,
+    // active is one of the statuses so the prerequisite only has to be accepted and can still be in progress
+    Accepted,
+    // the prerequisite has to have been failed which means it was accepted but never completed
+    Failed,
+}
+
+impl RequirementKind {
+    // any one of the statuses satisfies the requirement so the loosest one decides, requirements
+    // without a known status are treated as completion since that is the strictest
+    pub fn from_statuses(statuses: &[String]) -> Self {
+        let has = |status: &str| statuses.iter().any(|x| x.eq_ignore_ascii_case(status));
+        if has("active") {
+            Self::Accepted
+        } else if has("failed") && !has("complete") {
+            Self::Failed
+        } else {
+            Self::Finished
+        }
+    }
+}
+
 #[derive(Serialize, sqlx::FromRow)]
 pub struct TaskFromDB {
     pub _id: String,
@@ -185,7 +333,7 @@ pub struct TaskFromDB {
     pub wiki: String,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
 pub struct Task {
     pub _id: String,
     pub task_name: String,
@@ -199,7 +347,7 @@ pub struct Task {
     pub task_requirements: Vec<TaskRequirement>,
 }
 
-#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow, ToSchema)]
 pub struct TaskBase {
     pub _id: String,
     pub task_name: String,
@@ -222,13 +370,13 @@ impl From<TaskFromDB> for Task {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow, ToSchema)]
 pub struct SavedItemData {
     pub price_rub: i32,
     pub recorded_time: DateTime<Utc>,
 }
 
-#[derive(sqlx::FromRow, Serialize, Deserialize)]
+#[derive(sqlx::FromRow, Serialize, Deserialize, ToSchema)]
 pub struct DeviceItemQueryParams {
     #[serde(skip)]
     #[allow(dead_code)]
@@ -237,9 +385,26 @@ pub struct DeviceItemQueryParams {
     pub sort_asc: bool,
     pub sort_by: String,
     pub item_type: String,
+    pub sort: String,
+    pub min_base_price: Option<i32>,
+    pub max_base_price: Option<i32>,
+    pub min_flea_price: Option<i32>,
+    pub max_flea_price: Option<i32>,
+    pub min_per_slot: Option<i32>,
+    pub max_per_slot: Option<i32>,
+    pub min_buy_from_flea_instant_profit: Option<i32>,
+    pub max_buy_from_flea_instant_profit: Option<i32>,
+    pub min_buy_from_trader_instant_profit: Option<i32>,
+    pub max_buy_from_trader_instant_profit: Option<i32>,
+    pub min_slots: Option<i32>,
+    pub max_slots: Option<i32>,
+    pub min_avg_24h_price: Option<i32>,
+    pub max_avg_24h_price: Option<i32>,
+    pub is_flea: Option<bool>,
+    pub sellable_to: String,
 }
 
-#[derive(sqlx::FromRow, Serialize, Deserialize)]
+#[derive(sqlx::FromRow, Serialize, Deserialize, ToSchema)]
 pub struct DeviceTaskQueryParams {
     #[serde(skip)]
     #[allow(dead_code)]
@@ -252,7 +417,7 @@ pub struct DeviceTaskQueryParams {
     pub trader: String,
 }
 
-#[derive(sqlx::FromRow, Serialize, Deserialize)]
+#[derive(sqlx::FromRow, Serialize, Deserialize, ToSchema)]
 pub struct DeviceAmmoQueryParams {
     #[serde(skip)]
     #[allow(dead_code)]
@@ -264,6 +429,40 @@ pub struct DeviceAmmoQueryParams {
     pub penetration_power: i32,
     pub initial_speed: f32,
     pub ammo_type: String,
+    pub sort: String,
+    pub min_accuracy_modifier: Option<f32>,
+    pub max_accuracy_modifier: Option<f32>,
+    pub min_armor_damage: Option<i32>,
+    pub max_armor_damage: Option<i32>,
+    pub min_fragmentation_chance: Option<f32>,
+    pub max_fragmentation_chance: Option<f32>,
+    pub max_damage: Option<i32>,
+    pub min_heavy_bleed_modifier: Option<f32>,
+    pub max_heavy_bleed_modifier: Option<f32>,
+    pub max_initial_speed: Option<f32>,
+    pub min_light_bleed_modifier: Option<f32>,
+    pub max_light_bleed_modifier: Option<f32>,
+    pub min_penetration_chance: Option<f32>,
+    pub max_penetration_chance: Option<f32>,
+    pub max_penetration_power: Option<i32>,
+    pub min_penetration_power_deviation: Option<f32>,
+    pub max_penetration_power_deviation: Option<f32>,
+    pub min_projectile_count: Option<i32>,
+    pub max_projectile_count: Option<i32>,
+    pub min_recoil_modifier: Option<f32>,
+    pub max_recoil_modifier: Option<f32>,
+    pub min_ricochet_chance: Option<f32>,
+    pub max_ricochet_chance: Option<f32>,
+    pub min_stack_max_size: Option<i32>,
+    pub max_stack_max_size: Option<i32>,
+    pub min_stamina_burn_per_damage: Option<f32>,
+    pub max_stamina_burn_per_damage: Option<f32>,
+    pub min_weight: Option<f32>,
+    pub max_weight: Option<f32>,
+    pub tracer: Option<bool>,
+    pub caliber: Vec<String>,
+    pub embed_item: bool,
+    pub trader_level: Option<i32>,
 }
 
 // #[derive(sqlx::FromRow)]
@@ -273,7 +472,7 @@ pub struct DeviceAmmoQueryParams {
 //     pub last_visited: DateTime<Utc>,
 // }
 
-#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow, ToSchema)]
 pub struct Ammo {
     pub accuracy_modifier: f32,
     pub ammo_type: String,
@@ -296,4 +495,107 @@ pub struct Ammo {
     pub tracer_color: String,
     pub weight: f32,
     pub item_id: String,
+    // only filled in when embed_item=true
+    #[sqlx(skip)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub item: Option<Box<AmmoItem>>,
+}
+
+// market data of the item an ammo row is sold as
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct AmmoItem {
+    pub item_name: String,
+    pub short_name: String,
+    // cheapest offer allowed by the trader_level, flea market included
+    pub cheapest_buy: Option<BuyFor>,
+    pub flea_price: Option<i32>,
+}
+
+// AmmoItem is never stored, decoding it as jsonb lets query_as! select NULL for Ammo.item
+impl sqlx::Type<Postgres> for Box<AmmoItem> {
+    fn type_info() -> PgTypeInfo {
+        <Json<Self> as sqlx::Type<Postgres>>::type_info()
+    }
+}
+
+impl<'r> sqlx::Decode<'r, Postgres> for Box<AmmoItem> {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(Self::new(
+            <Json<AmmoItem> as sqlx::Decode<'r, Postgres>>::decode(value)?.0,
+        ))
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow, ToSchema)]
+pub struct Weapon {
+    pub _id: String,
+    pub weapon_name: String,
+    pub short_name: String,
+    // the raw value ammo is matched on like Caliber556x45NATO
+    pub caliber: String,
+    // readable form of caliber like 5.56x45mm NATO, filled in after the row is fetched
+    #[sqlx(skip)]
+    pub caliber_name: String,
+    pub fire_rate: i32,
+    pub ergonomics: f32,
+    pub recoil_vertical: i32,
+    pub recoil_horizontal: i32,
+}
+
+// a vest, plate or helmet, named apart from armor::Armor which is the model these get measured with
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow, ToSchema)]
+pub struct ArmorPiece {
+    pub _id: String,
+    pub armor_name: String,
+    pub short_name: String,
+    // armor, armorplate or helmet
+    pub armor_type: String,
+    pub armor_class: i32,
+    // max durability of the piece
+    pub durability: f32,
+    // snake case like armored_steel, empty when the api has none
+    pub material: String,
+    pub blunt_throughput: f32,
+    // only helmets and plates have a chance for rounds to glance off
+    pub ricochet_chance: f32,
+    pub zones: Vec<String>,
+    pub speed_penalty: f32,
+    pub turn_penalty: f32,
+    pub ergo_penalty: f32,
+    pub weight: f32,
+}
+
+impl Keyset for ArmorPiece {
+    const VALID_SORT_BY: &'static [&'static str] = VALID_ARMOR_SORT_BY;
+
+    fn struct_data_from_str(&self, name: &str) -> Option<FieldValue> {
+        match name.to_ascii_lowercase().as_str() {
+            "_id" => Some(FieldValue::String(self._id.clone())),
+            "armor_name" => Some(FieldValue::String(self.armor_name.clone())),
+            "short_name" => Some(FieldValue::String(self.short_name.clone())),
+            "armor_type" => Some(FieldValue::String(self.armor_type.clone())),
+            "armor_class" => Some(FieldValue::I32(self.armor_class)),
+            "durability" => Some(FieldValue::Float(self.durability)),
+            "material" => Some(FieldValue::String(self.material.clone())),
+            "blunt_throughput" => Some(FieldValue::Float(self.blunt_throughput)),
+            "ricochet_chance" => Some(FieldValue::Float(self.ricochet_chance)),
+            "speed_penalty" => Some(FieldValue::Float(self.speed_penalty)),
+            "turn_penalty" => Some(FieldValue::Float(self.turn_penalty)),
+            "ergo_penalty" => Some(FieldValue::Float(self.ergo_penalty)),
+            "weight" => Some(FieldValue::Float(self.weight)),
+            _ => None,
+        }
+    }
+
+    fn keyset_id(&self) -> &str {
+        &self._id
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow)]
+pub struct DatasetRefresh {
+    pub page: String,
+    pub source: String,
+    pub row_count: i32,
+    pub refreshed_at: DateTime<Utc>,
 }
@@ -259,3 +259,132 @@ pub const AMMO_QUERY: &str = "
   }
 }
 ";
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeaponProperties {
+    pub caliber: Option<String>,
+    pub fire_rate: Option<i32>,
+    pub ergonomics: Option<f32>,
+    pub recoil_vertical: Option<i32>,
+    pub recoil_horizontal: Option<i32>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Weapon {
+    #[serde(rename = "id")]
+    pub _id: String,
+    #[serde(rename = "name")]
+    pub weapon_name: String,
+    pub short_name: String,
+    // null for the few guns the api has no weapon properties for
+    pub properties: Option<WeaponProperties>,
+}
+
+// aliased so the response lands under data.weapons instead of data.items
+pub const WEAPONS_QUERY: &str = "
+{
+  weapons: items(type: gun, gameMode: regular) {
+    id
+    name
+    shortName
+    properties {
+      ... on ItemPropertiesWeapon {
+        caliber
+        fireRate
+        ergonomics
+        recoilVertical
+        recoilHorizontal
+      }
+    }
+  }
+}
+";
+
+#[derive(Deserialize, Serialize)]
+pub struct ArmorMaterial {
+    // like ArmoredSteel, stored in snake case to match the materials the effectiveness model takes
+    pub id: String,
+}
+
+// armor, helmets and plates all come back in this shape with whatever fields their type has
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArmorProperties {
+    pub class: Option<i32>,
+    pub durability: Option<f32>,
+    pub material: Option<ArmorMaterial>,
+    pub blunt_throughput: Option<f32>,
+    pub ricochet_x: Option<f32>,
+    pub zones: Option<Vec<String>>,
+    pub head_zones: Option<Vec<String>>,
+    pub speed_penalty: Option<f32>,
+    pub turn_penalty: Option<f32>,
+    pub ergo_penalty: Option<f32>,
+}
+
+#[derive(Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Armor {
+    #[serde(rename = "id")]
+    pub _id: String,
+    #[serde(rename = "name")]
+    pub armor_name: String,
+    pub short_name: String,
+    pub types: Vec<String>,
+    pub weight: Option<f32>,
+    pub properties: Option<ArmorProperties>,
+}
+
+pub const ARMOR_QUERY: &str = "
+{
+  armor: items(types: [armor, armorPlate, helmet], gameMode: regular) {
+    id
+    name
+    shortName
+    types
+    weight
+    properties {
+      ... on ItemPropertiesArmor {
+        class
+        durability
+        material {
+          id
+        }
+        bluntThroughput
+        zones
+        speedPenalty
+        turnPenalty
+        ergoPenalty
+      }
+      ... on ItemPropertiesArmorAttachment {
+        class
+        durability
+        material {
+          id
+        }
+        bluntThroughput
+        ricochetX
+        headZones
+        speedPenalty
+        turnPenalty
+        ergoPenalty
+      }
+      ... on ItemPropertiesHelmet {
+        class
+        durability
+        material {
+          id
+        }
+        bluntThroughput
+        ricochetX
+        headZones
+        speedPenalty
+        turnPenalty
+        ergoPenalty
+      }
+    }
+  }
+}
+";
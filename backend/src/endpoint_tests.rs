@@ -1,16 +1,32 @@
 #![cfg(test)]
+use crate::task_graph::{
+    CYCLE, DANGLING_REQUIREMENT, ORPHANED_TASK, TaskGraph, TaskGraphIssue, find_issues,
+};
+use crate::weapon_routes::caliber_name;
 use crate::{
+    api_routers::documented_router,
+    cli::{Command, Dataset, parse_args, parse_duration},
     database_types::{
-        Ammo, DeviceAmmoQueryParams, DeviceItemQueryParams, DeviceTaskQueryParams, Item, Task,
+        Ammo, ArmorPiece, DeviceAmmoQueryParams, DeviceItemQueryParams, DeviceTaskQueryParams,
+        Item, RequirementKind, Task, TaskBase, Weapon,
     },
+    filters::{list_cache_key, text_cache_key},
+    init_app_state::DEVICE_PREFERENCES_MAX_AGE,
+    pagination::filters_hash,
     query_types::{
-        AdjList, VALID_AMMO_SORT_BY, VALID_AMMO_TYPE, VALID_ITEM_SORT_BY, VALID_ITEM_TYPES,
-        VALID_OBJ_TYPES, VALID_TRADERS,
+        AdjList, AdjListV1, AdjacentTask, AvailableTask, TaskRelation, VALID_AMMO_SORT_BY,
+        VALID_AMMO_TYPE, VALID_ITEM_SORT_BY, VALID_ITEM_TYPES, VALID_OBJ_TYPES, VALID_TRADERS,
+    },
+    snapshots::{
+        SNAPSHOT_RETENTION, archive_snapshot, list_snapshots, read_snapshot, resolve_snapshot,
     },
+    upsert::{GraphQLError, PartialDataPolicy, parse_response},
 };
+use ahash::AHashMap as HashMap;
 use ahash::AHashSet as HashSet;
 use reqwest::Client;
 use serde::de::DeserializeOwned;
+use std::time::Duration;
 
 trait QueryParms: DeserializeOwned {
     fn get_base() -> String;
@@ -33,6 +49,18 @@ trait QueryParms: DeserializeOwned {
             )
         })
     }
+
+    // query params are saved in the background so poll until saved says they are there
+    async fn get_until(saved: impl Fn(&Self) -> bool) -> Self {
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        loop {
+            let parms = Self::get().await;
+            if saved(&parms) || std::time::Instant::now() > deadline {
+                return parms;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
 }
 
 impl QueryParms for DeviceItemQueryParams {
@@ -291,6 +319,85 @@ impl Test for Ammo {
 const URL: &str = "http://127.0.0.1:8000";
 const DEVICE_ID: &str = "501b8491-c3fe-4e37-9428-ce1456c1d386";
 
+async fn get_ok(url: &str, device_id: Option<&str>) -> reqwest::Response {
+    let mut req = Client::new().get(url);
+    if let Some(device_id) = device_id {
+        req = req.header("x-device-id", device_id);
+    }
+    let res = req
+        .send()
+        .await
+        .unwrap_or_else(|_| panic!("{url} did not get correctly"));
+    assert!(res.status().is_success(), "{url} returned {}", res.status());
+    res
+}
+
+async fn get_json<T: DeserializeOwned>(url: &str, device_id: Option<&str>) -> T {
+    get_ok(url, device_id)
+        .await
+        .json()
+        .await
+        .unwrap_or_else(|_| panic!("{url} did not serialize correctly"))
+}
+
+// the v2 adj_list in the tuple form the backend uses so the requirement kinds can be checked
+async fn get_adj_list() -> AdjList {
+    get_json::<HashMap<String, Vec<AdjacentTask>>>(&format!("{URL}/v2/tasks/adj_list"), None)
+        .await
+        .into_iter()
+        .map(|(task_id, adjacent)| {
+            let adjacent = adjacent
+                .into_iter()
+                .map(|x| {
+                    let unlocks = matches!(x.relation, TaskRelation::Unlocks);
+                    (x.task_id, unlocks, x.requirement)
+                })
+                .collect();
+            (task_id, adjacent)
+        })
+        .collect()
+}
+
+// every test that completes tasks gets a device of its own so the tests do not affect each other
+struct TestDevice(String);
+
+impl TestDevice {
+    fn new() -> Self {
+        Self(uuid::Uuid::new_v4().to_string())
+    }
+
+    fn id(&self) -> &str {
+        &self.0
+    }
+
+    async fn set_complete(&self, task_id: &str, direction: bool) {
+        let res = Client::new()
+            .post(format!("{URL}/tasks/set_complete"))
+            .header("x-device-id", &self.0)
+            .json(&serde_json::json!({"task_id": task_id, "direction": direction}))
+            .send()
+            .await
+            .expect("set complete endpoint failed");
+        assert!(res.status().is_success());
+    }
+
+    async fn completed(&self) -> HashSet<String> {
+        get_json::<Vec<TaskBase>>(&format!("{URL}/tasks/get_completed"), Some(self.id()))
+            .await
+            .into_iter()
+            .map(|x| x._id)
+            .collect()
+    }
+
+    async fn clear_completed(&self) {
+        get_ok(
+            &format!("{URL}/tasks/clear_completed_tasks"),
+            Some(self.id()),
+        )
+        .await;
+    }
+}
+
 #[tokio::test]
 async fn test_health() {
     let res = Client::new()
@@ -302,6 +409,62 @@ async fn test_health() {
     assert!(res.status().is_success());
 }
 
+#[tokio::test]
+async fn test_healthz() {
+    let res = Client::new()
+        .get(format!("{}{}", URL, "/healthz"))
+        .send()
+        .await
+        .expect("healthz endpoint failed");
+
+    assert!(res.status().is_success());
+}
+
+#[tokio::test]
+async fn test_readyz() {
+    let res = Client::new()
+        .get(format!("{}{}", URL, "/readyz"))
+        .send()
+        .await
+        .expect("readyz endpoint failed");
+
+    assert!(res.status().is_success());
+}
+
+#[tokio::test]
+async fn test_health_details() {
+    let res = Client::new()
+        .get(format!("{}{}", URL, "/health/details"))
+        .send()
+        .await
+        .expect("health details endpoint failed");
+
+    assert!(res.status().is_success());
+
+    let details: serde_json::Value = res
+        .json()
+        .await
+        .expect("health details endpoint did not serialize correctly");
+    assert!(details["datasets"].as_array().is_some_and(|x| x.len() == 5));
+}
+
+#[tokio::test]
+async fn test_metrics() {
+    let res = Client::new()
+        .get(format!("{}{}", URL, "/metrics"))
+        .send()
+        .await
+        .expect("metrics endpoint failed");
+
+    assert!(res.status().is_success());
+
+    let body = res
+        .text()
+        .await
+        .expect("metrics endpoint did not return text");
+    assert!(body.contains("seconds_until_next_refresh"));
+}
+
 #[tokio::test]
 async fn test_item_stats() {
     let res = Client::new()
@@ -561,7 +724,7 @@ async fn test_ammo_limit_and_offset() {
     Ammo::limit_and_offset_testing().await;
 }
 
-fn perform_dfs(start_id: String, ids: &HashSet<String>, adj_list: &AdjList) {
+fn perform_dfs(start_id: String, ids: &HashSet<String>, adj_list: &AdjListV1) {
     let mut st = vec![start_id];
     let mut visited = HashSet::new();
     while let Some(id) = st.pop() {
@@ -594,7 +757,7 @@ async fn test_adj_list() {
 
     assert!(res.status().is_success());
 
-    let adj_list: AdjList = res.json().await.expect("adjlist endpoint failed");
+    let adj_list: AdjListV1 = res.json().await.expect("adjlist endpoint failed");
 
     let (collector_vec, kappa, knockknock_vec, lightkeeper) = tokio::join!(
         Task::get_request_vec(format!(
@@ -698,3 +861,1264 @@ async fn large_limit_redis_testing() {
 
     println!("{}ms", total_time.elapsed().as_millis());
 }
+
+#[tokio::test]
+async fn test_error_body() {
+    let res = Client::new()
+        .get(format!("{}{}", URL, "/items/query_parms"))
+        .header("x-request-id", "test-error-body")
+        .send()
+        .await
+        .expect("query_parms endpoint failed");
+
+    assert_eq!(res.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let body: serde_json::Value = res.json().await.expect("error body was not json");
+    assert_eq!(body["code"], "unauthorized");
+    assert_eq!(body["request_id"], "test-error-body");
+    assert!(body["message"].is_string());
+
+    let res = Client::new()
+        .get(format!("{}{}", URL, "/does_not_exist"))
+        .send()
+        .await
+        .expect("fallback route failed");
+
+    assert_eq!(res.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let body: serde_json::Value = res.json().await.expect("error body was not json");
+    assert_eq!(body["code"], "not_found");
+
+    // a malformed device id is treated as no device id unless strict=true
+    let res = Client::new()
+        .get(format!("{}{}", URL, "/items/query_parms"))
+        .header("x-device-id", "not-a-uuid")
+        .send()
+        .await
+        .expect("query_parms endpoint failed");
+
+    assert_eq!(res.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let res = Client::new()
+        .get(format!("{}{}", URL, "/items?strict=true"))
+        .header("x-device-id", "not-a-uuid")
+        .send()
+        .await
+        .expect("items endpoint failed");
+
+    assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let body: serde_json::Value = res.json().await.expect("error body was not json");
+    assert_eq!(body["code"], "bad_request");
+}
+
+#[tokio::test]
+async fn test_strict_query_params() {
+    let res = Client::new()
+        .get(format!(
+            "{}{}",
+            URL, "/items?sort_by=base_prise&item_type=gun&strict=true&save=false"
+        ))
+        .send()
+        .await
+        .expect("strict items endpoint failed");
+
+    assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let body: serde_json::Value = res.json().await.expect("error body was not json");
+    assert_eq!(body["code"], "bad_request");
+
+    let details = body["details"].as_array().expect("details was not a list");
+    assert_eq!(details.len(), 1);
+    assert_eq!(details[0]["field"], "sort_by");
+    assert_eq!(details[0]["nearest_matches"][0], "base_price");
+
+    // the same query without strict falls back to defaults
+    let res = Client::new()
+        .get(format!(
+            "{}{}",
+            URL, "/items?sort_by=base_prise&item_type=gun&save=false"
+        ))
+        .send()
+        .await
+        .expect("lenient items endpoint failed");
+
+    assert!(res.status().is_success());
+}
+
+// routes!() registers a handler and its spec entry together so the spec is the list of routes,
+// every path and method in it has to reach a handler instead of the fallback
+#[tokio::test]
+async fn test_every_documented_route_is_served() {
+    let openapi = documented_router().into_openapi();
+    assert!(
+        !openapi.paths.paths.is_empty(),
+        "no routes were found in the openapi spec"
+    );
+
+    let mut unserved = vec![];
+    for (path, item) in &openapi.paths.paths {
+        let url = format!("{URL}{}", path.replace("{id}", "000000000000000000000000"));
+        let methods = [
+            (reqwest::Method::GET, item.get.is_some()),
+            (reqwest::Method::POST, item.post.is_some()),
+            (reqwest::Method::DELETE, item.delete.is_some()),
+        ];
+        for (method, _) in methods.into_iter().filter(|(_, documented)| *documented) {
+            let res = Client::new()
+                .request(method.clone(), &url)
+                .send()
+                .await
+                .expect("documented route failed");
+            let status = res.status();
+            let body = res.text().await.unwrap_or_default();
+            if status == reqwest::StatusCode::METHOD_NOT_ALLOWED
+                || body.contains("Route does not exist")
+            {
+                unserved.push(format!("{method} {path}"));
+            }
+        }
+    }
+
+    assert!(
+        unserved.is_empty(),
+        "documented routes that are not served {unserved:?}"
+    );
+}
+
+#[tokio::test]
+async fn test_openapi_spec() {
+    let res = Client::new()
+        .get(format!("{}{}", URL, "/openapi.json"))
+        .send()
+        .await
+        .expect("openapi endpoint failed");
+
+    assert!(res.status().is_success());
+
+    let spec: serde_json::Value = res.json().await.expect("openapi spec was not json");
+    assert!(spec["paths"]["/items"].is_object());
+    assert!(spec["paths"]["/tasks/set_complete"]["post"].is_object());
+}
+
+#[tokio::test]
+async fn test_versioned_routes() {
+    let unprefixed: Vec<Task> = Client::new()
+        .get(format!("{}{}", URL, "/tasks?limit=5&save=false"))
+        .send()
+        .await
+        .expect("tasks endpoint failed")
+        .json()
+        .await
+        .expect("tasks endpoint did not return tasks");
+
+    let v1: Vec<Task> = Client::new()
+        .get(format!("{}{}", URL, "/v1/tasks?limit=5&save=false"))
+        .send()
+        .await
+        .expect("v1 tasks endpoint failed")
+        .json()
+        .await
+        .expect("v1 tasks endpoint did not return tasks");
+
+    assert_eq!(
+        unprefixed.iter().map(Test::get_id).collect::<Vec<_>>(),
+        v1.iter().map(Test::get_id).collect::<Vec<_>>()
+    );
+
+    let v2: serde_json::Value = Client::new()
+        .get(format!("{}{}", URL, "/v2/tasks?limit=5&save=false"))
+        .send()
+        .await
+        .expect("v2 tasks endpoint failed")
+        .json()
+        .await
+        .expect("v2 tasks endpoint did not return json");
+
+    assert_eq!(v2["items"].as_array().map(Vec::len), Some(v1.len()));
+    assert_eq!(v2["limit"], 5);
+    assert_eq!(v2["offset"], 0);
+    assert!(v2["total"].is_number());
+
+    let required_items: serde_json::Value = Client::new()
+        .get(format!("{}{}", URL, "/v2/tasks/required_items"))
+        .send()
+        .await
+        .expect("v2 required items endpoint failed")
+        .json()
+        .await
+        .expect("v2 required items endpoint did not return json");
+
+    assert!(
+        required_items["items"]
+            .as_array()
+            .expect("required items was not a list")
+            .iter()
+            .all(|x| x["item"].is_object() && x["count"].is_number())
+    );
+}
+
+fn page_ids(page: &serde_json::Value) -> Vec<String> {
+    page["items"]
+        .as_array()
+        .expect("page items was not a list")
+        .iter()
+        .map(|x| {
+            x["_id"]
+                .as_str()
+                .or_else(|| x["item_id"].as_str())
+                .map(String::from)
+        })
+        .collect::<Option<Vec<_>>>()
+        .expect("page item had no id")
+}
+
+// walks every page by cursor and checks it lines up with total, with plain offsets
+// and with the same rows fetched as one page
+#[tokio::test]
+async fn test_pagination_cursor() {
+    for route in [
+        "/v2/tasks?save=false",
+        "/v2/ammo?save=false",
+        "/v2/ammo?save=false&sort_by=damage&sort_asc=false",
+        "/v2/ammo?save=false&sort_by=tracer&sort_asc=true",
+        "/v2/ammo?save=false&sort_by=caliber&sort_asc=true",
+        "/v2/ammo?save=false&sort=tracer:desc,damage:asc,caliber:desc",
+        "/v2/ammo?save=false&sort=price_per_penetration:asc,damage:desc&trader_level=1",
+    ] {
+        let mut ids = vec![];
+        let mut url = format!("{URL}{route}&limit=7");
+        let total = loop {
+            let page: serde_json::Value = Client::new()
+                .get(&url)
+                .send()
+                .await
+                .expect("paginated endpoint failed")
+                .json()
+                .await
+                .expect("paginated endpoint did not return json");
+
+            let offset_page: serde_json::Value = Client::new()
+                .get(format!("{URL}{route}&limit=7&offset={}", ids.len()))
+                .send()
+                .await
+                .expect("paginated endpoint failed")
+                .json()
+                .await
+                .expect("paginated endpoint did not return json");
+            assert_eq!(page["items"], offset_page["items"]);
+
+            ids.extend(page_ids(&page));
+
+            let Some(cursor) = page["next_cursor"].as_str() else {
+                break page["total"].as_u64();
+            };
+            url = format!("{URL}{route}&limit=7&cursor={cursor}");
+        };
+
+        assert_eq!(Some(ids.len() as u64), total);
+
+        let single_page: serde_json::Value = Client::new()
+            .get(format!("{URL}{route}&limit=500"))
+            .send()
+            .await
+            .expect("paginated endpoint failed")
+            .json()
+            .await
+            .expect("paginated endpoint did not return json");
+        assert_eq!(ids, page_ids(&single_page));
+    }
+
+    let res = Client::new()
+        .get(format!("{URL}/v2/ammo?cursor=not-a-cursor"))
+        .send()
+        .await
+        .expect("ammo endpoint failed");
+    assert_eq!(res.status(), 400);
+
+    // a cursor can not be carried over to a different set of filters
+    let page: serde_json::Value = Client::new()
+        .get(format!("{URL}/v2/ammo?save=false&limit=7"))
+        .send()
+        .await
+        .expect("ammo endpoint failed")
+        .json()
+        .await
+        .expect("ammo endpoint did not return json");
+    let cursor = page["next_cursor"].as_str().expect("ammo had one page");
+    let res = Client::new()
+        .get(format!(
+            "{URL}/v2/ammo?save=false&limit=7&min_weight=0.01&cursor={cursor}"
+        ))
+        .send()
+        .await
+        .expect("ammo endpoint failed");
+    assert_eq!(res.status(), 400);
+    let res = Client::new()
+        .get(format!("{URL}/v2/ammo?save=false&limit=7&cursor={cursor}"))
+        .send()
+        .await
+        .expect("ammo endpoint failed");
+    assert_eq!(res.status(), 200);
+}
+
+// later columns only decide the order between rows that tie on the earlier ones
+#[tokio::test]
+async fn test_ammo_multi_column_sort() {
+    let ammo = Ammo::get_request_vec(format!(
+        "{URL}/ammo?save=false&limit=500&sort=damage:desc,penetration_power:desc,weight:asc"
+    ))
+    .await;
+
+    assert!(!ammo.is_empty());
+    assert!(ammo.windows(2).all(|w| {
+        let (a, b) = (&w[0], &w[1]);
+        a.damage > b.damage
+            || (a.damage == b.damage && a.penetration_power > b.penetration_power)
+            || (a.damage == b.damage
+                && a.penetration_power == b.penetration_power
+                && a.weight <= b.weight)
+    }));
+
+    let res = Client::new()
+        .get(format!(
+            "{URL}/ammo?save=false&strict=true&sort=damag:desc,weight:up"
+        ))
+        .send()
+        .await
+        .expect("strict ammo endpoint failed");
+    assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let body: serde_json::Value = res.json().await.expect("error body was not json");
+    let details = body["details"].as_array().expect("details was not a list");
+    assert_eq!(details.len(), 2);
+    assert_eq!(details[0]["nearest_matches"][0], "damage");
+    assert_eq!(details[1]["allowed"], serde_json::json!(["asc", "desc"]));
+
+    // the normalized sort is saved with the device's query params
+    let _ = DeviceAmmoQueryParams::get().await;
+    let res = Client::new()
+        .get(format!("{URL}/ammo?sort=Damage:DESC,weight"))
+        .header("x-device-id", DEVICE_ID)
+        .send()
+        .await
+        .expect("ammo endpoint failed");
+    assert!(res.status().is_success());
+
+    let saved = DeviceAmmoQueryParams::get_until(|x| x.sort == "damage:desc,weight:asc").await;
+    assert_eq!(saved.sort, "damage:desc,weight:asc");
+}
+
+#[tokio::test]
+async fn test_item_range_filters() {
+    let items = Item::get_request_vec(format!(
+        "{URL}/items?save=false&limit=500&min_base_price=1000&max_base_price=50000&max_slots=2&is_flea=true&sellable_to=therapist"
+    ))
+    .await;
+
+    assert!(items.iter().all(|x| {
+        (1000..=50000).contains(&x.base_price)
+            && x.width * x.height <= 2
+            && x.is_flea
+            && x.sells
+                .iter()
+                .any(|s| s.trader_name.eq_ignore_ascii_case("therapist"))
+    }));
+
+    let res = Client::new()
+        .get(format!(
+            "{URL}/items?save=false&strict=true&sellable_to=therapst"
+        ))
+        .send()
+        .await
+        .expect("strict items endpoint failed");
+    assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    // filters are saved with the device's query params
+    let _ = DeviceItemQueryParams::get().await;
+    let res = Client::new()
+        .get(format!(
+            "{URL}/items?min_per_slot=500&max_avg_24h_price=90000&is_flea=false&sellable_to=Mechanic"
+        ))
+        .header("x-device-id", DEVICE_ID)
+        .send()
+        .await
+        .expect("items endpoint failed");
+    assert!(res.status().is_success());
+
+    let saved = DeviceItemQueryParams::get_until(|x| x.min_per_slot == Some(500)).await;
+    assert_eq!(saved.min_per_slot, Some(500));
+    assert_eq!(saved.max_avg_24h_price, Some(90000));
+    assert_eq!(saved.min_base_price, None);
+    assert_eq!(saved.is_flea, Some(false));
+    assert_eq!(saved.sellable_to.to_lowercase(), "mechanic");
+}
+
+#[tokio::test]
+async fn test_ammo_range_filters() {
+    let all = Ammo::get_request_vec(format!("{URL}/ammo?save=false&limit=500")).await;
+    let caliber = all.first().expect("no ammo to filter").caliber.clone();
+
+    let ammo = Ammo::get_request_vec(format!(
+        "{URL}/ammo?save=false&limit=500&damage=30&max_damage=80&max_weight=0.02&min_armor_damage=20&tracer=false"
+    ))
+    .await;
+    assert!(ammo.iter().all(|x| {
+        (30..=80).contains(&x.damage) && x.weight <= 0.02 && x.armor_damage >= 20 && !x.tracer
+    }));
+
+    let ammo = Ammo::get_request_vec(format!(
+        "{URL}/ammo?save=false&limit=500&caliber={caliber}&caliber=NotACaliber"
+    ))
+    .await;
+    assert!(!ammo.is_empty());
+    assert!(ammo.iter().all(|x| x.caliber == caliber));
+    assert_eq!(
+        ammo.len(),
+        all.iter().filter(|x| x.caliber == caliber).count()
+    );
+
+    // filters are saved with the device's query params
+    let _ = DeviceAmmoQueryParams::get().await;
+    let res = Client::new()
+        .get(format!(
+            "{URL}/ammo?max_penetration_power=40&min_recoil_modifier=-0.5&tracer=true&caliber={caliber}"
+        ))
+        .header("x-device-id", DEVICE_ID)
+        .send()
+        .await
+        .expect("ammo endpoint failed");
+    assert!(res.status().is_success());
+
+    let saved = DeviceAmmoQueryParams::get_until(|x| x.max_penetration_power == Some(40)).await;
+    assert_eq!(saved.max_penetration_power, Some(40));
+    assert_eq!(saved.min_recoil_modifier, Some(-0.5));
+    assert_eq!(saved.max_weight, None);
+    assert_eq!(saved.tracer, Some(true));
+    assert_eq!(saved.caliber, vec![caliber]);
+}
+
+#[tokio::test]
+async fn test_ammo_effectiveness() {
+    let get = |query: &str| {
+        let url = format!("{URL}/ammo/effectiveness?save=false&limit=500&{query}");
+        async move { get_json::<Vec<serde_json::Value>>(&url, None).await }
+    };
+
+    let class_2 = get("armor_class=2&sort_by=item_id&sort_asc=true").await;
+    let class_6 = get("armor_class=6&sort_by=item_id&sort_asc=true").await;
+    let class_6_broken = get("armor_class=6&durability=0&sort_by=item_id&sort_asc=true").await;
+    assert!(!class_2.is_empty());
+    assert_eq!(class_2.len(), class_6.len());
+
+    let chance = |x: &serde_json::Value| x["penetration_chance"].as_f64().unwrap_or(-1.0);
+    for ((low, high), broken) in class_2.iter().zip(&class_6).zip(&class_6_broken) {
+        assert_eq!(low["ammo"]["item_id"], high["ammo"]["item_id"]);
+        assert!((0.0..=1.0).contains(&chance(low)));
+        // better armor never lets more through and damaged armor never lets less through
+        assert!(chance(low) >= chance(high));
+        assert!(chance(broken) >= chance(high));
+    }
+
+    let ranked = get("armor_class=4&rank_by=penetration_chance&rank_asc=false").await;
+    assert!(ranked.windows(2).all(|w| chance(&w[0]) >= chance(&w[1])));
+
+    let ranked = get("armor_class=4&rank_by=thorax_shots_to_kill&rank_asc=true").await;
+    let shots = |x: &serde_json::Value| x["thorax_shots_to_kill"].as_u64().unwrap_or(u64::MAX);
+    assert!(ranked.windows(2).all(|w| shots(&w[0]) <= shots(&w[1])));
+
+    // ammo filters apply the same way they do on /ammo
+    let filtered = get("armor_class=4&damage=60").await;
+    assert!(
+        filtered
+            .iter()
+            .all(|x| x["ammo"]["damage"].as_i64() >= Some(60))
+    );
+
+    for query in [
+        "armor_class=9",
+        "strict=true&material=ceramc",
+        "strict=true&rank_by=damage",
+    ] {
+        let res = Client::new()
+            .get(format!("{URL}/ammo/effectiveness?{query}"))
+            .send()
+            .await
+            .expect("effectiveness endpoint failed");
+        assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST, "{query}");
+    }
+
+    let page: serde_json::Value = Client::new()
+        .get(format!(
+            "{URL}/v2/ammo/effectiveness?limit=5&rank_by=shots_to_penetrate&rank_asc=true"
+        ))
+        .send()
+        .await
+        .expect("v2 effectiveness endpoint failed")
+        .json()
+        .await
+        .expect("v2 effectiveness endpoint did not return json");
+    assert_eq!(page["items"].as_array().map(Vec::len), Some(5));
+    assert!(page["next_cursor"].is_string());
+}
+
+#[tokio::test]
+async fn test_ammo_market_data() {
+    let ammo = Ammo::get_request_vec(format!(
+        "{URL}/ammo?save=false&limit=500&embed_item=true&trader_level=2&sort=price:asc"
+    ))
+    .await;
+    assert!(!ammo.is_empty());
+
+    // ammo that can not be bought at this trader level comes last
+    let prices: Vec<Option<i32>> = ammo
+        .iter()
+        .map(|x| {
+            x.item
+                .as_ref()
+                .and_then(|item| item.cheapest_buy.as_ref())
+                .map(|buy| buy.price_rub)
+        })
+        .collect();
+    assert!(prices.windows(2).all(|w| match (w[0], w[1]) {
+        (Some(a), Some(b)) => a <= b,
+        (None, Some(_)) => false,
+        _ => true,
+    }));
+    assert!(
+        ammo.iter()
+            .filter_map(|x| x.item.as_ref()?.cheapest_buy.as_ref())
+            .all(|buy| buy.min_trader_level <= 2)
+    );
+
+    // the item is only there when it is asked for
+    let ammo = Ammo::get_request_vec(format!("{URL}/ammo?save=false&limit=5")).await;
+    assert!(ammo.iter().all(|x| x.item.is_none()));
+}
+
+// known FNV-1a values, a change here would reject every cursor already handed out
+#[test]
+fn test_filters_hash() {
+    assert_eq!(filters_hash(""), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(filters_hash("a"), 0xaf63_dc4c_8601_ec8c);
+    assert_eq!(filters_hash("foobar"), 0x8594_4171_f739_67e8);
+}
+
+// free text next to other fields must never give two different queries the same key
+#[test]
+fn test_text_cache_key() {
+    let key = |search: &str, suffix: &str| format!("{}e{suffix}", text_cache_key(search));
+    assert_ne!(key("ae", "true"), key("a", "etrue"));
+    assert_ne!(key("", "1"), key("e", ""));
+
+    let list = |x: &[&str]| list_cache_key(&x.iter().map(ToString::to_string).collect::<Vec<_>>());
+    assert_ne!(list(&["a,b"]), list(&["a", "b"]));
+    assert_ne!(list(&["1:a"]), list(&["a"]));
+}
+
+#[test]
+fn test_caliber_names() {
+    assert_eq!(caliber_name("Caliber556x45NATO"), "5.56x45mm NATO");
+    assert_eq!(caliber_name("Caliber12g"), "12/70 gauge");
+    // unknown calibers only lose the prefix
+    assert_eq!(caliber_name("Caliber999x99"), "999x99");
+    assert_eq!(caliber_name("Caliber"), "Caliber");
+}
+
+#[tokio::test]
+async fn test_weapons() {
+    let weapons: Vec<Weapon> = get_json(&format!("{URL}/weapons?limit=500"), None).await;
+    assert!(!weapons.is_empty());
+    // most ergonomic first by default
+    assert!(
+        weapons
+            .windows(2)
+            .all(|w| w[0].ergonomics >= w[1].ergonomics)
+    );
+    assert!(weapons.iter().all(|x| !x.caliber_name.is_empty()));
+
+    let weapon = &weapons[0];
+    let ammo: Vec<Ammo> = get_json(
+        &format!(
+            "{URL}/weapons/{}/ammo?sort_by=penetration_power&sort_asc=false&limit=500",
+            weapon._id
+        ),
+        None,
+    )
+    .await;
+    assert!(ammo.iter().all(|x| x.caliber == weapon.caliber));
+    assert!(
+        ammo.windows(2)
+            .all(|w| w[0].penetration_power >= w[1].penetration_power)
+    );
+
+    let same_caliber: Vec<Weapon> =
+        get_json(&format!("{URL}/weapons?caliber={}", weapon.caliber), None).await;
+    assert!(same_caliber.iter().all(|x| x.caliber == weapon.caliber));
+
+    let calibers: Vec<serde_json::Value> = get_json(&format!("{URL}/weapons/calibers"), None).await;
+    assert!(
+        calibers
+            .iter()
+            .any(|x| x["caliber"] == weapon.caliber.as_str())
+    );
+
+    let res = Client::new()
+        .get(format!("{URL}/weapons/000000000000000000000000/ammo"))
+        .send()
+        .await
+        .expect("weapon ammo endpoint failed");
+    assert_eq!(res.status(), reqwest::StatusCode::NOT_FOUND);
+
+    let res = Client::new()
+        .get(format!("{URL}/weapons?strict=true&sort_by=damage"))
+        .send()
+        .await
+        .expect("weapons endpoint failed");
+    assert_eq!(res.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let page: serde_json::Value = get_json(
+        &format!("{URL}/v2/weapons/{}/ammo?limit=1", weapon._id),
+        None,
+    )
+    .await;
+    assert!(page["total"].as_i64() >= Some(0));
+}
+
+#[tokio::test]
+async fn test_armor() {
+    let get = |query: &str| {
+        let url = format!("{URL}/armor?limit=500&{query}");
+        async move { get_json::<Vec<ArmorPiece>>(&url, None).await }
+    };
+
+    let armor = get("sort=armor_class:desc,weight:asc").await;
+    assert!(!armor.is_empty());
+    assert!(armor.windows(2).all(|w| {
+        w[0].armor_class > w[1].armor_class
+            || (w[0].armor_class == w[1].armor_class && w[0].weight <= w[1].weight)
+    }));
+
+    let helmets = get("armor_type=helmet&min_armor_class=3&max_weight=5").await;
+    assert!(
+        helmets
+            .iter()
+            .all(|x| x.armor_type == "helmet" && x.armor_class >= 3 && x.weight <= 5.0)
+    );
+
+    let thorax = get("zone=thorax").await;
+    assert!(
+        thorax
+            .iter()
+            .all(|x| x.zones.iter().any(|z| z.eq_ignore_ascii_case("thorax")))
+    );
+
+    let by_ids: Vec<ArmorPiece> = Client::new()
+        .get(format!("{URL}/armor/ids?ids={}", armor[0]._id))
+        .send()
+        .await
+        .expect("armor ids endpoint failed")
+        .json()
+        .await
+        .expect("armor ids endpoint did not return a list");
+    assert_eq!(by_ids.len(), 1);
+    assert_eq!(by_ids[0]._id, armor[0]._id);
+
+    // a real piece of armor can be used for the effectiveness model
+    let piece = armor.iter().find(|x| (1..=6).contains(&x.armor_class));
+    if let Some(piece) = piece {
+        let res = Client::new()
+            .get(format!(
+                "{URL}/ammo/effectiveness?armor_id={}&limit=5",
+                piece._id
+            ))
+            .send()
+            .await
+            .expect("effectiveness endpoint failed");
+        assert!(res.status().is_success());
+    }
+
+    for (query, status) in [
+        (
+            "/armor?strict=true&material=cardboard",
+            reqwest::StatusCode::BAD_REQUEST,
+        ),
+        (
+            "/armor?strict=true&sort=damage:desc",
+            reqwest::StatusCode::BAD_REQUEST,
+        ),
+        (
+            "/ammo/effectiveness?armor_id=000000000000000000000000",
+            reqwest::StatusCode::NOT_FOUND,
+        ),
+    ] {
+        let res = Client::new()
+            .get(format!("{URL}{query}"))
+            .send()
+            .await
+            .expect("armor endpoint failed");
+        assert_eq!(res.status(), status, "{query}");
+    }
+
+    let page: serde_json::Value = Client::new()
+        .get(format!("{URL}/v2/armor?limit=1"))
+        .send()
+        .await
+        .expect("v2 armor endpoint failed")
+        .json()
+        .await
+        .expect("v2 armor endpoint did not return json");
+    assert_eq!(page["items"].as_array().map(Vec::len), Some(1));
+    assert!(page["total"].as_i64() >= Some(1));
+}
+
+#[tokio::test]
+async fn test_task_path() {
+    // knock knock is the task where you meet the lightkeeper
+    const KNOCK_KNOCK: &str = "625d7005a4eb80027c4f2e09";
+
+    let device = TestDevice::new();
+    let get_path = |device_id: Option<&str>| {
+        let url = format!("{URL}/tasks/{KNOCK_KNOCK}/path");
+        let device_id = device_id.map(ToString::to_string);
+        async move { get_json::<serde_json::Value>(&url, device_id.as_deref()).await }
+    };
+    let ids = |path: &serde_json::Value| -> Vec<String> {
+        path["tasks"]
+            .as_array()
+            .map(|x| {
+                x.iter()
+                    .filter_map(|t| t["_id"].as_str().map(ToString::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let adj_list = get_adj_list().await;
+
+    let path = get_path(None).await;
+    let order = ids(&path);
+    assert!(order.len() > 1);
+    assert_eq!(order.last().map(String::as_str), Some(KNOCK_KNOCK));
+
+    // every prerequisite comes before the tasks it unlocks
+    for (i, id) in order.iter().enumerate() {
+        for (req_id, unlocks, _) in adj_list.get(id).into_iter().flatten() {
+            if !unlocks {
+                let pos = order.iter().position(|x| x == req_id);
+                assert!(
+                    pos.is_some_and(|pos| pos < i),
+                    "{req_id} is not before {id}"
+                );
+            }
+        }
+    }
+
+    let levels: Vec<i64> = path["tasks"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|x| x["min_player_level"].as_i64())
+        .collect();
+    assert_eq!(
+        path["min_player_level"].as_i64(),
+        levels.iter().max().copied()
+    );
+
+    // completing a task in the middle leaves it and everything before it out
+    let middle = order[order.len() / 2].clone();
+    device.set_complete(&middle, false).await;
+
+    let remaining = ids(&get_path(Some(device.id())).await);
+    assert!(remaining.len() < order.len());
+    assert!(!remaining.contains(&middle));
+    assert_eq!(remaining.last().map(String::as_str), Some(KNOCK_KNOCK));
+
+    device.clear_completed().await;
+
+    let res = Client::new()
+        .get(format!("{URL}/tasks/000000000000000000000000/path"))
+        .send()
+        .await
+        .expect("task path endpoint failed");
+    assert_eq!(res.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_task_graph() {
+    const KNOCK_KNOCK: &str = "625d7005a4eb80027c4f2e09";
+
+    let get_graph = |query: &str| {
+        let url = format!("{URL}/tasks/graph{query}");
+        async move { get_ok(&url, None).await }
+    };
+
+    let graph: TaskGraph = get_graph("")
+        .await
+        .json()
+        .await
+        .expect("task graph endpoint did not return json");
+    assert!(!graph.nodes.is_empty() && !graph.edges.is_empty());
+    let ids: HashSet<&str> = graph.nodes.iter().map(|x| x.task_id.as_str()).collect();
+    assert!(
+        graph
+            .edges
+            .iter()
+            .all(|x| ids.contains(x.from.as_str()) && ids.contains(x.to.as_str()))
+    );
+
+    let prapor: TaskGraph = get_graph("?trader=prapor")
+        .await
+        .json()
+        .await
+        .expect("task graph endpoint did not return json");
+    assert!(!prapor.nodes.is_empty() && prapor.nodes.len() < graph.nodes.len());
+    assert!(prapor.nodes.iter().all(|x| x.trader == "Prapor"));
+
+    let reachable: TaskGraph = get_graph("?task_id=625d7005a4eb80027c4f2e09")
+        .await
+        .json()
+        .await
+        .expect("task graph endpoint did not return json");
+    assert!(reachable.nodes.iter().any(|x| x.task_id == KNOCK_KNOCK));
+    assert!(reachable.nodes.len() < graph.nodes.len());
+
+    let res = get_graph("?format=dot").await;
+    assert_eq!(res.headers()["content-type"], "text/vnd.graphviz");
+    let dot = res
+        .text()
+        .await
+        .expect("task graph endpoint did not return text");
+    assert!(dot.starts_with("digraph tasks {") && dot.contains(" -> "));
+
+    let mermaid = get_graph("?format=mermaid")
+        .await
+        .text()
+        .await
+        .expect("task graph endpoint did not return text");
+    assert!(mermaid.starts_with("flowchart LR") && mermaid.contains(" -->|"));
+
+    let res = Client::new()
+        .get(format!(
+            "{URL}/tasks/graph?task_id=000000000000000000000000"
+        ))
+        .send()
+        .await
+        .expect("task graph endpoint failed");
+    assert_eq!(res.status(), reqwest::StatusCode::NOT_FOUND);
+}
+
+#[test]
+fn test_requirement_kind() {
+    let kind = |statuses: &[&str]| {
+        RequirementKind::from_statuses(
+            &statuses
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<String>>(),
+        )
+    };
+    assert_eq!(kind(&["complete"]), RequirementKind::Finished);
+    assert_eq!(kind(&["complete", "failed"]), RequirementKind::Finished);
+    assert_eq!(kind(&["active"]), RequirementKind::Accepted);
+    assert_eq!(kind(&["active", "complete"]), RequirementKind::Accepted);
+    assert_eq!(kind(&["failed"]), RequirementKind::Failed);
+    assert_eq!(kind(&[]), RequirementKind::Finished);
+}
+
+// completing a task must not complete a prerequisite it only needed accepted
+#[tokio::test]
+async fn test_set_complete_requirement_kinds() {
+    let device = TestDevice::new();
+    let adj_list = get_adj_list().await;
+
+    let (task_id, prerequisite) = adj_list
+        .iter()
+        .find_map(|(task_id, adjacent)| {
+            adjacent
+                .iter()
+                .find(|(_, unlocks, kind)| !unlocks && *kind == RequirementKind::Accepted)
+                .map(|(req_id, _, _)| (task_id.clone(), req_id.clone()))
+        })
+        .expect("no requirement only needs its prerequisite accepted");
+
+    device.set_complete(&task_id, false).await;
+    let done = device.completed().await;
+    assert!(done.contains(&task_id));
+    assert!(!done.contains(&prerequisite));
+
+    // the prerequisite was accepted so everything it needed finished is done
+    for (req_id, unlocks, kind) in &adj_list[&prerequisite] {
+        if !unlocks && *kind == RequirementKind::Finished {
+            assert!(done.contains(req_id));
+        }
+    }
+
+    // uncompleting the prerequisite leaves the task since the prerequisite can still be active
+    device.set_complete(&prerequisite, false).await;
+    device.set_complete(&prerequisite, true).await;
+    let done = device.completed().await;
+    assert!(done.contains(&task_id));
+    assert!(!done.contains(&prerequisite));
+
+    device.clear_completed().await;
+}
+
+#[tokio::test]
+async fn test_available_tasks() {
+    let device = TestDevice::new();
+    let get_available = |device_id: Option<&str>| {
+        let device_id = device_id.map(ToString::to_string);
+        async move {
+            get_json::<Vec<AvailableTask>>(
+                &format!("{URL}/tasks/available?limit=1000"),
+                device_id.as_deref(),
+            )
+            .await
+        }
+    };
+    let adj_list = get_adj_list().await;
+
+    // nothing is completed so only tasks that never need a prerequisite completed are available
+    let available = get_available(None).await;
+    assert!(!available.is_empty());
+    for AvailableTask { task, unlocks } in &available {
+        let adjacent = adj_list.get(&task._id).cloned().unwrap_or_default();
+        assert!(
+            adjacent
+                .iter()
+                .all(|(_, unlocks, kind)| *unlocks || *kind == RequirementKind::Accepted)
+        );
+        assert_eq!(*unlocks, adjacent.iter().filter(|x| x.1).count());
+    }
+
+    let first = available
+        .iter()
+        .find(|x| x.unlocks > 0)
+        .expect("no available task unlocks anything")
+        .task
+        ._id
+        .clone();
+
+    device.set_complete(&first, false).await;
+
+    let available = get_available(Some(device.id())).await;
+    assert!(available.iter().all(|x| x.task._id != first));
+    for AvailableTask { task, .. } in &available {
+        for (req_id, unlocks, kind) in adj_list.get(&task._id).into_iter().flatten() {
+            if !unlocks && *kind != RequirementKind::Accepted {
+                assert_eq!(req_id, &first);
+            }
+        }
+    }
+
+    // the same filter is an option on the task list
+    let tasks = Task::get_request_vec(format!("{URL}/tasks?available_only=true&limit=1000")).await;
+    assert_eq!(tasks.len(), get_available(None).await.len());
+
+    device.clear_completed().await;
+}
+
+#[test]
+fn test_find_task_graph_issues() {
+    let ids = |x: &[&str]| x.iter().map(ToString::to_string).collect::<Vec<String>>();
+    let task_ids = ids(&["a", "b", "c", "d", "e", "f"]);
+    let requirements: Vec<(String, String)> = [
+        ("a", "b"),
+        ("c", "d"),
+        ("d", "c"),
+        ("missing", "e"),
+        ("f", "f"),
+    ]
+    .iter()
+    .map(|(req_task_id, task_id)| (req_task_id.to_string(), task_id.to_string()))
+    .collect();
+
+    let issues = find_issues(&task_ids, &requirements);
+    let of_kind = |kind: &str| {
+        issues
+            .iter()
+            .filter(|x| x.kind == kind)
+            .map(|x| (x.task_id.as_str(), x.related_task_ids.clone()))
+            .collect::<Vec<(&str, Vec<String>)>>()
+    };
+
+    assert_eq!(
+        of_kind(DANGLING_REQUIREMENT),
+        vec![("e", ids(&["missing"]))]
+    );
+    assert_eq!(
+        of_kind(CYCLE),
+        vec![("c", ids(&["c", "d"])), ("f", ids(&["f"]))]
+    );
+    // e only requires a task that does not exist so it is cut off too
+    assert_eq!(of_kind(ORPHANED_TASK), vec![("e", vec![])]);
+}
+
+#[tokio::test]
+async fn test_task_graph_issues() {
+    let res = Client::new()
+        .get(format!("{URL}/admin/task_graph_issues"))
+        .send()
+        .await
+        .expect("task graph issues endpoint failed");
+    assert!(res.status().is_success());
+
+    let issues: Vec<TaskGraphIssue> = res
+        .json()
+        .await
+        .expect("task graph issues endpoint did not serialize correctly");
+    assert!(
+        issues
+            .iter()
+            .all(|x| [DANGLING_REQUIREMENT, CYCLE, ORPHANED_TASK].contains(&x.kind.as_str()))
+    );
+}
+
+#[derive(serde::Deserialize, Debug, PartialEq)]
+struct PartialEntry {
+    id: String,
+    price: i64,
+}
+
+fn partial_response() -> serde_json::Value {
+    serde_json::json!({
+        "errors": [
+            {"message": "price unavailable", "path": ["items", 1, "price"]},
+            {"message": "no path"}
+        ],
+        "data": {"items": [
+            {"id": "a", "price": 1},
+            {"id": "b", "price": null},
+            null,
+            {"id": "d", "price": 4}
+        ]}
+    })
+}
+
+#[test]
+fn test_graphql_error_path() {
+    let errors: Vec<GraphQLError> =
+        serde_json::from_value(partial_response()["errors"].clone()).expect("bad graphql errors");
+    assert_eq!(errors.len(), 2);
+    assert_eq!(errors[0].message, "price unavailable");
+    assert_eq!(errors[0].path_string(), "items.1.price");
+    assert!(errors[1].path.is_empty());
+    assert_eq!(errors[1].path_string(), "<root>");
+}
+
+#[test]
+fn test_partial_data_policy() {
+    let values: Vec<PartialEntry> =
+        parse_response(partial_response(), "items", PartialDataPolicy::Accept)
+            .expect("partial data was not accepted");
+    assert_eq!(
+        values,
+        vec![
+            PartialEntry {
+                id: String::from("a"),
+                price: 1
+            },
+            PartialEntry {
+                id: String::from("d"),
+                price: 4
+            },
+        ]
+    );
+
+    let err =
+        parse_response::<PartialEntry>(partial_response(), "items", PartialDataPolicy::Reject)
+            .expect_err("partial data was not rejected");
+    assert!(
+        err.to_string()
+            .starts_with("items query returned 2 graphql errors; items.1.price: price unavailable")
+    );
+
+    // without data there is nothing to accept
+    let mut no_data = partial_response();
+    no_data["data"] = serde_json::Value::Null;
+    assert!(parse_response::<PartialEntry>(no_data, "items", PartialDataPolicy::Accept).is_err());
+
+    // without errors every entry has to deserialize
+    let mut no_errors = partial_response();
+    no_errors.as_object_mut().unwrap().remove("errors");
+    assert!(parse_response::<PartialEntry>(no_errors, "items", PartialDataPolicy::Accept).is_err());
+}
+
+fn snapshot_test_dir() -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(format!("snapshots-{}", uuid::Uuid::new_v4()));
+    std::fs::create_dir_all(&dir).expect("failed to create snapshot test dir");
+    dir
+}
+
+#[test]
+fn test_list_snapshots() {
+    let dir = snapshot_test_dir();
+    for name in [
+        "items-20260102T000000.000Z.json.gz",
+        "items-20260101T000000.000Z.json.gz",
+        "items-20260101T120000.000Z.json.gz",
+        "tasks-20260101T000000.000Z.json.gz",
+        "items-20260101T000000.000Z.json",
+    ] {
+        std::fs::write(dir.join(name), b"").expect("failed to write snapshot");
+    }
+
+    let names: Vec<String> = list_snapshots(&dir, "items")
+        .expect("failed to list snapshots")
+        .iter()
+        .map(|x| x.file_name().unwrap().to_string_lossy().to_string())
+        .collect();
+    assert_eq!(
+        names,
+        vec![
+            "items-20260101T000000.000Z.json.gz",
+            "items-20260101T120000.000Z.json.gz",
+            "items-20260102T000000.000Z.json.gz",
+        ]
+    );
+    assert!(
+        list_snapshots(&dir.join("missing"), "items")
+            .expect("failed to list snapshots")
+            .is_empty()
+    );
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_snapshot_retention() {
+    let dir = snapshot_test_dir();
+    for i in 0..SNAPSHOT_RETENTION {
+        std::fs::write(
+            dir.join(format!("items-20200101T000000.{i:03}Z.json.gz")),
+            b"",
+        )
+        .expect("failed to write snapshot");
+    }
+
+    let path = archive_snapshot(&dir, "items", &serde_json::json!({}))
+        .expect("failed to archive snapshot");
+    let snapshots = list_snapshots(&dir, "items").expect("failed to list snapshots");
+    assert_eq!(snapshots.len(), SNAPSHOT_RETENTION);
+    assert!(!dir.join("items-20200101T000000.000Z.json.gz").exists());
+    assert!(dir.join("items-20200101T000000.001Z.json.gz").exists());
+    assert_eq!(snapshots.last(), Some(&path));
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_resolve_latest_snapshot() {
+    let dir = snapshot_test_dir();
+    assert!(resolve_snapshot(&dir, "items", "latest").is_err());
+
+    std::fs::write(dir.join("items-20200101T000000.000Z.json.gz"), b"")
+        .expect("failed to write snapshot");
+    let json = serde_json::json!({"data": {"items": [{"id": "a"}]}});
+    let path = archive_snapshot(&dir, "items", &json).expect("failed to archive snapshot");
+
+    let latest = resolve_snapshot(&dir, "items", "latest").expect("failed to resolve latest");
+    assert_eq!(latest, path);
+    assert_eq!(
+        read_snapshot(&latest).expect("failed to read snapshot"),
+        json
+    );
+    assert_eq!(
+        resolve_snapshot(&dir, "items", "some/other.json.gz").expect("failed to resolve path"),
+        std::path::PathBuf::from("some/other.json.gz")
+    );
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn test_parse_duration() {
+    assert_eq!(
+        parse_duration("30d").unwrap(),
+        Duration::from_secs(3600 * 24 * 30)
+    );
+    assert_eq!(
+        parse_duration("12h").unwrap(),
+        Duration::from_secs(3600 * 12)
+    );
+    assert_eq!(parse_duration("90m").unwrap(), Duration::from_secs(90 * 60));
+    assert_eq!(parse_duration("60s").unwrap(), Duration::from_secs(60));
+    assert_eq!(parse_duration("60").unwrap(), Duration::from_secs(60));
+
+    for bad in [
+        "0d",
+        "0",
+        "",
+        "d",
+        "-1d",
+        "5w",
+        "1.5h",
+        "18446744073709551615d",
+    ] {
+        assert!(parse_duration(bad).is_err(), "{bad} should not parse");
+    }
+}
+
+#[test]
+fn test_parse_args() {
+    let parse = |args: &[&str]| {
+        parse_args(
+            &args
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<String>>(),
+        )
+    };
+
+    assert_eq!(parse(&[]).unwrap(), Command::Serve);
+    assert_eq!(parse(&["serve"]).unwrap(), Command::Serve);
+    assert_eq!(parse(&["migrate"]).unwrap(), Command::Migrate);
+    assert_eq!(parse(&["check"]).unwrap(), Command::Check);
+    assert_eq!(
+        parse(&["import", "--file", "items.json", "--dataset", "Items"]).unwrap(),
+        Command::Import {
+            dataset: Dataset::Items,
+            file: String::from("items.json")
+        }
+    );
+    assert_eq!(
+        parse(&["export", "--dataset", "ammo"]).unwrap(),
+        Command::Export {
+            dataset: Dataset::Ammo,
+            file: None
+        }
+    );
+    assert_eq!(
+        parse(&["refresh", "--dataset", "weapons"]).unwrap(),
+        Command::Refresh {
+            dataset: Dataset::Weapons
+        }
+    );
+    assert_eq!(
+        parse(&["replay", "--dataset", "armor"]).unwrap(),
+        Command::Replay {
+            dataset: Dataset::Armor,
+            snapshot: String::from("latest")
+        }
+    );
+    assert_eq!(
+        parse(&["prune-devices"]).unwrap(),
+        Command::PruneDevices {
+            older_than: DEVICE_PREFERENCES_MAX_AGE
+        }
+    );
+    assert_eq!(
+        parse(&["prune-devices", "--older-than", "12h"]).unwrap(),
+        Command::PruneDevices {
+            older_than: Duration::from_secs(3600 * 12)
+        }
+    );
+
+    assert!(parse(&["import", "--dataset", "items"]).is_err());
+    assert!(parse(&["import", "--dataset", "guns", "--file", "x.json"]).is_err());
+    assert!(parse(&["export", "--dataset"]).is_err());
+    assert!(parse(&["prune-devices", "--older-than", "0d"]).is_err());
+    assert!(parse(&["help"]).is_err());
+    assert!(parse(&["unknown"]).is_err());
+}
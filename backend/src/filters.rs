@@ -0,0 +1,45 @@
+use sqlx::{Encode, Postgres, QueryBuilder, Type};
+use std::fmt::Display;
+
+// inclusive bounds on an expression, a missing bound is not filtered on
+pub fn push_range<'a, T>(
+    qb: &mut QueryBuilder<'a, Postgres>,
+    column: &str,
+    min: Option<T>,
+    max: Option<T>,
+) where
+    T: Encode<'a, Postgres> + Type<Postgres> + Send + 'a,
+{
+    if let Some(min) = min {
+        qb.push("AND ")
+            .push(column)
+            .push(" >= ")
+            .push_bind(min)
+            .push(" ");
+    }
+    if let Some(max) = max {
+        qb.push("AND ")
+            .push(column)
+            .push(" <= ")
+            .push_bind(max)
+            .push(" ");
+    }
+}
+
+// free text in a cache key with its length in front, so a crafted search can not run into
+// the fields after it and end up with the key of a different query
+pub fn text_cache_key(text: &str) -> String {
+    format!("{}:{}", text.len(), text)
+}
+
+// a list of free text values the same way, led by how many there are
+pub fn list_cache_key(values: &[String]) -> String {
+    let texts: String = values.iter().map(|x| text_cache_key(x)).collect();
+    format!("{}:{}", values.len(), texts)
+}
+
+// how a range shows up in a cache key, min-max with an empty side for a missing bound
+pub fn range_cache_key<T: Display>(min: Option<T>, max: Option<T>) -> String {
+    let bound = |v: Option<T>| v.map_or_else(String::new, |v| v.to_string());
+    format!("{}-{}", bound(min), bound(max))
+}
@@ -0,0 +1,139 @@
+use crate::database_types::DatasetRefresh;
+use crate::init_app_state::{AppState, MIGRATOR, table_counts};
+use crate::query_types::ErrorBody;
+use crate::query_types::{
+    AppError, AppErrorHandling, DatasetHealth, HealthDetails, PoolStats, ReadyStatus,
+};
+use crate::telemetry::db_span;
+use axum::{extract::State, http::StatusCode, response::Json};
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::RwLock;
+use tracing::Instrument;
+
+// the process is up and able to respond which is all a liveness probe needs
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "health",
+    responses((status = 200, body = String))
+)]
+pub async fn healthz() -> &'static str {
+    "ok"
+}
+
+// checks that every migration embedded in the binary has been applied successfully
+async fn migrations_applied(pgpool: &PgPool) -> Result<bool, sqlx::Error> {
+    let applied: Vec<i64> =
+        sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success = TRUE")
+            .fetch_all(pgpool)
+            .await?;
+
+    Ok(MIGRATOR
+        .iter()
+        .filter(|x| x.migration_type.is_up_migration())
+        .all(|x| applied.contains(&x.version)))
+}
+
+// returns 503 until the database is reachable, migrated and items, tasks and ammo have rows
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    responses(
+        (status = 200, body = ReadyStatus),
+        (status = 503, body = ReadyStatus)
+    )
+)]
+pub async fn readyz(State(app_state): State<AppState>) -> (StatusCode, Json<ReadyStatus>) {
+    let (counts, migrations) = tokio::join!(
+        table_counts(&app_state.pgpool),
+        migrations_applied(&app_state.pgpool)
+    );
+
+    let database_reachable = counts.is_ok() && migrations.is_ok();
+    // weapons and armor are left out so an empty optional dataset never keeps the pod unready,
+    // /health/details still reports their counts
+    let data_initialized =
+        counts.is_ok_and(|(items, tasks, ammo, _, _)| items > 0 && tasks > 0 && ammo > 0);
+    let migrations_applied = migrations.unwrap_or(false);
+
+    let ready = database_reachable && data_initialized && migrations_applied;
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        Json(ReadyStatus {
+            ready,
+            database_reachable,
+            data_initialized,
+            migrations_applied,
+        }),
+    )
+}
+
+async fn time_till_refresh_secs(timer: &Arc<RwLock<Instant>>) -> u64 {
+    timer
+        .read()
+        .await
+        .saturating_duration_since(Instant::now())
+        .as_secs()
+}
+
+// per dataset counts and refresh times along with pool and cache usage
+#[utoipa::path(
+    get,
+    path = "/health/details",
+    tag = "health",
+    responses(
+        (status = 200, body = HealthDetails),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn health_details(
+    State(app_state): State<AppState>,
+) -> Result<Json<HealthDetails>, AppError> {
+    let (items_count, tasks_count, ammo_count, weapons_count, armor_count) =
+        table_counts(&app_state.pgpool)
+            .await
+            .bad_sql("Health Details")?;
+
+    let refreshes = sqlx::query_as!(DatasetRefresh, "SELECT * FROM DatasetRefresh")
+        .fetch_all(&app_state.pgpool)
+        .instrument(db_span("Dataset Refresh"))
+        .await
+        .bad_sql("Dataset Refresh")?;
+
+    let mut datasets = vec![];
+    for (name, row_count, timer) in [
+        ("items", items_count, &app_state.next_items_call_timer),
+        ("tasks", tasks_count, &app_state.next_tasks_call_timer),
+        ("ammo", ammo_count, &app_state.next_ammo_call_timer),
+        ("weapons", weapons_count, &app_state.next_weapons_call_timer),
+        ("armor", armor_count, &app_state.next_armor_call_timer),
+    ] {
+        let refresh = refreshes.iter().find(|x| x.page == name);
+        datasets.push(DatasetHealth {
+            name,
+            row_count,
+            last_refresh: refresh.map(|x| x.refreshed_at),
+            last_refresh_source: refresh.map(|x| x.source.clone()),
+            time_till_refresh_secs: time_till_refresh_secs(timer).await,
+        });
+    }
+
+    Ok(Json(HealthDetails {
+        datasets,
+        pool: PoolStats {
+            size: app_state.pgpool.size(),
+            idle: app_state.pgpool.num_idle(),
+            max_connections: app_state.pgpool.options().get_max_connections(),
+        },
+        cache_entries: app_state.cache.entry_count(),
+    }))
+}
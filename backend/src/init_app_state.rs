@@ -1,13 +1,18 @@
+use crate::app_metrics::install_recorder;
 use crate::caching::AppCache;
-use crate::deserialize_json_types::{Ammo, Item, Task};
+use crate::deserialize_json_types::{Ammo, Armor, Item, Task, Weapon};
 use crate::upsert::Upsert;
 use anyhow::Result;
 //use dashmap::DashMap;
+use metrics_exporter_prometheus::PrometheusHandle;
 use sqlx::PgPool;
+use sqlx::migrate::Migrator;
 use sqlx::postgres::PgPoolOptions;
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+use tokio_util::task::TaskTracker;
 
 #[derive(Clone)]
 pub struct AppState {
@@ -17,32 +22,49 @@ pub struct AppState {
     pub next_items_call_timer: Arc<RwLock<Instant>>,
     pub next_tasks_call_timer: Arc<RwLock<Instant>>,
     pub next_ammo_call_timer: Arc<RwLock<Instant>>,
+    pub next_weapons_call_timer: Arc<RwLock<Instant>>,
+    pub next_armor_call_timer: Arc<RwLock<Instant>>,
+    // every background task is spawned on this so shutdown can wait for upserts to finish
+    pub background_tracker: TaskTracker,
+    pub metrics: PrometheusHandle,
 }
 
-const ITEMS_FILE: &str = "most_recent_items.json";
+pub const ITEMS_FILE: &str = "most_recent_items.json";
 pub const ITEM_SLEEP_TIME: u64 = 900;
 pub const ITEMS_UNIQUE_CACHE_PREFIX: char = '!';
 
-const TASKS_FILE: &str = "most_recent_tasks.json";
+pub const TASKS_FILE: &str = "most_recent_tasks.json";
 const TASK_SLEEP_TIME: u64 = 3600 * 24;
 pub const TASKS_UNIQUE_CACHE_PREFIX: char = '@';
 
-const AMMO_FILE: &str = "most_recent_ammo.json";
+pub const AMMO_FILE: &str = "most_recent_ammo.json";
 const AMMO_SLEEP_TIME: u64 = 3600 * 24;
 pub const AMMO_UNIQUE_CACHE_PREFIX: char = '#';
 
+pub const WEAPONS_FILE: &str = "most_recent_weapons.json";
+const WEAPON_SLEEP_TIME: u64 = 3600 * 24;
+pub const WEAPONS_UNIQUE_CACHE_PREFIX: char = '%';
+
+pub const ARMOR_FILE: &str = "most_recent_armor.json";
+const ARMOR_SLEEP_TIME: u64 = 3600 * 24;
+pub const ARMOR_UNIQUE_CACHE_PREFIX: char = '^';
+
 const DELETE_DEVICE_PREFERENCES_TIME: u64 = 3600 * 24;
+pub const DEVICE_PREFERENCES_MAX_AGE: Duration = Duration::from_secs(3600 * 24 * 30);
 
 // this is the max total size of the item history table in db where 1 entry gets added every ITEM_SLEEP_TIME for every item
 pub const ITEM_HISTORY_SIZE: i64 = 500;
 
-pub async fn init_app_state(postgres_url: String, _redis_url: String) -> Result<AppState> {
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+// waits for the database to come up and runs any migrations that have not been applied
+pub async fn connect_db(postgres_url: &str) -> Result<PgPool> {
     let pgpool = loop {
         match PgPoolOptions::new()
             .min_connections(1)
             .max_connections(10)
             .idle_timeout(Duration::from_secs(60))
-            .connect(&postgres_url)
+            .connect(postgres_url)
             .await
         {
             Ok(p) => break p,
@@ -52,9 +74,21 @@ pub async fn init_app_state(postgres_url: String, _redis_url: String) -> Result<
             }
         }
     };
-    sqlx::migrate!("./migrations").run(&pgpool).await?;
+    MIGRATOR.run(&pgpool).await?;
+
+    Ok(pgpool)
+}
+
+pub async fn init_app_state(
+    postgres_url: String,
+    _redis_url: String,
+    shutdown: &CancellationToken,
+) -> Result<AppState> {
+    let pgpool = connect_db(&postgres_url).await?;
+    let background_tracker = TaskTracker::new();
+    let metrics = install_recorder()?;
 
-    init_data(&pgpool).await?;
+    init_data(&pgpool, &background_tracker).await?;
 
     // let redispool = bb8::Pool::builder()
     //     .connection_timeout(Duration::from_millis(100))
@@ -65,136 +99,157 @@ pub async fn init_app_state(postgres_url: String, _redis_url: String) -> Result<
     let next_items_call_timer = Arc::new(RwLock::new(Instant::now()));
     let next_tasks_call_timer = Arc::new(RwLock::new(Instant::now()));
     let next_ammo_call_timer = Arc::new(RwLock::new(Instant::now()));
+    let next_weapons_call_timer = Arc::new(RwLock::new(Instant::now()));
+    let next_armor_call_timer = Arc::new(RwLock::new(Instant::now()));
 
     let cache = AppCache::new();
 
-    background_tasks(
-        &cache,
-        &next_items_call_timer,
-        &next_tasks_call_timer,
-        &next_ammo_call_timer,
-        &pgpool,
-    );
-
     //let rate_limit = Arc::new(DashMap::new());
 
-    Ok(AppState {
+    let app_state = AppState {
         pgpool,
         cache,
         //rate_limit,
         next_items_call_timer,
         next_tasks_call_timer,
         next_ammo_call_timer,
-    })
+        next_weapons_call_timer,
+        next_armor_call_timer,
+        background_tracker,
+        metrics,
+    };
+
+    background_tasks(&app_state, shutdown);
+
+    Ok(app_state)
 }
 
-// this initializes the database
-async fn init_data(pgpool: &PgPool) -> Result<()> {
-    let (items_count, tasks_count, ammo_count): (i64, i64, i64) = tokio::try_join!(
+// returns the row counts of the Item, Task, Ammo, Weapon and Armor tables
+pub async fn table_counts(pgpool: &PgPool) -> Result<(i64, i64, i64, i64, i64), sqlx::Error> {
+    tokio::try_join!(
         sqlx::query_scalar("SELECT COUNT(*) FROM Item").fetch_one(pgpool),
         sqlx::query_scalar("SELECT COUNT(*) FROM Task").fetch_one(pgpool),
-        sqlx::query_scalar("SELECT COUNT(*) FROM Ammo").fetch_one(pgpool)
-    )?;
+        sqlx::query_scalar("SELECT COUNT(*) FROM Ammo").fetch_one(pgpool),
+        sqlx::query_scalar("SELECT COUNT(*) FROM Weapon").fetch_one(pgpool),
+        sqlx::query_scalar("SELECT COUNT(*) FROM Armor").fetch_one(pgpool)
+    )
+}
+
+// deletes the preferences of every device that has not visited within older_than
+pub async fn prune_devices(pgpool: &PgPool, older_than: Duration) -> Result<u64, sqlx::Error> {
+    let v = sqlx::query!(
+        "DELETE FROM DevicePreferences WHERE last_visited < NOW() - make_interval(secs => $1)",
+        older_than.as_secs_f64()
+    )
+    .execute(pgpool)
+    .await?;
+
+    Ok(v.rows_affected())
+}
+
+// this initializes the database
+async fn init_data(pgpool: &PgPool, tracker: &TaskTracker) -> Result<()> {
+    let (items_count, tasks_count, ammo_count, weapons_count, armor_count) =
+        table_counts(pgpool).await?;
 
     if items_count == 0 {
         let pgpool = pgpool.clone();
-        tokio::spawn(async move { Item::init(ITEMS_FILE, pgpool).await });
+        tracker.spawn(async move { Item::init(ITEMS_FILE, pgpool).await });
     }
 
     if tasks_count == 0 {
         let pgpool = pgpool.clone();
-        tokio::spawn(async move { Task::init(TASKS_FILE, pgpool).await });
+        tracker.spawn(async move { Task::init(TASKS_FILE, pgpool).await });
     }
 
     if ammo_count == 0 {
         let pgpool = pgpool.clone();
-        tokio::spawn(async move { Ammo::init(AMMO_FILE, pgpool).await });
+        tracker.spawn(async move { Ammo::init(AMMO_FILE, pgpool).await });
+    }
+
+    if weapons_count == 0 {
+        let pgpool = pgpool.clone();
+        tracker.spawn(async move { Weapon::init(WEAPONS_FILE, pgpool).await });
+    }
+
+    if armor_count == 0 {
+        let pgpool = pgpool.clone();
+        tracker.spawn(async move { Armor::init(ARMOR_FILE, pgpool).await });
     }
 
     Ok(())
 }
 
-// this spawns all of the background tasks that the app will need
-fn background_tasks(
-    cache: &AppCache,
-    next_items_call_timer: &Arc<RwLock<Instant>>,
-    next_tasks_call_timer: &Arc<RwLock<Instant>>,
-    next_ammo_call_timer: &Arc<RwLock<Instant>>,
-    pgpool: &PgPool,
+// keeps refreshing one dataset via api call every sleep_time seconds until shutdown
+fn spawn_refresh<T: Upsert>(
+    app_state: &AppState,
+    file: &'static str,
+    timer: &Arc<RwLock<Instant>>,
+    sleep_time: u64,
+    shutdown: &CancellationToken,
 ) {
-    let pgpool1 = pgpool.clone();
-    let pgpool2 = pgpool.clone();
-    let pgpool3 = pgpool.clone();
-    let pgpool4 = pgpool.clone();
-    let items_call = next_items_call_timer.clone();
-    let tasks_call = next_tasks_call_timer.clone();
-    let ammo_call = next_ammo_call_timer.clone();
-
-    let mut cache1 = cache.clone();
-    let mut cache2 = cache.clone();
-    let mut cache3 = cache.clone();
-
-    // spawn background task to refresh items in the database via api call
-    tokio::spawn(async move {
-        let pgpool1 = pgpool1;
-        let items_call = items_call;
-        loop {
-            Item::background_task(
-                ITEMS_FILE,
-                &items_call,
-                ITEM_SLEEP_TIME,
-                &mut cache1,
-                &pgpool1,
-            )
-            .await;
-        }
-    });
-
-    // spawn background task to refresh tasks in the database via api call
-    tokio::spawn(async move {
-        let pgpool2 = pgpool2;
-        let tasks_call = tasks_call;
-        loop {
-            Task::background_task(
-                TASKS_FILE,
-                &tasks_call,
-                TASK_SLEEP_TIME,
-                &mut cache2,
-                &pgpool2,
-            )
-            .await;
+    let mut cache = app_state.cache.clone();
+    let pgpool = app_state.pgpool.clone();
+    let timer = timer.clone();
+    let shutdown = shutdown.clone();
+    app_state.background_tracker.spawn(async move {
+        while !shutdown.is_cancelled() {
+            T::background_task(file, &timer, sleep_time, &mut cache, &pgpool, &shutdown).await;
         }
     });
+}
 
-    // spawn background task to refresh ammo via api call
-    tokio::spawn(async move {
-        let pgpool3 = pgpool3;
-        let ammo_call = ammo_call;
-        loop {
-            Ammo::background_task(
-                AMMO_FILE,
-                &ammo_call,
-                AMMO_SLEEP_TIME,
-                &mut cache3,
-                &pgpool3,
-            )
-            .await;
-        }
-    });
+// this spawns all of the background tasks that the app will need
+fn background_tasks(app_state: &AppState, shutdown: &CancellationToken) {
+    spawn_refresh::<Item>(
+        app_state,
+        ITEMS_FILE,
+        &app_state.next_items_call_timer,
+        ITEM_SLEEP_TIME,
+        shutdown,
+    );
+    spawn_refresh::<Task>(
+        app_state,
+        TASKS_FILE,
+        &app_state.next_tasks_call_timer,
+        TASK_SLEEP_TIME,
+        shutdown,
+    );
+    spawn_refresh::<Ammo>(
+        app_state,
+        AMMO_FILE,
+        &app_state.next_ammo_call_timer,
+        AMMO_SLEEP_TIME,
+        shutdown,
+    );
+    spawn_refresh::<Weapon>(
+        app_state,
+        WEAPONS_FILE,
+        &app_state.next_weapons_call_timer,
+        WEAPON_SLEEP_TIME,
+        shutdown,
+    );
+    spawn_refresh::<Armor>(
+        app_state,
+        ARMOR_FILE,
+        &app_state.next_armor_call_timer,
+        ARMOR_SLEEP_TIME,
+        shutdown,
+    );
 
     // spawn background task to delete device preferences that are inactive
-    tokio::spawn(async move {
+    let pgpool = app_state.pgpool.clone();
+    let shutdown = shutdown.clone();
+    app_state.background_tracker.spawn(async move {
         loop {
-            tokio::time::sleep(Duration::from_secs(DELETE_DEVICE_PREFERENCES_TIME)).await;
-            let v = sqlx::query!(
-                r#"DELETE FROM DevicePreferences WHERE last_visited < NOW() - INTERVAL '30 days'"#,
-            )
-            .execute(&pgpool4)
-            .await;
-
-            match v {
-                Ok(value) => {
-                    tracing::info!("successfully deleted {} rows", value.rows_affected());
+            tokio::select! {
+                () = shutdown.cancelled() => break,
+                () = tokio::time::sleep(Duration::from_secs(DELETE_DEVICE_PREFERENCES_TIME)) => {}
+            }
+
+            match prune_devices(&pgpool, DEVICE_PREFERENCES_MAX_AGE).await {
+                Ok(rows_affected) => {
+                    tracing::info!("successfully deleted {} rows", rows_affected);
                 }
                 Err(e) => {
                     tracing::error!("failed to delete old device preferences with error {}", e);
@@ -1,18 +1,41 @@
 use crate::api_routers::Device;
 use crate::database_types::{
-    BuyFor, DeviceItemQueryParams, FieldValue, Item, ItemFromDB, SavedItemData, SellFor,
+    BuyFor, DeviceItemQueryParams, Item, ItemFromDB, SavedItemData, SellFor,
 };
+use crate::filters::{push_range, range_cache_key, text_cache_key};
 use crate::init_app_state::{AppState, ITEM_SLEEP_TIME, ITEMS_UNIQUE_CACHE_PREFIX};
-use crate::query_types::{AppError, AppError::BadRequest};
-use crate::query_types::{AppErrorHandling, ItemHistoryQueryParams, ItemQueryParams, ItemStats};
+use crate::pagination::{
+    Cursor, SortKey, cached_count, parse_sort, push_keyset_condition, push_order_by,
+    resolve_keyset, sort_to_string,
+};
+use crate::query_types::ErrorBody;
+use crate::query_types::{AppError, AppError::Unauthorized};
+use crate::query_types::{
+    AppErrorHandling, ItemHistoryQueryParams, ItemQueryParams, ItemStats, VALID_ITEM_SORT_BY,
+};
+use crate::telemetry::db_span;
+use crate::validation::{ITEM_FIELD_RULES, validate_strict};
 use ahash::AHashMap as HashMap;
-use axum::{extract::State, response::Json};
+use axum::{
+    extract::{RawQuery, State},
+    response::Json,
+};
 use axum_extra::extract::Query;
 use sqlx::types::Uuid;
 use sqlx::{PgPool, Postgres};
 use std::time::Instant;
+use tracing::Instrument;
 
 // gives data on different interesting stats about the data stored
+#[utoipa::path(
+    get,
+    path = "/stats",
+    tag = "items",
+    responses(
+        (status = 200, body = ItemStats),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
 pub async fn item_stats(State(app_state): State<AppState>) -> Result<Json<ItemStats>, AppError> {
     let time_in_seconds = app_state
         .next_items_call_timer
@@ -30,6 +53,7 @@ pub async fn item_stats(State(app_state): State<AppState>) -> Result<Json<ItemSt
 
     let items_count = sqlx::query_scalar!("SELECT COUNT(*) FROM Item")
         .fetch_one(&app_state.pgpool)
+        .instrument(db_span("Item Stats"))
         .await
         .bad_sql("Item Stats")?
         .unwrap_or(0);
@@ -55,6 +79,7 @@ pub async fn items_from_db_to_items(
     let ids: Vec<String> = items_from_db.iter().map(|item| item._id.clone()).collect();
     let buy_for_vec = sqlx::query_as!(BuyFor, "SELECT * FROM BuyFor WHERE item_id = ANY($1)", &ids)
         .fetch_all(&mut *txn)
+        .instrument(db_span("BuyFor and SellFor"))
         .await
         .bad_sql("BuyFor and SellFor")?;
 
@@ -64,6 +89,7 @@ pub async fn items_from_db_to_items(
         &ids
     )
     .fetch_all(&mut *txn)
+    .instrument(db_span("BuyFor and SellFor"))
     .await
     .bad_sql("BuyFor and SellFor")?;
 
@@ -102,7 +128,13 @@ fn save_item_query_parms(device_id: Uuid, query_parms: ItemQueryParams, pgpool:
     tokio::spawn(async move {
         let _ = sqlx::query!(
             "UPDATE ItemQueryParams
-                    SET search = $2, sort_asc = $3, sort_by = $4, item_type = $5 WHERE id = $1",
+                    SET search = $2, sort_asc = $3, sort_by = $4, item_type = $5, sort = $6,
+                    min_base_price = $7, max_base_price = $8, min_flea_price = $9, max_flea_price = $10,
+                    min_per_slot = $11, max_per_slot = $12,
+                    min_buy_from_flea_instant_profit = $13, max_buy_from_flea_instant_profit = $14,
+                    min_buy_from_trader_instant_profit = $15, max_buy_from_trader_instant_profit = $16,
+                    min_slots = $17, max_slots = $18, min_avg_24h_price = $19, max_avg_24h_price = $20,
+                    is_flea = $21, sellable_to = $22 WHERE id = $1",
             device_id,
             query_parms.search,
             query_parms.sort_asc,
@@ -116,12 +148,188 @@ fn save_item_query_parms(device_id: Uuid, query_parms: ItemQueryParams, pgpool:
             } else {
                 query_parms.item_type
             },
+            sort_to_string(&parse_sort(&query_parms.sort, VALID_ITEM_SORT_BY)),
+            query_parms.min_base_price,
+            query_parms.max_base_price,
+            query_parms.min_flea_price,
+            query_parms.max_flea_price,
+            query_parms.min_per_slot,
+            query_parms.max_per_slot,
+            query_parms.min_buy_from_flea_instant_profit,
+            query_parms.max_buy_from_flea_instant_profit,
+            query_parms.min_buy_from_trader_instant_profit,
+            query_parms.max_buy_from_trader_instant_profit,
+            query_parms.min_slots,
+            query_parms.max_slots,
+            query_parms.min_avg_24h_price,
+            query_parms.max_avg_24h_price,
+            query_parms.is_flea,
+            if query_parms.sellable_to.is_empty() {
+                "any".to_string()
+            } else {
+                query_parms.sellable_to
+            },
         )
         .execute(&pgpool)
         .await;
     });
 }
 
+// flea_market sorts on the joined BuyFor price every other column is on Item
+fn item_column_sql(column: &str) -> String {
+    if column == "flea_market" {
+        "b.price_rub".to_string()
+    } else {
+        format!("i.{column}")
+    }
+}
+
+// every filter that changes which rows come back, sort and paging are added by the callers
+pub fn item_filters_cache_key(query_parms: &ItemQueryParams) -> String {
+    format!(
+        "{}b{}f{}s{}pf{}pt{}sl{}a{}fl{:?}t{}s{}",
+        text_cache_key(&query_parms.item_type),
+        range_cache_key(query_parms.min_base_price, query_parms.max_base_price),
+        range_cache_key(query_parms.min_flea_price, query_parms.max_flea_price),
+        range_cache_key(query_parms.min_per_slot, query_parms.max_per_slot),
+        range_cache_key(
+            query_parms.min_buy_from_flea_instant_profit,
+            query_parms.max_buy_from_flea_instant_profit
+        ),
+        range_cache_key(
+            query_parms.min_buy_from_trader_instant_profit,
+            query_parms.max_buy_from_trader_instant_profit
+        ),
+        range_cache_key(query_parms.min_slots, query_parms.max_slots),
+        range_cache_key(query_parms.min_avg_24h_price, query_parms.max_avg_24h_price),
+        query_parms.is_flea,
+        text_cache_key(&query_parms.sellable_to.to_lowercase()),
+        text_cache_key(&query_parms.search),
+    )
+}
+
+// the FROM and WHERE shared by the items query and its count so both see the same filter set
+fn push_item_filters(
+    qb: &mut sqlx::QueryBuilder<'_, Postgres>,
+    sort: &[SortKey],
+    query_parms: &ItemQueryParams,
+) {
+    let sorts_by = |column: &str| sort.iter().any(|k| k.column == column);
+    let is_flea = sorts_by("flea_market")
+        || sorts_by("buy_from_flea_instant_profit")
+        || sorts_by("buy_from_trader_instant_profit")
+        || sorts_by("per_slot")
+        || sorts_by("avg_24h_price")
+        || sorts_by("change_last_48h_percent");
+
+    if sorts_by("flea_market") {
+        qb.push("FROM Item i LEFT JOIN BuyFor b ON i._id = b.item_id WHERE LOWER(b.trader_name) = 'flea market' ");
+    } else {
+        qb.push("FROM Item i WHERE 1=1 ");
+    }
+
+    let search = &query_parms.search;
+    if !search.is_empty() {
+        qb.push("AND (i.item_name ILIKE ")
+            .push_bind(format!("%{}%", search))
+            .push(" OR i.item_name % ")
+            .push_bind(search.clone())
+            .push(") ");
+    }
+
+    qb.push("AND i.item_types ILIKE ")
+        .push_bind(format!("%{}%", query_parms.item_type))
+        .push(" ");
+
+    if is_flea {
+        qb.push("AND i.is_flea = TRUE ");
+    }
+
+    if let Some(is_flea) = query_parms.is_flea {
+        qb.push("AND i.is_flea = ").push_bind(is_flea).push(" ");
+    }
+
+    for (column, min, max) in [
+        (
+            "i.base_price",
+            query_parms.min_base_price,
+            query_parms.max_base_price,
+        ),
+        (
+            "i.per_slot",
+            query_parms.min_per_slot,
+            query_parms.max_per_slot,
+        ),
+        (
+            "i.buy_from_flea_instant_profit",
+            query_parms.min_buy_from_flea_instant_profit,
+            query_parms.max_buy_from_flea_instant_profit,
+        ),
+        (
+            "i.buy_from_trader_instant_profit",
+            query_parms.min_buy_from_trader_instant_profit,
+            query_parms.max_buy_from_trader_instant_profit,
+        ),
+        (
+            "i.width * i.height",
+            query_parms.min_slots,
+            query_parms.max_slots,
+        ),
+        (
+            "i.avg_24h_price",
+            query_parms.min_avg_24h_price,
+            query_parms.max_avg_24h_price,
+        ),
+    ] {
+        push_range(qb, column, min, max);
+    }
+
+    // a subquery rather than the flea_market join so it works with any sort
+    if query_parms.min_flea_price.is_some() || query_parms.max_flea_price.is_some() {
+        qb.push("AND EXISTS (SELECT 1 FROM BuyFor f WHERE f.item_id = i._id AND LOWER(f.trader_name) = 'flea market' ");
+        push_range(
+            qb,
+            "f.price_rub",
+            query_parms.min_flea_price,
+            query_parms.max_flea_price,
+        );
+        qb.push(") ");
+    }
+
+    if !query_parms.sellable_to.is_empty() {
+        qb.push("AND EXISTS (SELECT 1 FROM SellFor s WHERE s.item_id = i._id AND LOWER(s.trader_name) = ")
+            .push_bind(query_parms.sellable_to.to_lowercase())
+            .push(") ");
+    }
+}
+
+// total rows for a filter set so pages can show how many there are
+pub async fn count_items(
+    app_state: &AppState,
+    query_parms: &ItemQueryParams,
+) -> Result<i64, AppError> {
+    let sort = query_parms.sort_keys();
+    let cache_key = format!(
+        "{}count{}{}",
+        ITEMS_UNIQUE_CACHE_PREFIX,
+        sort_to_string(&sort),
+        item_filters_cache_key(query_parms),
+    );
+
+    let mut qb: sqlx::QueryBuilder<'_, Postgres> =
+        sqlx::query_builder::QueryBuilder::new("SELECT COUNT(*) ");
+    push_item_filters(&mut qb, &sort, query_parms);
+
+    cached_count(
+        app_state,
+        cache_key,
+        ITEMS_UNIQUE_CACHE_PREFIX,
+        qb,
+        "Items Count",
+    )
+    .await
+}
+
 // grabs items from the Item table based off of the query params
 // there are 2 types of queries the first approach is to query for
 // Item, BuyFor, SellFor as 3 separate queries which runs quickly
@@ -129,174 +337,83 @@ fn save_item_query_parms(device_id: Uuid, query_parms: ItemQueryParams, pgpool:
 // the second approach is to query all at once as a join which does not have
 // the connections issue and is much easier to work with but is also much slower
 #[allow(clippy::too_many_lines)]
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "items",
+    params(Device, ItemQueryParams),
+    responses(
+        (status = 200, body = Vec<Item>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
 pub async fn get_items(
     device: Device,
     Query(query_parms): Query<ItemQueryParams>,
+    RawQuery(raw_query): RawQuery,
     State(app_state): State<AppState>,
 ) -> Result<Json<Vec<Item>>, AppError> {
+    let sort = query_parms.sort_keys();
     let ItemQueryParams {
         save,
-        search,
-        sort_asc,
-        sort_by,
-        item_type,
         limit,
         offset,
+        cursor,
+        strict,
+        ..
     } = query_parms.clone();
 
+    if strict {
+        validate_strict(raw_query.as_deref(), &query_parms, ITEM_FIELD_RULES)?;
+    }
+
+    let filters_cache_key = item_filters_cache_key(&query_parms);
+    let cursor = cursor
+        .filter(|c| !c.is_empty())
+        .map(|c| Cursor::decode(&c, &sort, &filters_cache_key))
+        .transpose()?;
+    let offset = cursor.as_ref().map_or(offset, |c| c.offset);
+
     let limit = std::cmp::min(limit, 500);
 
     // save query
     if save && let Some(device_id) = device.0 {
-        save_item_query_parms(device_id, query_parms, app_state.pgpool.clone());
+        save_item_query_parms(device_id, query_parms.clone(), app_state.pgpool.clone());
     }
 
     // redis performance falls off at large amounts of items
-    let cache_key = format!(
-        "{}{}{}{}{}{}{}",
-        ITEMS_UNIQUE_CACHE_PREFIX,
-        if sort_asc { "1" } else { "0" },
-        sort_by,
-        limit,
-        item_type,
-        offset,
-        search,
-    );
-
-    if let Some(values) = app_state.cache.get_vec(&cache_key) {
-        return Ok(Json(values));
-    }
-
-    // this is for keyset pagination
-    let prev_last_value: Option<Item> = if offset >= limit {
-        let prev_cache_key = format!(
-            "{}{}{}{}{}{}{}",
+    let page_cache_key = |offset: u32| {
+        format!(
+            "{}{}l{}o{}{}",
             ITEMS_UNIQUE_CACHE_PREFIX,
-            if sort_asc { "1" } else { "0" },
-            sort_by,
+            sort_to_string(&sort),
             limit,
-            item_type,
-            offset - limit,
-            search,
-        );
-
-        app_state
-            .cache
-            .get_vec::<Item>(&prev_cache_key)
-            .and_then(|values| values.last().map(std::borrow::ToOwned::to_owned))
-    } else {
-        None
+            offset,
+            filters_cache_key,
+        )
     };
+    let cache_key = page_cache_key(offset);
 
-    let sort_by = sort_by.to_lowercase();
-    let is_flea = sort_by == "flea_market"
-        || sort_by == "buy_from_flea_instant_profit"
-        || sort_by == "buy_from_trader_instant_profit"
-        || sort_by == "per_slot"
-        || sort_by == "avg_24h_price"
-        || sort_by == "change_last_48h_percent";
-
-    let mut qb: sqlx::QueryBuilder<'_, Postgres> = sqlx::query_builder::QueryBuilder::new("");
-    if sort_by == "flea_market" {
-        qb.push("SELECT i.* FROM Item i LEFT JOIN BuyFor b ON i._id = b.item_id WHERE LOWER(b.trader_name) = 'flea market' ");
-    } else {
-        qb.push("SELECT i.* FROM Item i WHERE 1=1 ");
+    if let Some(values) = app_state.cache.get_vec(&cache_key) {
+        return Ok(Json(values));
     }
 
-    if !search.is_empty() {
-        qb.push("AND (i.item_name ILIKE ")
-            .push_bind(format!("%{}%", search))
-            .push(" OR i.item_name % ")
-            .push_bind(search)
-            .push(") ");
-    }
+    // this is for keyset pagination, the cursor carries the last row of the previous page
+    // and without one the previous page may still be cached
+    let prev_cache_key = (offset >= limit).then(|| page_cache_key(offset - limit));
+    let keyset = resolve_keyset::<Item>(cursor, &app_state.cache, prev_cache_key, &sort);
 
-    qb.push("AND i.item_types ILIKE ")
-        .push_bind(format!("%{}%", item_type))
-        .push(" ");
+    let mut qb: sqlx::QueryBuilder<'_, Postgres> =
+        sqlx::query_builder::QueryBuilder::new("SELECT i.* ");
+    push_item_filters(&mut qb, &sort, &query_parms);
 
-    if is_flea {
-        qb.push("AND i.is_flea = TRUE ");
+    let successful_keyset = keyset.is_some();
+    if let Some(keyset) = keyset {
+        push_keyset_condition(&mut qb, &sort, item_column_sql, "i._id", keyset);
     }
 
-    let successful_keyset = if let Some(item) = prev_last_value
-        && let Some((sort_order, id)) = item.get_keyset_offset(&sort_by)
-    {
-        /*
-        for stable keyset pagination the query looks like this
-        AND (
-            sort_col > last_sort_value
-            OR (
-                sort_col = last_sort_value
-                AND i._id > last_id
-            )
-        )
-        */
-
-        qb.push("AND (");
-        if sort_by == "flea_market" {
-            qb.push("b.price_rub");
-        } else {
-            qb.push("i.".to_string() + &sort_by);
-        }
-
-        qb.push(if sort_asc { " > " } else { " < " });
-
-        match sort_order.clone() {
-            FieldValue::String(v) => {
-                qb.push_bind(v);
-            }
-            FieldValue::I32(v) => {
-                qb.push_bind(v);
-            }
-            FieldValue::Float(v) => {
-                qb.push_bind(v);
-            }
-        }
-
-        qb.push(" OR (");
-
-        if sort_by == "flea_market" {
-            qb.push("b.price_rub");
-        } else {
-            qb.push("i.".to_string() + &sort_by);
-        }
-
-        qb.push(" = ");
-
-        match sort_order {
-            FieldValue::String(v) => {
-                qb.push_bind(v);
-            }
-            FieldValue::I32(v) => {
-                qb.push_bind(v);
-            }
-            FieldValue::Float(v) => {
-                qb.push_bind(v);
-            }
-        }
-
-        qb.push(" AND i._id")
-            .push(if sort_asc { " > " } else { " < " })
-            .push_bind(id)
-            .push(")) ");
-
-        true
-    } else {
-        false
-    };
-
-    if sort_by == "flea_market" {
-        qb.push("ORDER BY b.price_rub");
-    } else {
-        qb.push("ORDER BY i.").push(sort_by);
-    }
-
-    qb.push(if sort_asc { " ASC" } else { " DESC" })
-        .push(", i._id ")
-        .push(if sort_asc { "ASC" } else { "DESC" })
-        .push(" LIMIT ")
-        .push_bind(i64::from(limit));
+    push_order_by(&mut qb, &sort, item_column_sql, "i._id");
+    qb.push("LIMIT ").push_bind(i64::from(limit));
 
     if !successful_keyset {
         qb.push(" OFFSET ").push_bind(i64::from(offset));
@@ -306,6 +423,7 @@ pub async fn get_items(
     let items_from_db = qb
         .build_query_as()
         .fetch_all(&mut *txn)
+        .instrument(db_span("Items"))
         .await
         .bad_sql("Items")?;
 
@@ -321,12 +439,22 @@ pub async fn get_items(
     Ok(Json(items))
 }
 
+#[utoipa::path(
+    get,
+    path = "/query_parms",
+    tag = "items",
+    params(Device),
+    responses(
+        (status = 200, body = DeviceItemQueryParams),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
 pub async fn get_device_item_query_parms(
     device: Device,
     State(app_state): State<AppState>,
 ) -> Result<Json<DeviceItemQueryParams>, AppError> {
     if device.0.is_none() {
-        return Err(BadRequest("Endpoint Requires a device id".into()));
+        return Err(Unauthorized("Endpoint Requires a device id".into()));
     }
     let device_id = device.0.unwrap();
 
@@ -337,6 +465,7 @@ pub async fn get_device_item_query_parms(
         device_id
     )
     .execute(&mut *txn)
+    .instrument(db_span("Device Preferences"))
     .await
     .bad_sql("Device Preferences")?;
 
@@ -345,6 +474,7 @@ pub async fn get_device_item_query_parms(
         device_id
     )
     .execute(&mut *txn)
+    .instrument(db_span("ItemQueryParams"))
     .await
     .bad_sql("ItemQueryParams")?;
 
@@ -354,6 +484,7 @@ pub async fn get_device_item_query_parms(
         device_id
     )
     .fetch_one(&mut *txn)
+    .instrument(db_span("Items"))
     .await
     .bad_sql("Items")?;
 
@@ -363,6 +494,16 @@ pub async fn get_device_item_query_parms(
 }
 
 // returns flea market data by timestamp for a single id
+#[utoipa::path(
+    get,
+    path = "/history",
+    tag = "items",
+    params(ItemHistoryQueryParams),
+    responses(
+        (status = 200, body = Vec<SavedItemData>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
 pub async fn get_item_history(
     Query(query_parms): Query<ItemHistoryQueryParams>,
     State(app_state): State<AppState>,
@@ -393,6 +534,7 @@ pub async fn get_item_history(
         sample_interval
     )
     .fetch_all(&app_state.pgpool)
+    .instrument(db_span("ItemHistory"))
     .await
     .bad_sql("ItemHistory")?;
 
@@ -406,6 +548,13 @@ pub async fn get_item_history(
     Ok(Json(rows))
 }
 
+#[utoipa::path(
+    get,
+    path = "/help",
+    tag = "items",
+    params(ItemQueryParams),
+    responses((status = 200, body = ItemQueryParams))
+)]
 pub async fn get_items_help(Query(query_parms): Query<ItemQueryParams>) -> Json<ItemQueryParams> {
     Json(query_parms)
 }
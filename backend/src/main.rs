@@ -1,40 +1,71 @@
 mod ammo_routes;
 mod api_routers;
+mod app_metrics;
+mod armor;
+mod armor_routes;
 mod caching;
+mod cli;
 mod database_types;
 mod deserialize_json_types;
 mod endpoint_tests;
+mod filters;
+mod health_routes;
 mod init_app_state;
 mod item_routes;
 mod middleware;
+mod pagination;
 mod query_types;
+mod snapshots;
+mod task_graph;
 mod task_routes;
+mod telemetry;
 mod upsert;
+mod v2_routes;
+mod validation;
+mod weapon_routes;
 
 use anyhow::Result;
 use axum::Router;
 use axum::extract::Request;
 use axum::http::Response;
+use cli::Command;
 use dotenvy::dotenv;
 use init_app_state::init_app_state;
 use std::env;
 use std::time::Duration;
+use telemetry::REQUEST_ID_HEADER;
+use tokio_util::sync::CancellationToken;
 use tower_http::cors::{Any, CorsLayer};
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
 use tower_http::trace::TraceLayer;
 
+// how long to wait for in-flight requests and then again for background upserts when shutting down
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(15);
+
 #[global_allocator]
 static GLOBAL: mimalloc::MiMalloc = mimalloc::MiMalloc;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let (non_blocking, _guard) = tracing_appender::non_blocking(std::io::stdout());
-    tracing_subscriber::fmt().with_writer(non_blocking).init();
+    let (non_blocking, guard) = tracing_appender::non_blocking(std::io::stdout());
+    let telemetry = telemetry::init_tracing(non_blocking, guard)?;
+
+    let args: Vec<String> = env::args().skip(1).collect();
+    let command = cli::parse_args(&args)?;
 
     dotenv().ok();
     let postgres_url = env::var("DATABASE_URL")?;
+
+    if command != Command::Serve {
+        return cli::run_command(command, &postgres_url).await;
+    }
+
     let redis_url = env::var("REDIS_URL")?;
 
-    let app_state = init_app_state(postgres_url, redis_url).await?;
+    let shutdown = CancellationToken::new();
+    let app_state = init_app_state(postgres_url, redis_url, &shutdown).await?;
+    let pgpool = app_state.pgpool.clone();
+    let background_tracker = app_state.background_tracker.clone();
 
     let cors = CorsLayer::new()
         .allow_origin([
@@ -43,23 +74,34 @@ async fn main() -> Result<()> {
             "http://frontend:3000".parse()?,
         ])
         .allow_headers(Any)
+        .expose_headers([REQUEST_ID_HEADER])
         .allow_methods(Any);
 
     let app = Router::new()
         .merge(api_routers::api_router())
+        .route_layer(axum::middleware::from_fn(app_metrics::track_metrics))
         // .layer(axum::middleware::from_fn_with_state(
         //     app_state.clone(),
         //     rate_limit_user,
         // ))
         .with_state(app_state)
+        .layer(axum::middleware::from_fn(middleware::error_request_id))
+        .layer(PropagateRequestIdLayer::new(REQUEST_ID_HEADER))
         .layer(
             TraceLayer::new_for_http()
                 .make_span_with(|req: &Request<_>| {
+                    let request_id = req
+                        .headers()
+                        .get(REQUEST_ID_HEADER)
+                        .and_then(|v| v.to_str().ok())
+                        .unwrap_or_default();
+
                     tracing::info_span!(
                         "request",
                         method = %req.method(),
                         path = %req.uri().path(),
-                        version = ?req.version()
+                        version = ?req.version(),
+                        request_id = %request_id
                     )
                 })
                 .on_response(
@@ -86,10 +128,73 @@ async fn main() -> Result<()> {
                     },
                 ),
         )
+        // an id sent by the client or a proxy is kept so logs line up across services
+        .layer(SetRequestIdLayer::new(REQUEST_ID_HEADER, MakeRequestUuid))
         .layer(cors);
 
     let listener = tokio::net::TcpListener::bind("0.0.0.0:8000").await?;
-    axum::serve(listener, app).await?;
+    tokio::spawn(shutdown_signal(shutdown.clone()));
+
+    let server =
+        axum::serve(listener, app).with_graceful_shutdown(shutdown.clone().cancelled_owned());
+
+    // graceful shutdown waits on every in-flight request so stop waiting after SHUTDOWN_TIMEOUT
+    tokio::select! {
+        res = server => res?,
+        () = async {
+            shutdown.cancelled().await;
+            tokio::time::sleep(SHUTDOWN_TIMEOUT).await;
+        } => tracing::warn!("timed out waiting for in-flight requests to finish"),
+    }
+
+    // the refresh loops stop at their next sleep but an upsert that already started gets to commit
+    background_tracker.close();
+    if tokio::time::timeout(SHUTDOWN_TIMEOUT, background_tracker.wait())
+        .await
+        .is_err()
+    {
+        tracing::warn!("timed out waiting for background tasks to finish");
+    }
+
+    pgpool.close().await;
+    tracing::info!("shutdown complete");
+
+    // dropping the guard flushes whatever logs and spans are still buffered
+    drop(telemetry);
 
     Ok(())
 }
+
+// resolves on ctrl+c or when the container gets stopped with SIGTERM
+async fn shutdown_signal(shutdown: CancellationToken) {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!("failed to listen for ctrl+c with error {}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!("failed to listen for SIGTERM with error {}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        () = ctrl_c => {},
+        () = terminate => {},
+    }
+
+    tracing::info!("shutdown signal received, draining in-flight requests");
+    shutdown.cancel();
+}
@@ -1,4 +1,28 @@
 #![allow(dead_code)]
+use crate::query_types::ErrorBody;
+use crate::telemetry::REQUEST_ID_HEADER;
+use axum::{extract::Request, middleware::Next, response::IntoResponse, response::Response};
+
+// errors are built without access to the request so the request id gets attached here
+pub async fn error_request_id(req: Request, next: Next) -> Response {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+
+    let res = next.run(req).await;
+    let Some(body) = res.extensions().get::<ErrorBody>().cloned() else {
+        return res;
+    };
+
+    let (mut parts, _) = res.into_parts();
+    parts.headers.remove(axum::http::header::CONTENT_LENGTH);
+    let body = axum::Json(ErrorBody { request_id, ..body });
+
+    (parts, body).into_response()
+}
+
 // use crate::init_app_state::AppState;
 // use axum::{
 //     extract::{Request, State},
@@ -6,7 +30,7 @@
 //     middleware::Next,
 //     response::Response,
 // };
-// use reqwest::StatusCode;
+// use crate::query_types::AppError;
 // use std::time::Instant;
 
 // const MAX_TOKENS: f64 = 50.0;
@@ -17,7 +41,7 @@
 //     headers: HeaderMap,
 //     req: Request,
 //     next: Next,
-// ) -> Result<Response, StatusCode> {
+// ) -> Result<Response, AppError> {
 //     let Some(ip): Option<String> = headers
 //         .get("x-real-ip")
 //         .and_then(|v| v.to_str().ok())
@@ -38,7 +62,7 @@
 //     e.1 = Instant::now();
 
 //     if e.0 < 1.0 {
-//         return Err(StatusCode::TOO_MANY_REQUESTS);
+//         return Err(AppError::RateLimited("Too many requests slow down".into()));
 //     }
 //     e.0 -= 1.0;
 
@@ -0,0 +1,287 @@
+use crate::caching::{AppCache, Cacheable};
+use crate::database_types::{FieldValue, Keyset, KeysetOffset};
+use crate::init_app_state::AppState;
+use crate::query_types::{AppError, AppErrorHandling};
+use crate::telemetry::db_span;
+use base64::{Engine, engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, QueryBuilder};
+use tracing::Instrument;
+
+// tasks have no sort_by param and are always returned in id order
+pub const TASK_CURSOR_SORT_BY: &str = "_id";
+
+// one column of an ORDER BY, column is always one of the valid sort_by values
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct SortKey {
+    pub column: String,
+    pub asc: bool,
+}
+
+impl SortKey {
+    pub fn new(column: &str, asc: bool) -> Self {
+        Self {
+            column: column.to_lowercase(),
+            asc,
+        }
+    }
+}
+
+pub fn task_sort_keys() -> Vec<SortKey> {
+    vec![SortKey::new(TASK_CURSOR_SORT_BY, true)]
+}
+
+// parses sort=damage:desc,weight:asc, entries with an unknown column or direction and repeated
+// columns are dropped the same way other invalid params fall back to defaults
+pub fn parse_sort(sort: &str, valid_columns: &[&str]) -> Vec<SortKey> {
+    let mut keys: Vec<SortKey> = vec![];
+    for entry in sort.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+        let (column, direction) = entry.split_once(':').unwrap_or((entry, "asc"));
+
+        let Some(column) = valid_columns
+            .iter()
+            .find(|x| x.eq_ignore_ascii_case(column.trim()))
+        else {
+            continue;
+        };
+
+        let asc = match direction.trim().to_lowercase().as_str() {
+            "asc" => true,
+            "desc" => false,
+            _ => continue,
+        };
+
+        if !keys.iter().any(|k| k.column == *column) {
+            keys.push(SortKey::new(column, asc));
+        }
+    }
+
+    keys
+}
+
+// the normalized form used in cache keys and saved with the device's query params
+pub fn sort_to_string(sort: &[SortKey]) -> String {
+    sort.iter()
+        .map(|k| format!("{}:{}", k.column, if k.asc { "asc" } else { "desc" }))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+// a cursor only stores a hash of the filters since the full filter cache key can get long,
+// 64 bit FNV-1a so cursors handed out stay valid across toolchain upgrades and machines
+pub fn filters_hash(filters_cache_key: &str) -> u64 {
+    filters_cache_key
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        })
+}
+
+// clients should treat this as opaque, it is base64 json so what is inside can change freely
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Cursor {
+    pub sort: Vec<SortKey>,
+    pub filters: u64,
+    pub offset: u32,
+    // sort values and id of the last row of the previous page used for keyset pagination
+    pub keyset: Option<KeysetOffset>,
+}
+
+impl Cursor {
+    pub fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    // a cursor only makes sense for the ordering and filters it was created with
+    pub fn decode(
+        cursor: &str,
+        sort: &[SortKey],
+        filters_cache_key: &str,
+    ) -> Result<Self, AppError> {
+        let invalid = || AppError::BadRequest("cursor is invalid or expired".into());
+
+        let bytes = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+        let cursor: Self = serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+
+        if cursor.sort != sort {
+            return Err(AppError::BadRequest(
+                "cursor was created with a different sort order".into(),
+            ));
+        }
+
+        if cursor.filters != filters_hash(filters_cache_key) {
+            return Err(AppError::BadRequest(
+                "cursor was created with different filters".into(),
+            ));
+        }
+
+        // a keyset that does not line up with the sort order can not be turned into a query
+        if cursor
+            .keyset
+            .as_ref()
+            .is_some_and(|(values, _)| values.len() != sort.len())
+        {
+            return Err(invalid());
+        }
+
+        Ok(cursor)
+    }
+
+    // None once the page that was just fetched reaches the end of the results
+    pub fn next(
+        sort: &[SortKey],
+        filters_cache_key: &str,
+        offset: u32,
+        page_len: usize,
+        total: i64,
+        keyset: Option<KeysetOffset>,
+    ) -> Option<String> {
+        let next_offset = offset.saturating_add(u32::try_from(page_len).ok()?);
+        if page_len == 0 || i64::from(next_offset) >= total {
+            return None;
+        }
+
+        Some(
+            Self {
+                sort: sort.to_vec(),
+                filters: filters_hash(filters_cache_key),
+                offset: next_offset,
+                keyset,
+            }
+            .encode(),
+        )
+    }
+}
+
+// the cursor's keyset wins, without one the last row of the previous page works just as well when it is still cached
+pub fn resolve_keyset<T>(
+    cursor: Option<Cursor>,
+    cache: &AppCache,
+    prev_cache_key: Option<String>,
+    sort: &[SortKey],
+) -> Option<KeysetOffset>
+where
+    T: Keyset + Cacheable + Send + Sync,
+{
+    if let Some(keyset) = cursor.and_then(|c| c.keyset) {
+        return Some(keyset);
+    }
+
+    cache
+        .get_vec::<T>(&prev_cache_key?)?
+        .last()
+        .and_then(|row| row.get_keyset_offset(sort))
+}
+
+fn push_field_value(qb: &mut QueryBuilder<'_, Postgres>, value: FieldValue) {
+    match value {
+        FieldValue::String(v) => {
+            qb.push_bind(v);
+        }
+        FieldValue::I32(v) => {
+            qb.push_bind(v);
+        }
+        FieldValue::Float(v) => {
+            qb.push_bind(v);
+        }
+        FieldValue::Bool(v) => {
+            qb.push_bind(v);
+        }
+    }
+}
+
+// the id breaks ties in the direction of the last sort column
+fn id_asc(sort: &[SortKey]) -> bool {
+    sort.last().is_none_or(|key| key.asc)
+}
+
+// column_sql maps a sort column to what it is called in the query
+pub fn push_order_by(
+    qb: &mut QueryBuilder<'_, Postgres>,
+    sort: &[SortKey],
+    column_sql: impl Fn(&str) -> String,
+    id_column: &str,
+) {
+    qb.push("ORDER BY ");
+    for key in sort {
+        qb.push(column_sql(&key.column))
+            .push(if key.asc { " ASC, " } else { " DESC, " });
+    }
+
+    qb.push(id_column)
+        .push(if id_asc(sort) { " ASC " } else { " DESC " });
+}
+
+/*
+for stable keyset pagination every row after the last one of the previous page is kept
+which for sort columns a and b looks like this
+AND (
+    (a > last_a)
+    OR (a = last_a AND b > last_b)
+    OR (a = last_a AND b = last_b AND id_col > last_id)
+)
+with < instead of > for descending columns
+*/
+pub fn push_keyset_condition(
+    qb: &mut QueryBuilder<'_, Postgres>,
+    sort: &[SortKey],
+    column_sql: impl Fn(&str) -> String,
+    id_column: &str,
+    (values, id): KeysetOffset,
+) {
+    let cmp = |asc: bool| if asc { " > " } else { " < " };
+
+    qb.push("AND (");
+    for (n, (key, value)) in sort.iter().zip(&values).enumerate() {
+        qb.push("(");
+        for (prev_key, prev_value) in sort.iter().zip(&values).take(n) {
+            qb.push(column_sql(&prev_key.column)).push(" = ");
+            push_field_value(qb, prev_value.clone());
+            qb.push(" AND ");
+        }
+
+        qb.push(column_sql(&key.column)).push(cmp(key.asc));
+        push_field_value(qb, value.clone());
+        qb.push(") OR ");
+    }
+
+    qb.push("(");
+    for (key, value) in sort.iter().zip(values) {
+        qb.push(column_sql(&key.column)).push(" = ");
+        push_field_value(qb, value);
+        qb.push(" AND ");
+    }
+
+    qb.push(id_column)
+        .push(cmp(id_asc(sort)))
+        .push_bind(id)
+        .push(")) ");
+}
+
+// the total a v2 page reports, qb is a COUNT(*) with the filters already pushed and cache_key
+// has to cover those same filters since the count is cached until the dataset refreshes
+pub async fn cached_count(
+    app_state: &AppState,
+    cache_key: String,
+    cache_prefix: char,
+    mut qb: QueryBuilder<'_, Postgres>,
+    name: &'static str,
+) -> Result<i64, AppError> {
+    if let Some(total) = app_state.cache.get(&cache_key) {
+        return Ok(total);
+    }
+
+    let total: i64 = qb
+        .build_query_scalar()
+        .fetch_one(&app_state.pgpool)
+        .instrument(db_span(name))
+        .await
+        .bad_sql(name)?;
+
+    let cache = app_state.cache.clone();
+    tokio::spawn(async move {
+        cache.insert(cache_key, total, cache_prefix);
+    });
+
+    Ok(total)
+}
@@ -1,30 +1,107 @@
+// the IntoParams derive for the larger query param structs builds one array of every param on the stack,
+// the derived impl sits next to the struct so an allow on the struct itself does not reach it
+#![allow(clippy::large_stack_arrays)]
+use crate::armor::VALID_ARMOR_MATERIALS;
+use crate::database_types::{ItemBase, RequirementKind, SavedItemData, Task};
+use crate::pagination::{SortKey, parse_sort};
 use ahash::AHashMap as HashMap;
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Json, Response},
 };
 use serde::{Deserialize, Deserializer, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use utoipa::openapi::schema::{Object, ObjectBuilder, Type};
+use utoipa::{IntoParams, ToSchema};
 
 // standard error handling for all endpoints
 #[derive(Debug, serde::Serialize)]
 pub enum AppError {
     // FailedRedisConnection(String),
-    UninitalizedDatabase(String),
     BadSqlQuery(String),
     BadRequest(String),
+    NotFound(String),
+    Conflict(String),
+    // returned by the rate limiter in middleware.rs once it is turned back on
+    #[allow(dead_code)]
+    RateLimited(String),
+    Unauthorized(String),
+    // strict=true query params that failed validation
+    InvalidQueryParams(Vec<InvalidParam>),
+}
+
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct InvalidParam {
+    pub field: String,
+    pub value: String,
+    pub allowed: Vec<String>,
+    pub nearest_matches: Vec<String>,
+}
+
+// the body every error is sent back as so the frontend can branch on code
+// request_id is filled in by the error_request_id middleware
+#[derive(Clone, Debug, Serialize, ToSchema)]
+pub struct ErrorBody {
+    pub code: &'static str,
+    pub message: String,
+    pub details: Option<serde_json::Value>,
+    pub request_id: Option<String>,
+}
+
+impl AppError {
+    pub const fn status(&self) -> StatusCode {
+        match self {
+            Self::BadSqlQuery(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::BadRequest(_) | Self::InvalidQueryParams(_) => StatusCode::BAD_REQUEST,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+        }
+    }
+
+    pub const fn code(&self) -> &'static str {
+        match self {
+            Self::BadSqlQuery(_) => "database_error",
+            Self::BadRequest(_) | Self::InvalidQueryParams(_) => "bad_request",
+            Self::NotFound(_) => "not_found",
+            Self::Conflict(_) => "conflict",
+            Self::RateLimited(_) => "rate_limited",
+            Self::Unauthorized(_) => "unauthorized",
+        }
+    }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        match self {
-            Self::UninitalizedDatabase(msg) => {
-                (StatusCode::SERVICE_UNAVAILABLE, Json(msg)).into_response()
-            }
-            Self::BadSqlQuery(msg) => {
-                (StatusCode::INTERNAL_SERVER_ERROR, Json(msg)).into_response()
+        let status = self.status();
+        let code = self.code();
+        let (message, details) = match self {
+            Self::BadSqlQuery(msg)
+            | Self::BadRequest(msg)
+            | Self::NotFound(msg)
+            | Self::Conflict(msg)
+            | Self::RateLimited(msg)
+            | Self::Unauthorized(msg) => (msg, None),
+            Self::InvalidQueryParams(params) => {
+                let fields: Vec<&str> = params.iter().map(|p| p.field.as_str()).collect();
+                (
+                    format!("Invalid query params {}", fields.join(", ")),
+                    serde_json::to_value(params).ok(),
+                )
             }
-            Self::BadRequest(msg) => (StatusCode::BAD_REQUEST, Json(msg)).into_response(),
-        }
+        };
+
+        let body = ErrorBody {
+            code,
+            message,
+            details,
+            request_id: None,
+        };
+
+        let mut res = (status, Json(body.clone())).into_response();
+        res.extensions_mut().insert(body);
+        res
     }
 }
 
@@ -34,7 +111,18 @@ pub trait AppErrorHandling<T> {
 
 impl<T> AppErrorHandling<T> for Result<T, sqlx::Error> {
     fn bad_sql(self, name: &'static str) -> Result<T, AppError> {
-        self.map_err(|_| AppError::BadSqlQuery(format!("{name} Query did not run successfully")))
+        self.map_err(|e| match e {
+            sqlx::Error::RowNotFound => AppError::NotFound(format!("{name} not found")),
+            sqlx::Error::Database(db_err) if db_err.is_unique_violation() => {
+                tracing::warn!("{name} query hit a unique violation {db_err}");
+                AppError::Conflict(format!("{name} already exists"))
+            }
+            _ => {
+                // the real error only goes to the logs since it can leak details about the schema
+                tracing::error!("{name} query failed with error {e}");
+                AppError::BadSqlQuery(format!("{name} Query did not run successfully"))
+            }
+        })
     }
 }
 
@@ -42,15 +130,20 @@ const fn default_true() -> bool {
     true
 }
 
-pub type AdjList = HashMap<String, Vec<(String, bool)>>;
+// every task to its adjacent tasks, whether the adjacent task is unlocked by it
+// and what the requirement between the two needs
+pub type AdjList = HashMap<String, Vec<(String, bool, RequirementKind)>>;
 
-#[derive(Serialize)]
+// what /tasks/adj_list serves, the requirement kind is left out so v1 keeps its shape
+pub type AdjListV1 = HashMap<String, Vec<(String, bool)>>;
+
+#[derive(Serialize, ToSchema)]
 pub struct ItemStats {
     pub items_count: i64,
     pub time_till_items_refresh_secs: u64,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct TaskStats {
     pub tasks_completed_count: usize,
     pub tasks_count: i64,
@@ -61,12 +154,148 @@ pub struct TaskStats {
     pub time_till_tasks_refresh_secs: u64,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct AmmoStats {
     pub ammo_count: i64,
     pub time_till_ammo_refresh_secs: u64,
 }
 
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Serialize, ToSchema)]
+pub struct ReadyStatus {
+    pub ready: bool,
+    pub database_reachable: bool,
+    pub data_initialized: bool,
+    pub migrations_applied: bool,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DatasetHealth {
+    pub name: &'static str,
+    pub row_count: i64,
+    pub last_refresh: Option<DateTime<Utc>>,
+    pub last_refresh_source: Option<String>,
+    pub time_till_refresh_secs: u64,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct PoolStats {
+    pub size: u32,
+    pub idle: usize,
+    pub max_connections: u32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct HealthDetails {
+    pub datasets: Vec<DatasetHealth>,
+    pub pool: PoolStats,
+    pub cache_entries: usize,
+}
+
+// v2 wraps every list in an object so fields can be added without breaking clients
+// the ToSchema derive trips option_if_let_else for generic structs hence the module
+#[allow(clippy::option_if_let_else)]
+mod envelopes {
+    use serde::Serialize;
+    use utoipa::ToSchema;
+
+    #[derive(Serialize, ToSchema)]
+    pub struct Paginated<T> {
+        pub items: Vec<T>,
+        // number of rows matching the filters across every page
+        pub total: i64,
+        pub limit: u32,
+        pub offset: u32,
+        // pass back as cursor for the next page, None once the last page has been reached
+        pub next_cursor: Option<String>,
+    }
+
+    #[derive(Serialize, ToSchema)]
+    pub struct ListResponse<T> {
+        pub items: Vec<T>,
+    }
+}
+pub use envelopes::{ListResponse, Paginated};
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct RequiredItem {
+    pub item: ItemBase,
+    pub count: i32,
+}
+
+// a task that can be started right now and how many tasks completing it leads to
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct AvailableTask {
+    pub task: Task,
+    // tasks not completed yet that list this one as a prerequisite
+    pub unlocks: usize,
+}
+
+// everything left to do before a task can be done, see get_task_path
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct TaskPath {
+    // prerequisites in an order they can be completed in, ending with the task itself
+    pub tasks: Vec<Task>,
+    pub required_items: Vec<RequiredItem>,
+    // the highest min_player_level along the path
+    pub min_player_level: i32,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ItemHistory {
+    pub item_id: String,
+    pub history: Vec<SavedItemData>,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskRelation {
+    // the adjacent task has to be completed first
+    Prerequisite,
+    // completing this task unlocks the adjacent task
+    Unlocks,
+}
+
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct AdjacentTask {
+    pub task_id: String,
+    pub relation: TaskRelation,
+    pub requirement: RequirementKind,
+}
+
+// documents the VALID_* values so the openapi spec lists what each param accepts
+fn valid_values_schema(values: &[&str], default: Option<&str>) -> Object {
+    ObjectBuilder::new()
+        .schema_type(Type::String)
+        .enum_values(Some(values.iter().copied()))
+        .default(default.map(Into::into))
+        .build()
+}
+
+fn item_sort_by_schema() -> Object {
+    valid_values_schema(VALID_ITEM_SORT_BY, Some("base_price"))
+}
+
+fn item_type_schema() -> Object {
+    valid_values_schema(VALID_ITEM_TYPES, None)
+}
+
+fn obj_type_schema() -> Object {
+    valid_values_schema(VALID_OBJ_TYPES, None)
+}
+
+fn trader_schema() -> Object {
+    valid_values_schema(VALID_TRADERS, None)
+}
+
+fn ammo_sort_by_schema() -> Object {
+    valid_values_schema(VALID_AMMO_SORT_BY, Some("penetration_power"))
+}
+
+fn ammo_type_schema() -> Object {
+    valid_values_schema(VALID_AMMO_TYPE, None)
+}
+
 fn default_item_sort_by() -> String {
     String::from("base_price")
 }
@@ -97,33 +326,97 @@ where
     })
 }
 
-#[derive(Serialize, Deserialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ItemQueryParams {
     #[serde(default)]
     pub search: String,
     #[serde(default)]
     pub sort_asc: bool,
+    #[param(schema_with = item_sort_by_schema)]
+    #[schema(schema_with = item_sort_by_schema)]
     #[serde(
         default = "default_item_sort_by",
         deserialize_with = "deserialize_item_sort_by"
     )]
     pub sort_by: String,
+    // comma separated column:asc|desc list that takes over from sort_by and sort_asc when set
+    #[param(example = "per_slot:desc,item_name:asc")]
+    #[schema(example = "per_slot:desc,item_name:asc")]
+    #[serde(default)]
+    pub sort: String,
+    #[param(schema_with = item_type_schema)]
+    #[schema(schema_with = item_type_schema)]
     #[serde(default, deserialize_with = "deserialize_item_type")]
     pub item_type: String,
+    // inclusive bounds where a missing bound is not filtered on, slots is width * height
+    #[serde(default)]
+    pub min_base_price: Option<i32>,
+    #[serde(default)]
+    pub max_base_price: Option<i32>,
+    #[serde(default)]
+    pub min_flea_price: Option<i32>,
+    #[serde(default)]
+    pub max_flea_price: Option<i32>,
+    #[serde(default)]
+    pub min_per_slot: Option<i32>,
+    #[serde(default)]
+    pub max_per_slot: Option<i32>,
+    #[serde(default)]
+    pub min_buy_from_flea_instant_profit: Option<i32>,
+    #[serde(default)]
+    pub max_buy_from_flea_instant_profit: Option<i32>,
+    #[serde(default)]
+    pub min_buy_from_trader_instant_profit: Option<i32>,
+    #[serde(default)]
+    pub max_buy_from_trader_instant_profit: Option<i32>,
+    #[serde(default)]
+    pub min_slots: Option<i32>,
+    #[serde(default)]
+    pub max_slots: Option<i32>,
+    #[serde(default)]
+    pub min_avg_24h_price: Option<i32>,
+    #[serde(default)]
+    pub max_avg_24h_price: Option<i32>,
+    #[serde(default)]
+    pub is_flea: Option<bool>,
+    // only items this trader will buy from the player
+    #[param(schema_with = trader_schema)]
+    #[schema(schema_with = trader_schema)]
+    #[serde(default, deserialize_with = "deserialize_trader")]
+    pub sellable_to: String,
     #[serde(default = "default_limit")]
     pub limit: u32,
     #[serde(default)]
     pub offset: u32,
+    // next_cursor from a previous v2 page, takes over from offset when set
+    #[serde(default)]
+    pub cursor: Option<String>,
     #[serde(default = "default_true")]
     pub save: bool,
+    #[serde(default)]
+    pub strict: bool,
+}
+
+impl ItemQueryParams {
+    pub fn sort_keys(&self) -> Vec<SortKey> {
+        let keys = parse_sort(&self.sort, VALID_ITEM_SORT_BY);
+        if keys.is_empty() {
+            vec![SortKey::new(&self.sort_by, self.sort_asc)]
+        } else {
+            keys
+        }
+    }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct ItemHistoryQueryParams {
     pub item_id: Option<String>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct IdsQueryParams {
     pub ids: Option<Vec<String>>,
 }
@@ -169,8 +462,70 @@ where
     })
 }
 
+fn default_path_obj_type() -> String {
+    String::from("giveitem")
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TaskPathQueryParams {
+    // which objectives count towards the required items, find and give objectives
+    // list the same items so only the ones that get handed in are counted by default
+    #[param(schema_with = obj_type_schema)]
+    #[schema(schema_with = obj_type_schema)]
+    #[serde(
+        default = "default_path_obj_type",
+        deserialize_with = "deserialize_obj_type"
+    )]
+    pub obj_type: String,
+    #[serde(default)]
+    pub strict: bool,
+}
+
+fn default_graph_format() -> String {
+    String::from("json")
+}
+
+fn deserialize_graph_format<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?.to_lowercase();
+    Ok(if VALID_GRAPH_FORMATS.contains(&s.as_str()) {
+        s
+    } else {
+        default_graph_format()
+    })
+}
+
+fn graph_format_schema() -> Object {
+    valid_values_schema(VALID_GRAPH_FORMATS, Some("json"))
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct TaskGraphQueryParams {
+    #[param(schema_with = graph_format_schema)]
+    #[schema(schema_with = graph_format_schema)]
+    #[serde(
+        default = "default_graph_format",
+        deserialize_with = "deserialize_graph_format"
+    )]
+    pub format: String,
+    // only the tasks given by this trader and the requirements between them
+    #[param(schema_with = trader_schema)]
+    #[schema(schema_with = trader_schema)]
+    #[serde(default, deserialize_with = "deserialize_trader")]
+    pub trader: String,
+    // only the tasks that lead up to or follow from this task
+    pub task_id: Option<String>,
+    #[serde(default)]
+    pub strict: bool,
+}
+
 #[allow(clippy::struct_excessive_bools)]
-#[derive(Serialize, Deserialize, Clone, Default)]
+#[derive(Serialize, Deserialize, Clone, Default, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct TaskQueryParams {
     #[serde(default)]
     pub search: String,
@@ -178,8 +533,12 @@ pub struct TaskQueryParams {
     pub is_kappa: bool,
     #[serde(default)]
     pub is_lightkeeper: bool,
+    #[param(schema_with = obj_type_schema)]
+    #[schema(schema_with = obj_type_schema)]
     #[serde(default, deserialize_with = "deserialize_obj_type")]
     pub obj_type: String,
+    #[param(schema_with = trader_schema)]
+    #[schema(schema_with = trader_schema)]
     #[serde(default, deserialize_with = "deserialize_trader")]
     pub trader: String,
     #[serde(default = "default_player_lvl")]
@@ -188,10 +547,18 @@ pub struct TaskQueryParams {
     pub limit: u32,
     #[serde(default)]
     pub offset: u32,
+    // next_cursor from a previous v2 page, takes over from offset when set
+    #[serde(default)]
+    pub cursor: Option<String>,
     #[serde(default = "default_true")]
     pub include_completed: bool,
+    // only tasks whose prerequisites are met by the device's completed tasks
+    #[serde(default)]
+    pub available_only: bool,
     #[serde(default = "default_true")]
     pub save: bool,
+    #[serde(default)]
+    pub strict: bool,
 }
 
 fn default_ammo_sort_by() -> String {
@@ -224,10 +591,14 @@ where
     })
 }
 
-#[derive(Serialize, Deserialize, Clone, Default)]
+#[allow(clippy::struct_excessive_bools)]
+#[derive(Serialize, Deserialize, Clone, Default, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
 pub struct AmmoQueryParams {
     #[serde(default)]
     pub search: String,
+    #[param(schema_with = ammo_sort_by_schema)]
+    #[schema(schema_with = ammo_sort_by_schema)]
     #[serde(
         default = "default_ammo_sort_by",
         deserialize_with = "deserialize_ammo_sort_by"
@@ -235,20 +606,394 @@ pub struct AmmoQueryParams {
     pub sort_by: String,
     #[serde(default)]
     pub sort_asc: bool,
+    // comma separated column:asc|desc list that takes over from sort_by and sort_asc when set
+    #[param(example = "damage:desc,penetration_power:desc,weight:asc")]
+    #[schema(example = "damage:desc,penetration_power:desc,weight:asc")]
+    #[serde(default)]
+    pub sort: String,
     #[serde(default)]
     pub damage: i32,
     #[serde(default)]
     pub penetration_power: i32,
     #[serde(default)]
     pub initial_speed: f32,
+    #[param(schema_with = ammo_type_schema)]
+    #[schema(schema_with = ammo_type_schema)]
     #[serde(default, deserialize_with = "deserialize_ammo_type")]
     pub ammo_type: String,
+    // inclusive bounds where a missing bound is not filtered on,
+    // damage, penetration_power and initial_speed above are the minimums for those columns
+    #[serde(default)]
+    pub min_accuracy_modifier: Option<f32>,
+    #[serde(default)]
+    pub max_accuracy_modifier: Option<f32>,
+    #[serde(default)]
+    pub min_armor_damage: Option<i32>,
+    #[serde(default)]
+    pub max_armor_damage: Option<i32>,
+    #[serde(default)]
+    pub min_fragmentation_chance: Option<f32>,
+    #[serde(default)]
+    pub max_fragmentation_chance: Option<f32>,
+    #[serde(default)]
+    pub max_damage: Option<i32>,
+    #[serde(default)]
+    pub min_heavy_bleed_modifier: Option<f32>,
+    #[serde(default)]
+    pub max_heavy_bleed_modifier: Option<f32>,
+    #[serde(default)]
+    pub max_initial_speed: Option<f32>,
+    #[serde(default)]
+    pub min_light_bleed_modifier: Option<f32>,
+    #[serde(default)]
+    pub max_light_bleed_modifier: Option<f32>,
+    #[serde(default)]
+    pub min_penetration_chance: Option<f32>,
+    #[serde(default)]
+    pub max_penetration_chance: Option<f32>,
+    #[serde(default)]
+    pub max_penetration_power: Option<i32>,
+    #[serde(default)]
+    pub min_penetration_power_deviation: Option<f32>,
+    #[serde(default)]
+    pub max_penetration_power_deviation: Option<f32>,
+    #[serde(default)]
+    pub min_projectile_count: Option<i32>,
+    #[serde(default)]
+    pub max_projectile_count: Option<i32>,
+    #[serde(default)]
+    pub min_recoil_modifier: Option<f32>,
+    #[serde(default)]
+    pub max_recoil_modifier: Option<f32>,
+    #[serde(default)]
+    pub min_ricochet_chance: Option<f32>,
+    #[serde(default)]
+    pub max_ricochet_chance: Option<f32>,
+    #[serde(default)]
+    pub min_stack_max_size: Option<i32>,
+    #[serde(default)]
+    pub max_stack_max_size: Option<i32>,
+    #[serde(default)]
+    pub min_stamina_burn_per_damage: Option<f32>,
+    #[serde(default)]
+    pub max_stamina_burn_per_damage: Option<f32>,
+    #[serde(default)]
+    pub min_weight: Option<f32>,
+    #[serde(default)]
+    pub max_weight: Option<f32>,
+    #[serde(default)]
+    pub tracer: Option<bool>,
+    // exact calibers to include, repeat the param to pick several
+    #[param(example = json!(["Caliber556x45NATO", "Caliber762x39"]))]
+    #[schema(example = json!(["Caliber556x45NATO", "Caliber762x39"]))]
+    #[serde(default)]
+    pub caliber: Vec<String>,
+    // adds the item's names, cheapest offer and flea price to every row
+    #[serde(default)]
+    pub embed_item: bool,
+    // the player's loyalty level with every trader, offers needing a higher level are left out of
+    // the embedded cheapest offer and the price sorts, missing means any level
+    #[param(example = 2)]
+    #[schema(example = 2)]
+    #[serde(default)]
+    pub trader_level: Option<i32>,
     #[serde(default = "default_limit")]
     pub limit: u32,
     #[serde(default)]
     pub offset: u32,
+    // next_cursor from a previous v2 page, takes over from offset when set
+    #[serde(default)]
+    pub cursor: Option<String>,
     #[serde(default = "default_true")]
     pub save: bool,
+    #[serde(default)]
+    pub strict: bool,
+}
+
+impl AmmoQueryParams {
+    pub fn sort_keys(&self) -> Vec<SortKey> {
+        let keys = parse_sort(&self.sort, VALID_AMMO_SORT_BY);
+        if keys.is_empty() {
+            vec![SortKey::new(&self.sort_by, self.sort_asc)]
+        } else {
+            keys
+        }
+    }
+}
+
+fn armor_material_schema() -> Object {
+    valid_values_schema(VALID_ARMOR_MATERIALS, None)
+}
+
+fn effectiveness_rank_by_schema() -> Object {
+    valid_values_schema(VALID_EFFECTIVENESS_RANK_BY, None)
+}
+
+const fn default_armor_class() -> u8 {
+    4
+}
+
+fn deserialize_armor_material<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    Ok(
+        if VALID_ARMOR_MATERIALS.contains(&s.to_lowercase().as_str()) {
+            s
+        } else {
+            String::new()
+        },
+    )
+}
+
+fn deserialize_effectiveness_rank_by<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    Ok(
+        if VALID_EFFECTIVENESS_RANK_BY.contains(&s.to_lowercase().as_str()) {
+            s
+        } else {
+            String::new()
+        },
+    )
+}
+
+// the armor the ammo is measured against, taken alongside AmmoQueryParams by /ammo/effectiveness
+#[derive(Serialize, Deserialize, Clone, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ArmorQueryParams {
+    // a piece from /armor whose class, max durability, material and blunt throughput are used
+    // instead of armor_class, max_durability and material
+    #[serde(default)]
+    pub armor_id: Option<String>,
+    // 1 to 6
+    #[param(example = 4)]
+    #[schema(example = 4)]
+    #[serde(default = "default_armor_class")]
+    pub armor_class: u8,
+    // defaults to undamaged armor
+    #[serde(default)]
+    pub durability: Option<f32>,
+    // defaults to a typical max durability for the class
+    #[serde(default)]
+    pub max_durability: Option<f32>,
+    // defaults to a typical material for the class
+    #[param(schema_with = armor_material_schema)]
+    #[schema(schema_with = armor_material_schema)]
+    #[serde(default, deserialize_with = "deserialize_armor_material")]
+    pub material: String,
+    // ranks by a computed column first, ties keep the order from sort or sort_by
+    #[param(schema_with = effectiveness_rank_by_schema)]
+    #[schema(schema_with = effectiveness_rank_by_schema)]
+    #[serde(default, deserialize_with = "deserialize_effectiveness_rank_by")]
+    pub rank_by: String,
+    #[serde(default)]
+    pub rank_asc: bool,
+}
+
+impl Default for ArmorQueryParams {
+    fn default() -> Self {
+        Self {
+            armor_id: None,
+            armor_class: default_armor_class(),
+            durability: None,
+            max_durability: None,
+            material: String::new(),
+            rank_by: String::new(),
+            rank_asc: false,
+        }
+    }
+}
+
+fn armor_sort_by_schema() -> Object {
+    valid_values_schema(VALID_ARMOR_SORT_BY, Some("armor_class"))
+}
+
+fn armor_type_schema() -> Object {
+    valid_values_schema(VALID_ARMOR_TYPES, None)
+}
+
+fn default_armor_sort_by() -> String {
+    String::from("armor_class")
+}
+
+fn deserialize_armor_sort_by<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    Ok(
+        if !s.is_empty() && VALID_ARMOR_SORT_BY.contains(&s.to_lowercase().as_str()) {
+            s
+        } else {
+            default_armor_sort_by()
+        },
+    )
+}
+
+fn deserialize_armor_type<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    Ok(if VALID_ARMOR_TYPES.contains(&s.to_lowercase().as_str()) {
+        s
+    } else {
+        String::new()
+    })
+}
+
+// filters for the /armor list, ArmorQueryParams is the armor /ammo/effectiveness measures against
+#[derive(Serialize, Deserialize, Clone, Default, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ArmorPieceQueryParams {
+    #[serde(default)]
+    pub search: String,
+    #[param(schema_with = armor_sort_by_schema)]
+    #[schema(schema_with = armor_sort_by_schema)]
+    #[serde(
+        default = "default_armor_sort_by",
+        deserialize_with = "deserialize_armor_sort_by"
+    )]
+    pub sort_by: String,
+    #[serde(default)]
+    pub sort_asc: bool,
+    // comma separated column:asc|desc list that takes over from sort_by and sort_asc when set
+    #[param(example = "armor_class:desc,weight:asc")]
+    #[schema(example = "armor_class:desc,weight:asc")]
+    #[serde(default)]
+    pub sort: String,
+    #[param(schema_with = armor_type_schema)]
+    #[schema(schema_with = armor_type_schema)]
+    #[serde(default, deserialize_with = "deserialize_armor_type")]
+    pub armor_type: String,
+    #[param(schema_with = armor_material_schema)]
+    #[schema(schema_with = armor_material_schema)]
+    #[serde(default, deserialize_with = "deserialize_armor_material")]
+    pub material: String,
+    // pieces covering any of these zones, repeat the param to pick several
+    #[param(example = json!(["Thorax"]))]
+    #[schema(example = json!(["Thorax"]))]
+    #[serde(default)]
+    pub zone: Vec<String>,
+    // inclusive bounds where a missing bound is not filtered on
+    #[serde(default)]
+    pub min_armor_class: Option<i32>,
+    #[serde(default)]
+    pub max_armor_class: Option<i32>,
+    #[serde(default)]
+    pub min_durability: Option<f32>,
+    #[serde(default)]
+    pub max_durability: Option<f32>,
+    #[serde(default)]
+    pub min_blunt_throughput: Option<f32>,
+    #[serde(default)]
+    pub max_blunt_throughput: Option<f32>,
+    #[serde(default)]
+    pub min_ricochet_chance: Option<f32>,
+    #[serde(default)]
+    pub max_ricochet_chance: Option<f32>,
+    #[serde(default)]
+    pub min_speed_penalty: Option<f32>,
+    #[serde(default)]
+    pub max_speed_penalty: Option<f32>,
+    #[serde(default)]
+    pub min_turn_penalty: Option<f32>,
+    #[serde(default)]
+    pub max_turn_penalty: Option<f32>,
+    #[serde(default)]
+    pub min_ergo_penalty: Option<f32>,
+    #[serde(default)]
+    pub max_ergo_penalty: Option<f32>,
+    #[serde(default)]
+    pub min_weight: Option<f32>,
+    #[serde(default)]
+    pub max_weight: Option<f32>,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+    #[serde(default)]
+    pub offset: u32,
+    // next_cursor from a previous v2 page, takes over from offset when set
+    #[serde(default)]
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub strict: bool,
+}
+
+impl ArmorPieceQueryParams {
+    pub fn sort_keys(&self) -> Vec<SortKey> {
+        let keys = parse_sort(&self.sort, VALID_ARMOR_SORT_BY);
+        if keys.is_empty() {
+            vec![SortKey::new(&self.sort_by, self.sort_asc)]
+        } else {
+            keys
+        }
+    }
+}
+
+fn weapon_sort_by_schema() -> Object {
+    valid_values_schema(VALID_WEAPON_SORT_BY, Some("ergonomics"))
+}
+
+fn default_weapon_sort_by() -> String {
+    String::from("ergonomics")
+}
+
+fn deserialize_weapon_sort_by<'de, D>(deserializer: D) -> Result<String, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    Ok(
+        if !s.is_empty() && VALID_WEAPON_SORT_BY.contains(&s.to_lowercase().as_str()) {
+            s
+        } else {
+            default_weapon_sort_by()
+        },
+    )
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, ToSchema, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct WeaponQueryParams {
+    #[serde(default)]
+    pub search: String,
+    // exact raw calibers to include, repeat the param to pick several
+    #[param(example = json!(["Caliber556x45NATO"]))]
+    #[schema(example = json!(["Caliber556x45NATO"]))]
+    #[serde(default)]
+    pub caliber: Vec<String>,
+    #[param(schema_with = weapon_sort_by_schema)]
+    #[schema(schema_with = weapon_sort_by_schema)]
+    #[serde(
+        default = "default_weapon_sort_by",
+        deserialize_with = "deserialize_weapon_sort_by"
+    )]
+    pub sort_by: String,
+    #[serde(default)]
+    pub sort_asc: bool,
+    #[serde(default = "default_limit")]
+    pub limit: u32,
+    #[serde(default)]
+    pub offset: u32,
+    // next_cursor from a previous v2 page, takes over from offset when set
+    #[serde(default)]
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub strict: bool,
+}
+
+// a raw caliber along with how many weapons fire it and how many rounds there are for it
+#[derive(Serialize, Deserialize, Clone, ToSchema, sqlx::FromRow)]
+pub struct Caliber {
+    pub caliber: String,
+    #[sqlx(skip)]
+    pub caliber_name: String,
+    pub weapon_count: i64,
+    pub ammo_count: i64,
 }
 
 // pub const ITEM_ENABLE_FLEA_FOR: &[&str] = &[
@@ -322,6 +1067,8 @@ pub const VALID_OBJ_TYPES: &[&str] = &[
     "skill",
 ];
 
+pub const VALID_GRAPH_FORMATS: &[&str] = &["json", "dot", "mermaid"];
+
 pub const VALID_TRADERS: &[&str] = &[
     "prapor",
     "therapist",
@@ -356,6 +1103,45 @@ pub const VALID_AMMO_SORT_BY: &[&str] = &[
     "tracer_color",
     "weight",
     "item_id",
+    // cheapest offer the trader_level allows, ammo that can not be bought counts as the most expensive
+    "price",
+    "price_per_penetration",
+];
+
+pub const VALID_WEAPON_SORT_BY: &[&str] = &[
+    "_id",
+    "weapon_name",
+    "short_name",
+    "caliber",
+    "fire_rate",
+    "ergonomics",
+    "recoil_vertical",
+    "recoil_horizontal",
+];
+
+pub const VALID_ARMOR_SORT_BY: &[&str] = &[
+    "_id",
+    "armor_name",
+    "short_name",
+    "armor_type",
+    "armor_class",
+    "durability",
+    "material",
+    "blunt_throughput",
+    "ricochet_chance",
+    "speed_penalty",
+    "turn_penalty",
+    "ergo_penalty",
+    "weight",
+];
+
+pub const VALID_ARMOR_TYPES: &[&str] = &["armor", "armorplate", "helmet"];
+
+pub const VALID_EFFECTIVENESS_RANK_BY: &[&str] = &[
+    "penetration_chance",
+    "shots_to_penetrate",
+    "thorax_shots_to_kill",
+    "head_shots_to_kill",
 ];
 
 pub const VALID_AMMO_TYPE: &[&str] = &["bullet", "buckshot", "grenade", "flashbang"];
@@ -0,0 +1,111 @@
+use crate::upsert::{Upsert, parse_response, record_refresh};
+use chrono::Utc;
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+use serde_json::Value;
+use sqlx::PgPool;
+use std::{
+    error::Error,
+    fs::File,
+    io::{BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+const SNAPSHOT_EXTENSION: &str = ".json.gz";
+
+// how many raw api responses are kept per page before the oldest ones get deleted
+// items refresh every 15 minutes so this is a little over 2 days of items responses
+pub const SNAPSHOT_RETENTION: usize = 200;
+
+pub fn snapshot_dir() -> PathBuf {
+    std::env::var("SNAPSHOT_DIR").map_or_else(|_| PathBuf::from("snapshots"), PathBuf::from)
+}
+
+// snapshots are named page-timestamp.json.gz so sorting by name also sorts by time
+fn snapshot_name(page: &str) -> String {
+    format!(
+        "{}-{}{}",
+        page,
+        Utc::now().format("%Y%m%dT%H%M%S%.3fZ"),
+        SNAPSHOT_EXTENSION
+    )
+}
+
+// returns every snapshot for a page sorted oldest to newest
+pub fn list_snapshots(dir: &Path, page: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let prefix = format!("{page}-");
+    let mut snapshots: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .filter_map(Result::ok)
+        .map(|x| x.path())
+        .filter(|x| {
+            x.file_name()
+                .and_then(|x| x.to_str())
+                .is_some_and(|x| x.starts_with(&prefix) && x.ends_with(SNAPSHOT_EXTENSION))
+        })
+        .collect();
+
+    snapshots.sort();
+    Ok(snapshots)
+}
+
+// writes the raw api response to a compressed file then deletes the oldest snapshots past SNAPSHOT_RETENTION
+pub fn archive_snapshot(dir: &Path, page: &str, json: &Value) -> Result<PathBuf, Box<dyn Error>> {
+    std::fs::create_dir_all(dir)?;
+
+    let path = dir.join(snapshot_name(page));
+    let mut encoder = GzEncoder::new(BufWriter::new(File::create(&path)?), Compression::default());
+    serde_json::to_writer(&mut encoder, json)?;
+    encoder.finish()?.flush()?;
+
+    let snapshots = list_snapshots(dir, page)?;
+    if snapshots.len() > SNAPSHOT_RETENTION {
+        for old in &snapshots[..snapshots.len() - SNAPSHOT_RETENTION] {
+            std::fs::remove_file(old)?;
+        }
+    }
+
+    Ok(path)
+}
+
+pub fn read_snapshot(path: &Path) -> Result<Value, Box<dyn Error>> {
+    let decoder = GzDecoder::new(BufReader::new(File::open(path)?));
+    Ok(serde_json::from_reader(decoder)?)
+}
+
+// resolves either a path to a snapshot or "latest" for the most recent snapshot of that page
+pub fn resolve_snapshot(dir: &Path, page: &str, snapshot: &str) -> Result<PathBuf, Box<dyn Error>> {
+    if snapshot != "latest" {
+        return Ok(PathBuf::from(snapshot));
+    }
+
+    list_snapshots(dir, page)?
+        .pop()
+        .ok_or_else(|| format!("no {page} snapshots found in {}", dir.display()).into())
+}
+
+// loads an archived api response and runs it through the same upsert as the background task
+pub async fn replay_snapshot<T: Upsert>(
+    snapshot: &str,
+    pgpool: &PgPool,
+) -> Result<usize, Box<dyn Error>> {
+    let page = T::get_page();
+    let path = resolve_snapshot(&snapshot_dir(), page, snapshot)?;
+    let json = read_snapshot(&path)?;
+
+    let values: Vec<T> = parse_response(json, page, T::partial_data_policy())?;
+
+    T::upsert_data(&values, pgpool, false).await?;
+    record_refresh(page, "snapshot", values.len(), pgpool).await?;
+
+    tracing::info!(
+        "finished {} replay of {} with {} entries",
+        page,
+        path.display(),
+        values.len()
+    );
+
+    Ok(values.len())
+}
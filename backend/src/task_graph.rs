@@ -0,0 +1,349 @@
+use crate::database_types::RequirementKind;
+use ahash::{AHashMap as HashMap, AHashSet as HashSet};
+use serde::{Deserialize, Serialize};
+use sqlx::types::chrono::{DateTime, Utc};
+use std::fmt::Write;
+use utoipa::ToSchema;
+
+/*
+the task requirements as a proper graph instead of the adj_list, every edge points from the
+prerequisite to the task that requires it so the graph reads in the order tasks get done
+and it can be rendered as json, graphviz dot or mermaid, requirements that do not need the
+prerequisite finished are drawn dashed
+*/
+
+const COMPLETED_COLOR: &str = "#9be39b";
+
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow, ToSchema)]
+pub struct TaskGraphNode {
+    pub task_id: String,
+    pub task_name: String,
+    pub trader: String,
+    pub kappa_required: bool,
+    pub lightkeeper_required: bool,
+    // only ever true when a device id is passed
+    pub completed: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct TaskGraphEdge {
+    // the prerequisite
+    pub from: String,
+    pub to: String,
+    // the TaskRequirement statuses like complete or failed
+    pub statuses: Vec<String>,
+    pub requirement: RequirementKind,
+}
+
+#[derive(Serialize, Deserialize, Clone, ToSchema)]
+pub struct TaskGraph {
+    pub nodes: Vec<TaskGraphNode>,
+    pub edges: Vec<TaskGraphEdge>,
+}
+
+impl TaskGraph {
+    // edges to tasks that do not exist are dropped so every edge has both ends in nodes
+    pub fn new(nodes: Vec<TaskGraphNode>, edges: Vec<TaskGraphEdge>) -> Self {
+        let ids: HashSet<&str> = nodes.iter().map(|x| x.task_id.as_str()).collect();
+        let edges = edges
+            .into_iter()
+            .filter(|x| ids.contains(x.from.as_str()) && ids.contains(x.to.as_str()))
+            .collect();
+
+        Self { nodes, edges }
+    }
+
+    fn retain(self, keep: &HashSet<String>) -> Self {
+        Self {
+            nodes: self
+                .nodes
+                .into_iter()
+                .filter(|x| keep.contains(&x.task_id))
+                .collect(),
+            edges: self
+                .edges
+                .into_iter()
+                .filter(|x| keep.contains(&x.from) && keep.contains(&x.to))
+                .collect(),
+        }
+    }
+
+    pub fn with_trader(self, trader: &str) -> Self {
+        let keep: HashSet<String> = self
+            .nodes
+            .iter()
+            .filter(|x| x.trader.eq_ignore_ascii_case(trader))
+            .map(|x| x.task_id.clone())
+            .collect();
+
+        self.retain(&keep)
+    }
+
+    // everything before and after a task, None when the task is not in the graph
+    pub fn reachable_from(self, task_id: &str) -> Option<Self> {
+        if !self.nodes.iter().any(|x| x.task_id == task_id) {
+            return None;
+        }
+
+        let mut keep: HashSet<String> = HashSet::new();
+        for forward in [true, false] {
+            let mut visited: HashSet<&str> = HashSet::new();
+            let mut st = vec![task_id];
+            while let Some(top) = st.pop() {
+                if !visited.insert(top) {
+                    continue;
+                }
+
+                for edge in &self.edges {
+                    let (start, end) = if forward {
+                        (&edge.from, &edge.to)
+                    } else {
+                        (&edge.to, &edge.from)
+                    };
+                    if start == top {
+                        st.push(end);
+                    }
+                }
+            }
+            keep.extend(visited.into_iter().map(ToString::to_string));
+        }
+
+        Some(self.retain(&keep))
+    }
+
+    pub fn mark_completed(&mut self, completed: &HashSet<String>) {
+        for node in &mut self.nodes {
+            node.completed = completed.contains(&node.task_id);
+        }
+    }
+
+    pub fn to_dot(&self) -> String {
+        let escape = |x: &str| x.replace('\\', "\\\\").replace('"', "\\\"");
+
+        let mut dot =
+            String::from("digraph tasks {\n    node [shape=box, style=filled, fillcolor=white];\n");
+        for node in &self.nodes {
+            let _ = write!(
+                dot,
+                "    \"{}\" [label=\"{}\\n{}{}\"",
+                node.task_id,
+                escape(&node.task_name),
+                escape(&node.trader),
+                node_flags(node, "\\n"),
+            );
+            if node.completed {
+                let _ = write!(dot, ", fillcolor=\"{COMPLETED_COLOR}\"");
+            }
+            dot.push_str("];\n");
+        }
+        for edge in &self.edges {
+            let _ = write!(
+                dot,
+                "    \"{}\" -> \"{}\" [label=\"{}\"",
+                edge.from,
+                edge.to,
+                escape(&edge.statuses.join(", "))
+            );
+            match edge.requirement {
+                RequirementKind::Finished => {}
+                RequirementKind::Accepted => dot.push_str(", style=dashed"),
+                RequirementKind::Failed => dot.push_str(", style=dashed, color=red"),
+            }
+            dot.push_str("];\n");
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    // ids get a prefix since mermaid reads some words like end as keywords
+    pub fn to_mermaid(&self) -> String {
+        let escape = |x: &str| x.replace('"', "#quot;");
+
+        let mut mermaid = String::from("flowchart LR\n");
+        for node in &self.nodes {
+            let _ = writeln!(
+                mermaid,
+                "    t{}[\"{}<br/>{}{}\"]",
+                node.task_id,
+                escape(&node.task_name),
+                escape(&node.trader),
+                node_flags(node, "<br/>"),
+            );
+        }
+        for edge in &self.edges {
+            let _ = writeln!(
+                mermaid,
+                "    t{} {}|{}| t{}",
+                edge.from,
+                if edge.requirement == RequirementKind::Finished {
+                    "-->"
+                } else {
+                    "-.->"
+                },
+                escape(&edge.statuses.join(", ")),
+                edge.to
+            );
+        }
+
+        let completed: Vec<String> = self
+            .nodes
+            .iter()
+            .filter(|x| x.completed)
+            .map(|x| format!("t{}", x.task_id))
+            .collect();
+        if !completed.is_empty() {
+            let _ = writeln!(mermaid, "    classDef completed fill:{COMPLETED_COLOR};");
+            let _ = writeln!(mermaid, "    class {} completed;", completed.join(","));
+        }
+
+        mermaid
+    }
+}
+
+fn node_flags(node: &TaskGraphNode, separator: &str) -> String {
+    let mut flags = String::new();
+    if node.kappa_required {
+        flags.push_str(separator);
+        flags.push_str("kappa");
+    }
+    if node.lightkeeper_required {
+        flags.push_str(separator);
+        flags.push_str("lightkeeper");
+    }
+
+    flags
+}
+
+pub const DANGLING_REQUIREMENT: &str = "dangling_requirement";
+pub const CYCLE: &str = "cycle";
+pub const ORPHANED_TASK: &str = "orphaned_task";
+
+/*
+a problem in the task requirements found after an upsert,
+dangling_requirement is a requirement on a task that does not exist with the missing id in related_task_ids,
+cycle is a set of tasks that require each other so none of them can be started, task_id is the lowest id
+and related_task_ids has all of them, orphaned_task is a task that no task requires and that requires nothing
+*/
+#[derive(Serialize, Deserialize, Clone, sqlx::FromRow, ToSchema)]
+pub struct TaskGraphIssue {
+    pub kind: String,
+    pub task_id: String,
+    pub related_task_ids: Vec<String>,
+    pub detected_at: DateTime<Utc>,
+}
+
+// requirements are (req_task_id, task_id) pairs the same way TaskRequirement stores them
+pub fn find_issues(task_ids: &[String], requirements: &[(String, String)]) -> Vec<TaskGraphIssue> {
+    let detected_at = Utc::now();
+    let issue = |kind: &str, task_id: &str, related_task_ids: Vec<String>| TaskGraphIssue {
+        kind: kind.to_string(),
+        task_id: task_id.to_string(),
+        related_task_ids,
+        detected_at,
+    };
+
+    let ids: HashSet<&str> = task_ids.iter().map(String::as_str).collect();
+    let mut issues = vec![];
+
+    let mut unlocks: HashMap<&str, Vec<&str>> = HashMap::new();
+    let mut connected: HashSet<&str> = HashSet::new();
+    for (req_task_id, task_id) in requirements {
+        if !ids.contains(req_task_id.as_str()) {
+            issues.push(issue(
+                DANGLING_REQUIREMENT,
+                task_id,
+                vec![req_task_id.clone()],
+            ));
+            continue;
+        }
+
+        unlocks.entry(req_task_id).or_default().push(task_id);
+        connected.extend([req_task_id.as_str(), task_id.as_str()]);
+    }
+
+    for mut cycle in strongly_connected(&ids, &unlocks)
+        .into_iter()
+        .filter(|x| x.len() > 1 || unlocks.get(x[0]).is_some_and(|y| y.contains(&x[0])))
+    {
+        cycle.sort_unstable();
+        issues.push(issue(
+            CYCLE,
+            cycle[0],
+            cycle.into_iter().map(ToString::to_string).collect(),
+        ));
+    }
+
+    let mut orphaned: Vec<&str> = ids.difference(&connected).copied().collect();
+    orphaned.sort_unstable();
+    issues.extend(
+        orphaned
+            .into_iter()
+            .map(|task_id| issue(ORPHANED_TASK, task_id, vec![])),
+    );
+
+    issues
+}
+
+// tarjan's algorithm, the task graph is small enough that recursing is fine
+fn strongly_connected<'a>(
+    ids: &HashSet<&'a str>,
+    unlocks: &HashMap<&'a str, Vec<&'a str>>,
+) -> Vec<Vec<&'a str>> {
+    struct State<'a> {
+        index: HashMap<&'a str, usize>,
+        low: HashMap<&'a str, usize>,
+        on_stack: HashSet<&'a str>,
+        st: Vec<&'a str>,
+        components: Vec<Vec<&'a str>>,
+    }
+
+    fn visit<'a>(id: &'a str, unlocks: &HashMap<&'a str, Vec<&'a str>>, state: &mut State<'a>) {
+        let index = state.index.len();
+        state.index.insert(id, index);
+        state.low.insert(id, index);
+        state.st.push(id);
+        state.on_stack.insert(id);
+
+        for next in unlocks.get(id).into_iter().flatten() {
+            if !state.index.contains_key(next) {
+                visit(next, unlocks, state);
+                let low = state.low[id].min(state.low[next]);
+                state.low.insert(id, low);
+            } else if state.on_stack.contains(next) {
+                let low = state.low[id].min(state.index[next]);
+                state.low.insert(id, low);
+            }
+        }
+
+        if state.low[id] == state.index[id] {
+            let mut component = vec![];
+            while let Some(top) = state.st.pop() {
+                state.on_stack.remove(top);
+                component.push(top);
+                if top == id {
+                    break;
+                }
+            }
+            state.components.push(component);
+        }
+    }
+
+    let mut state = State {
+        index: HashMap::new(),
+        low: HashMap::new(),
+        on_stack: HashSet::new(),
+        st: vec![],
+        components: vec![],
+    };
+
+    let mut ids: Vec<&str> = ids.iter().copied().collect();
+    ids.sort_unstable();
+    for id in ids {
+        if !state.index.contains_key(id) {
+            visit(id, unlocks, &mut state);
+        }
+    }
+
+    state.components
+}
@@ -1,17 +1,36 @@
 use crate::api_routers::{Device, Page, fetch_page_by_ids};
 use crate::database_types::{
-    DeviceTaskQueryParams, ItemBase, NeededItemsDB, Objective, Task, TaskBase, TaskFromDB,
-    TaskRequirement,
+    DeviceTaskQueryParams, ItemBase, NeededItemsDB, Objective, RequirementKind, Task, TaskBase,
+    TaskFromDB, TaskRequirement,
 };
+use crate::filters::text_cache_key;
 use crate::init_app_state::{AppState, ITEMS_UNIQUE_CACHE_PREFIX, TASKS_UNIQUE_CACHE_PREFIX};
-use crate::query_types::{AdjList, AppErrorHandling, TaskQueryParams, TaskStats};
-use crate::query_types::{AppError, AppError::BadRequest};
+use crate::pagination::{Cursor, resolve_keyset, task_sort_keys};
+use crate::query_types::ErrorBody;
+use crate::query_types::{
+    AdjList, AdjListV1, AppErrorHandling, AvailableTask, RequiredItem, TaskGraphQueryParams,
+    TaskPath, TaskPathQueryParams, TaskQueryParams, TaskStats,
+};
+use crate::query_types::{AppError, AppError::NotFound, AppError::Unauthorized};
+use crate::task_graph::{TaskGraph, TaskGraphEdge, TaskGraphIssue, TaskGraphNode};
+use crate::telemetry::db_span;
+use crate::validation::{
+    TASK_FIELD_RULES, TASK_GRAPH_FIELD_RULES, TASK_PATH_FIELD_RULES, validate_strict,
+};
 use ahash::{AHashMap as HashMap, AHashSet as HashSet};
-use axum::{extract::State, response::Json};
+use axum::{
+    extract::{Path, RawQuery, State},
+    http::header::CONTENT_TYPE,
+    response::{IntoResponse, Json, Response},
+};
 use axum_extra::extract::Query;
 use sqlx::PgPool;
 use sqlx::types::Uuid;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 use std::time::Instant;
+use tracing::Instrument;
+use utoipa::ToSchema;
 
 #[derive(Clone)]
 pub struct GrabIds {
@@ -19,12 +38,22 @@ pub struct GrabIds {
 }
 
 // gives data on different interesting stats about the data stored
+#[utoipa::path(
+    get,
+    path = "/stats",
+    tag = "tasks",
+    params(Device),
+    responses(
+        (status = 200, body = TaskStats),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
 pub async fn task_stats(
     device: Device,
     State(app_state): State<AppState>,
 ) -> Result<Json<TaskStats>, AppError> {
     if device.0.is_none() {
-        return Err(BadRequest("Endpoint Requires a device id".into()));
+        return Err(Unauthorized("Endpoint Requires a device id".into()));
     }
     let device_id = device.0.unwrap();
 
@@ -33,6 +62,7 @@ pub async fn task_stats(
     } else {
         let count = sqlx::query_scalar!("SELECT COUNT(*) FROM Task")
             .fetch_one(&app_state.pgpool)
+            .instrument(db_span("Task Stats"))
             .await
             .bad_sql("Task Stats")?
             .unwrap_or(0);
@@ -51,6 +81,7 @@ pub async fn task_stats(
     } else {
         let kappa = sqlx::query_as!(GrabIds, "SELECT _id FROM Task WHERE kappa_required = True")
             .fetch_all(&app_state.pgpool)
+            .instrument(db_span("Task Stats Kappa"))
             .await
             .bad_sql("Task Stats Kappa")?;
         let tokio_cache = app_state.cache.clone();
@@ -71,6 +102,7 @@ pub async fn task_stats(
             "SELECT _id FROM Task WHERE lightkeeper_required = True"
         )
         .fetch_all(&app_state.pgpool)
+        .instrument(db_span("Task Stats Lightkeeper"))
         .await
         .bad_sql("Task Stats Lightkeeper")?;
         let tokio_cache = app_state.cache.clone();
@@ -128,6 +160,7 @@ pub async fn tasks_from_db_to_tasks(
         &ids
     )
     .fetch_all(&mut *txn)
+    .instrument(db_span("Objective and Task Requirement"))
     .await
     .bad_sql("Objective and Task Requirement")?;
     let task_requirement_vec = sqlx::query_as!(
@@ -136,6 +169,7 @@ pub async fn tasks_from_db_to_tasks(
         &ids
     )
     .fetch_all(&mut *txn)
+    .instrument(db_span("Objective and Task Requirement"))
     .await
     .bad_sql("Objective and Task Requirement")?;
 
@@ -201,9 +235,92 @@ fn save_task_query_parms(device_id: Uuid, query_parms: TaskQueryParams, pgpool:
     });
 }
 
+// every filter that changes which tasks come back besides the completed tasks of a device
+pub fn task_filters_cache_key(query_parms: &TaskQueryParams) -> String {
+    format!(
+        "{}{}o{}p{}t{}s{}",
+        if query_parms.is_kappa { "1" } else { "0" },
+        if query_parms.is_lightkeeper { "1" } else { "0" },
+        text_cache_key(&query_parms.obj_type),
+        query_parms.player_lvl,
+        text_cache_key(&query_parms.trader),
+        text_cache_key(&query_parms.search),
+    )
+}
+
+// tasks are always ordered by id so a cursor only has to carry where the page starts
+fn decode_task_cursor(
+    cursor: Option<String>,
+    query_parms: &TaskQueryParams,
+) -> Result<Option<Cursor>, AppError> {
+    cursor
+        .filter(|c| !c.is_empty())
+        .map(|c| Cursor::decode(&c, &task_sort_keys(), &task_filters_cache_key(query_parms)))
+        .transpose()
+}
+
+// total tasks for a filter set, only cached when no completed tasks are filtered out
+pub async fn count_tasks(
+    app_state: &AppState,
+    query_parms: &TaskQueryParams,
+    completed_ids: &[String],
+) -> Result<i64, AppError> {
+    #[allow(clippy::cast_possible_wrap)]
+    let player_lvl = query_parms.player_lvl as i32;
+
+    let cache_key = format!(
+        "{}count{}",
+        TASKS_UNIQUE_CACHE_PREFIX,
+        task_filters_cache_key(query_parms)
+    );
+
+    let use_cache = completed_ids.is_empty();
+    if use_cache && let Some(total) = app_state.cache.get(&cache_key) {
+        return Ok(total);
+    }
+
+    let total = sqlx::query_scalar!(
+                "SELECT COUNT(*) FROM Task t WHERE ($1 = '' OR task_name ILIKE '%' || $1 || '%' OR task_name % $1) AND trader ILIKE $2 AND min_player_level <= $3 AND NOT (_id = ANY($4)) AND 
+                ($5 IS FALSE OR kappa_required = TRUE) AND ($6 IS FALSE OR lightkeeper_required = TRUE) AND 
+                EXISTS (SELECT 1 FROM Objective o WHERE o.task_id = t._id AND o.obj_type ILIKE $7)",
+                query_parms.search,
+                format!("%{}%", query_parms.trader),
+                player_lvl,
+                completed_ids,
+                query_parms.is_kappa,
+                query_parms.is_lightkeeper,
+                format!("%{}%", query_parms.obj_type),
+            )
+            .fetch_one(&app_state.pgpool)
+            .instrument(db_span("Tasks Count"))
+            .await
+            .bad_sql("Tasks Count")?
+            .unwrap_or(0);
+
+    if use_cache {
+        let cache = app_state.cache.clone();
+        tokio::spawn(async move {
+            cache.insert(cache_key, total, TASKS_UNIQUE_CACHE_PREFIX);
+        });
+    }
+
+    Ok(total)
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "tasks",
+    params(Device, TaskQueryParams),
+    responses(
+        (status = 200, body = Vec<Task>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
 pub async fn get_tasks(
     device: Device,
     Query(query_parms): Query<TaskQueryParams>,
+    RawQuery(raw_query): RawQuery,
     State(app_state): State<AppState>,
 ) -> Result<Json<Vec<Task>>, AppError> {
     let TaskQueryParams {
@@ -216,35 +333,44 @@ pub async fn get_tasks(
         player_lvl,
         limit,
         offset,
-        include_completed,
+        cursor,
+        include_completed: _,
+        available_only: _,
+        strict,
     } = query_parms.clone();
 
+    if strict {
+        validate_strict(raw_query.as_deref(), &query_parms, TASK_FIELD_RULES)?;
+    }
+
+    let cursor = decode_task_cursor(cursor, &query_parms)?;
+    let offset = cursor.as_ref().map_or(offset, |c| c.offset);
+
     #[allow(clippy::cast_possible_wrap)]
     let player_lvl = player_lvl as i32;
 
-    let ids = if include_completed && let Some(device_id) = device.0 {
-        get_completed_task_by_device_id(&app_state.pgpool, device_id).await?
-    } else {
-        vec![]
-    };
+    let ids = excluded_task_ids(&app_state, &query_parms, device.0).await?;
 
     // save query
     if save && let Some(device_id) = device.0 {
         save_task_query_parms(device_id, query_parms, app_state.pgpool.clone());
     }
 
-    let cache_key = format!(
-        "{}{}{}{}{}{}l{}o{}{}",
-        TASKS_UNIQUE_CACHE_PREFIX,
-        if is_kappa { "1" } else { "0" },
-        if is_lightkeeper { "1" } else { "0" },
-        obj_type,
-        player_lvl,
-        trader,
-        limit,
-        offset,
-        search,
-    );
+    let page_cache_key = |offset: u32| {
+        format!(
+            "{}{}{}{}{}{}l{}o{}{}",
+            TASKS_UNIQUE_CACHE_PREFIX,
+            if is_kappa { "1" } else { "0" },
+            if is_lightkeeper { "1" } else { "0" },
+            obj_type,
+            player_lvl,
+            trader,
+            limit,
+            offset,
+            search,
+        )
+    };
+    let cache_key = page_cache_key(offset);
 
     // try not to create too many cache keys when its not needed
     let use_cache = ids.is_empty();
@@ -252,12 +378,19 @@ pub async fn get_tasks(
         return Ok(Json(values));
     }
 
+    // tasks are ordered by id alone so the last id is all keyset pagination needs
+    let prev_cache_key = (use_cache && offset >= limit).then(|| page_cache_key(offset - limit));
+    let last_id =
+        resolve_keyset::<Task>(cursor, &app_state.cache, prev_cache_key, &task_sort_keys())
+            .map(|(_, id)| id);
+
     let mut txn = app_state.pgpool.begin().await.bad_sql("Tasks")?;
     let tasks_from_db = sqlx::query_as!(
                 TaskFromDB,
                 "SELECT * FROM Task t WHERE ($1 = '' OR task_name ILIKE '%' || $1 || '%' OR task_name % $1) AND trader ILIKE $2 AND min_player_level <= $3 AND NOT (_id = ANY($4)) AND 
                 ($5 IS FALSE OR kappa_required = TRUE) AND ($6 IS FALSE OR lightkeeper_required = TRUE) AND 
-                EXISTS (SELECT 1 FROM Objective o WHERE o.task_id = t._id AND o.obj_type ILIKE $7) ORDER BY _id ASC LIMIT $8 OFFSET $9",
+                EXISTS (SELECT 1 FROM Objective o WHERE o.task_id = t._id AND o.obj_type ILIKE $7) AND 
+                ($10::TEXT IS NULL OR _id > $10) ORDER BY _id ASC LIMIT $8 OFFSET $9",
                 search,
                 format!("%{trader}%"),
                 player_lvl,
@@ -266,9 +399,12 @@ pub async fn get_tasks(
                 is_lightkeeper,
                 format!("%{obj_type}%"),
                 i64::from(limit),
-                i64::from(offset)
+                // the keyset already skips the earlier pages
+                if last_id.is_some() { 0 } else { i64::from(offset) },
+                last_id
             )
             .fetch_all(&mut *txn)
+            .instrument(db_span("Tasks"))
             .await
             .bad_sql("Tasks")?;
 
@@ -287,9 +423,20 @@ pub async fn get_tasks(
     Ok(Json(tasks))
 }
 
+#[utoipa::path(
+    get,
+    path = "/base",
+    tag = "tasks",
+    params(Device, TaskQueryParams),
+    responses(
+        (status = 200, body = Vec<TaskBase>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
 pub async fn get_tasks_base(
     device: Device,
     Query(query_parms): Query<TaskQueryParams>,
+    RawQuery(raw_query): RawQuery,
     State(app_state): State<AppState>,
 ) -> Result<Json<Vec<TaskBase>>, AppError> {
     let TaskQueryParams {
@@ -302,30 +449,39 @@ pub async fn get_tasks_base(
         player_lvl,
         limit,
         offset,
-        include_completed,
+        cursor,
+        include_completed: _,
+        available_only: _,
+        strict,
     } = query_parms.clone();
 
+    if strict {
+        validate_strict(raw_query.as_deref(), &query_parms, TASK_FIELD_RULES)?;
+    }
+
+    let cursor = decode_task_cursor(cursor, &query_parms)?;
+    let offset = cursor.as_ref().map_or(offset, |c| c.offset);
+
     #[allow(clippy::cast_possible_wrap)]
     let player_lvl = player_lvl as i32;
 
-    let ids = if include_completed && let Some(device_id) = device.0 {
-        get_completed_task_by_device_id(&app_state.pgpool, device_id).await?
-    } else {
-        vec![]
+    let ids = excluded_task_ids(&app_state, &query_parms, device.0).await?;
+
+    let page_cache_key = |offset: u32| {
+        format!(
+            "{}b{}{}{}{}{}l{}o{}{}",
+            TASKS_UNIQUE_CACHE_PREFIX,
+            if is_kappa { "1" } else { "0" },
+            if is_lightkeeper { "1" } else { "0" },
+            obj_type,
+            player_lvl,
+            trader,
+            limit,
+            offset,
+            search,
+        )
     };
-
-    let cache_key = format!(
-        "{}b{}{}{}{}{}l{}o{}{}",
-        TASKS_UNIQUE_CACHE_PREFIX,
-        if is_kappa { "1" } else { "0" },
-        if is_lightkeeper { "1" } else { "0" },
-        obj_type,
-        player_lvl,
-        trader,
-        limit,
-        offset,
-        search,
-    );
+    let cache_key = page_cache_key(offset);
 
     // try not to create too many cache keys when its not needed
     let use_cache = ids.is_empty();
@@ -333,11 +489,18 @@ pub async fn get_tasks_base(
         return Ok(Json(values));
     }
 
+    // tasks are ordered by id alone so the last id is all keyset pagination needs
+    let prev_cache_key = (use_cache && offset >= limit).then(|| page_cache_key(offset - limit));
+    let last_id =
+        resolve_keyset::<TaskBase>(cursor, &app_state.cache, prev_cache_key, &task_sort_keys())
+            .map(|(_, id)| id);
+
     let tasks = sqlx::query_as!(
                 TaskBase,
                 "SELECT _id, task_name FROM Task t WHERE ($1 = '' OR task_name ILIKE '%' || $1 || '%' OR task_name % $1) AND trader ILIKE $2 AND min_player_level <= $3 AND NOT (_id = ANY($4)) AND 
                 ($5 IS FALSE OR kappa_required = TRUE) AND ($6 IS FALSE OR lightkeeper_required = TRUE) AND 
-                EXISTS (SELECT 1 FROM Objective o WHERE o.task_id = t._id AND o.obj_type ILIKE $7) ORDER BY _id ASC LIMIT $8 OFFSET $9",
+                EXISTS (SELECT 1 FROM Objective o WHERE o.task_id = t._id AND o.obj_type ILIKE $7) AND 
+                ($10::TEXT IS NULL OR _id > $10) ORDER BY _id ASC LIMIT $8 OFFSET $9",
                 search,
                 format!("%{trader}%"),
                 player_lvl,
@@ -346,9 +509,12 @@ pub async fn get_tasks_base(
                 is_lightkeeper,
                 format!("%{obj_type}%"),
                 i64::from(limit),
-                i64::from(offset)
+                // the keyset already skips the earlier pages
+                if last_id.is_some() { 0 } else { i64::from(offset) },
+                last_id
             )
             .fetch_all(&app_state.pgpool)
+            .instrument(db_span("TasksBase"))
             .await
             .bad_sql("TasksBase")?;
 
@@ -365,12 +531,22 @@ pub async fn get_tasks_base(
     Ok(Json(tasks))
 }
 
+#[utoipa::path(
+    get,
+    path = "/query_parms",
+    tag = "tasks",
+    params(Device),
+    responses(
+        (status = 200, body = DeviceTaskQueryParams),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
 pub async fn get_device_task_query_parms(
     device: Device,
     State(app_state): State<AppState>,
 ) -> Result<Json<DeviceTaskQueryParams>, AppError> {
     if device.0.is_none() {
-        return Err(BadRequest("Endpoint Requires a device id".into()));
+        return Err(Unauthorized("Endpoint Requires a device id".into()));
     }
     let device_id = device.0.unwrap();
 
@@ -381,6 +557,7 @@ pub async fn get_device_task_query_parms(
         device_id
     )
     .execute(&mut *txn)
+    .instrument(db_span("Device Preferences"))
     .await
     .bad_sql("Device Preferences")?;
 
@@ -389,6 +566,7 @@ pub async fn get_device_task_query_parms(
         device_id
     )
     .execute(&mut *txn)
+    .instrument(db_span("TaskQueryParams"))
     .await
     .bad_sql("TaskQueryParams")?;
 
@@ -398,6 +576,7 @@ pub async fn get_device_task_query_parms(
         device_id
     )
     .fetch_one(&mut *txn)
+    .instrument(db_span("Tasks"))
     .await
     .bad_sql("Tasks")?;
 
@@ -406,11 +585,11 @@ pub async fn get_device_task_query_parms(
     Ok(Json(v))
 }
 
-// returns a HashMap which maps every task_id to a Vec<task_id, status> where status can only be
+// returns a HashMap which maps every task_id to a Vec<task_id, status, requirement> where status can only be
 // "prerequisite" which is all the tasks that come before current task or
 // "unlocks" is all the tasks that come after current task
-// effectively mapping every task to their adjacent tasks
-async fn fetch_adj_list(app_state: &AppState) -> Result<AdjList, AppError> {
+// effectively mapping every task to their adjacent tasks, requirement is the same on both sides of an edge
+pub async fn fetch_adj_list(app_state: &AppState) -> Result<AdjList, AppError> {
     let cache_key = TASKS_UNIQUE_CACHE_PREFIX.to_string() + "adj_list";
 
     if let Some(ans) = app_state.cache.get(&cache_key) {
@@ -419,20 +598,25 @@ async fn fetch_adj_list(app_state: &AppState) -> Result<AdjList, AppError> {
 
     let task_requirements = sqlx::query_as!(TaskRequirement, "SELECT * FROM TaskRequirement")
         .fetch_all(&app_state.pgpool)
+        .instrument(db_span("TaskRequirements"))
         .await
         .bad_sql("TaskRequirements")?;
 
     let mut adj_list: AdjList = HashMap::new();
     for req in task_requirements {
+        let kind = req.kind();
         let from_id = req.task_id;
         let to_id = req.req_task_id;
 
         adj_list
             .entry(from_id.clone())
             .or_default()
-            .push((to_id.clone(), false));
+            .push((to_id.clone(), false, kind));
 
-        adj_list.entry(to_id).or_default().push((from_id, true));
+        adj_list
+            .entry(to_id)
+            .or_default()
+            .push((from_id, true, kind));
     }
 
     let cache = app_state.cache.clone();
@@ -446,11 +630,274 @@ async fn fetch_adj_list(app_state: &AppState) -> Result<AdjList, AppError> {
     Ok(adj_list)
 }
 
-pub async fn get_adj_list(State(app_state): State<AppState>) -> Result<Json<AdjList>, AppError> {
-    Ok(Json(fetch_adj_list(&app_state).await?))
+#[utoipa::path(
+    get,
+    path = "/adj_list",
+    tag = "tasks",
+    responses(
+        (status = 200, body = HashMap<String, Vec<(String, bool)>>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_adj_list(State(app_state): State<AppState>) -> Result<Json<AdjListV1>, AppError> {
+    Ok(Json(
+        fetch_adj_list(&app_state)
+            .await?
+            .into_iter()
+            .map(|(task_id, adjacent)| {
+                let adjacent = adjacent
+                    .into_iter()
+                    .map(|(task_id, unlocks, _)| (task_id, unlocks))
+                    .collect();
+                (task_id, adjacent)
+            })
+            .collect(),
+    ))
 }
 
-async fn get_completed_task_by_device_id(
+/*
+the tasks that cannot be started yet, a requirement that needs its prerequisite finished or failed
+is met once the prerequisite is completed since completing is also how failed tasks get marked done,
+one that only needs it accepted is met as soon as the prerequisite itself could be started
+*/
+pub fn locked_tasks(adj_list: &AdjList, completed: &HashSet<String>) -> HashSet<String> {
+    fn unlocked<'a>(
+        adj_list: &'a AdjList,
+        completed: &HashSet<String>,
+        memo: &mut HashMap<&'a str, bool>,
+        task_id: &'a str,
+    ) -> bool {
+        if completed.contains(task_id) {
+            return true;
+        }
+        if let Some(ans) = memo.get(task_id) {
+            return *ans;
+        }
+
+        // a task in a cycle of accepted requirements can never be started
+        memo.insert(task_id, false);
+        let ans = adj_list
+            .get(task_id)
+            .into_iter()
+            .flatten()
+            .filter(|(_, unlocks, _)| !unlocks)
+            .all(|(req_id, _, kind)| match kind {
+                RequirementKind::Finished | RequirementKind::Failed => completed.contains(req_id),
+                RequirementKind::Accepted => unlocked(adj_list, completed, memo, req_id),
+            });
+        memo.insert(task_id, ans);
+
+        ans
+    }
+
+    let mut memo: HashMap<&str, bool> = HashMap::new();
+    adj_list
+        .keys()
+        .filter(|id| !unlocked(adj_list, completed, &mut memo, id))
+        .cloned()
+        .collect()
+}
+
+// the task ids a query leaves out, completed tasks when include_completed is set
+// and with available_only every task that is completed or still locked
+pub async fn excluded_task_ids(
+    app_state: &AppState,
+    query_parms: &TaskQueryParams,
+    device_id: Option<Uuid>,
+) -> Result<Vec<String>, AppError> {
+    let completed = match device_id {
+        Some(device_id) if query_parms.include_completed || query_parms.available_only => {
+            get_completed_task_by_device_id(&app_state.pgpool, device_id).await?
+        }
+        _ => vec![],
+    };
+
+    if !query_parms.available_only {
+        return Ok(completed);
+    }
+
+    let adj_list = fetch_adj_list(app_state).await?;
+    let completed: HashSet<String> = completed.into_iter().collect();
+    let locked = locked_tasks(&adj_list, &completed);
+
+    Ok(completed.into_iter().chain(locked).collect())
+}
+
+// the tasks that can be started right now, each with how many tasks it leads to
+#[utoipa::path(
+    get,
+    path = "/available",
+    tag = "tasks",
+    params(Device, TaskQueryParams),
+    responses(
+        (status = 200, body = Vec<AvailableTask>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_available_tasks(
+    device: Device,
+    Query(mut query_parms): Query<TaskQueryParams>,
+    raw_query: RawQuery,
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<AvailableTask>>, AppError> {
+    query_parms.available_only = true;
+    query_parms.save = false;
+
+    let device_id = device.0;
+    let Json(tasks) = get_tasks(
+        device,
+        Query(query_parms),
+        raw_query,
+        State(app_state.clone()),
+    )
+    .await?;
+
+    let completed: HashSet<String> = match device_id {
+        Some(device_id) => get_completed_task_by_device_id(&app_state.pgpool, device_id)
+            .await?
+            .into_iter()
+            .collect(),
+        None => HashSet::new(),
+    };
+    let adj_list = fetch_adj_list(&app_state).await?;
+
+    Ok(Json(
+        tasks
+            .into_iter()
+            .map(|task| AvailableTask {
+                unlocks: adj_list.get(&task._id).map_or(0, |x| {
+                    x.iter()
+                        .filter(|(adj_task_id, unlocks, _)| {
+                            *unlocks && !completed.contains(adj_task_id)
+                        })
+                        .count()
+                }),
+                task,
+            })
+            .collect(),
+    ))
+}
+
+async fn fetch_task_graph(app_state: &AppState) -> Result<TaskGraph, AppError> {
+    let cache_key = TASKS_UNIQUE_CACHE_PREFIX.to_string() + "graph";
+
+    if let Some(graph) = app_state.cache.get(&cache_key) {
+        return Ok(graph);
+    }
+
+    let nodes = sqlx::query_as!(
+        TaskGraphNode,
+        r#"SELECT _id AS task_id, task_name, trader, kappa_required, lightkeeper_required, false AS "completed!"
+        FROM Task ORDER BY _id"#
+    )
+    .fetch_all(&app_state.pgpool)
+    .instrument(db_span("Task Graph"))
+    .await
+    .bad_sql("Task Graph")?;
+
+    let edges = sqlx::query_as!(TaskRequirement, "SELECT * FROM TaskRequirement")
+        .fetch_all(&app_state.pgpool)
+        .instrument(db_span("TaskRequirements"))
+        .await
+        .bad_sql("TaskRequirements")?
+        .into_iter()
+        .map(|req| TaskGraphEdge {
+            requirement: req.kind(),
+            from: req.req_task_id,
+            to: req.task_id,
+            statuses: req.statuses,
+        })
+        .collect();
+
+    let graph = TaskGraph::new(nodes, edges);
+
+    let (cache, tokio_graph) = (app_state.cache.clone(), graph.clone());
+    tokio::spawn(async move {
+        cache.insert(cache_key, tokio_graph, TASKS_UNIQUE_CACHE_PREFIX);
+    });
+
+    Ok(graph)
+}
+
+// the requirement graph as json or rendered for graphviz and mermaid, completed tasks are coloured in
+#[utoipa::path(
+    get,
+    path = "/graph",
+    tag = "tasks",
+    params(Device, TaskGraphQueryParams),
+    responses(
+        (status = 200, content(
+            (TaskGraph = "application/json"),
+            (String = "text/vnd.graphviz"),
+            (String = "text/plain")
+        )),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_task_graph(
+    device: Device,
+    Query(query_parms): Query<TaskGraphQueryParams>,
+    RawQuery(raw_query): RawQuery,
+    State(app_state): State<AppState>,
+) -> Result<Response, AppError> {
+    if query_parms.strict {
+        validate_strict(raw_query.as_deref(), &query_parms, TASK_GRAPH_FIELD_RULES)?;
+    }
+
+    let mut graph = fetch_task_graph(&app_state).await?;
+
+    if !query_parms.trader.is_empty() {
+        graph = graph.with_trader(&query_parms.trader);
+    }
+
+    if let Some(task_id) = query_parms.task_id.filter(|x| !x.is_empty()) {
+        graph = graph
+            .reachable_from(&task_id)
+            .ok_or_else(|| NotFound(format!("Task {task_id} is not in the graph")))?;
+    }
+
+    if let Some(device_id) = device.0 {
+        let completed: HashSet<String> =
+            get_completed_task_by_device_id(&app_state.pgpool, device_id)
+                .await?
+                .into_iter()
+                .collect();
+        graph.mark_completed(&completed);
+    }
+
+    Ok(match query_parms.format.as_str() {
+        "dot" => ([(CONTENT_TYPE, "text/vnd.graphviz")], graph.to_dot()).into_response(),
+        "mermaid" => ([(CONTENT_TYPE, "text/plain")], graph.to_mermaid()).into_response(),
+        _ => Json(graph).into_response(),
+    })
+}
+
+// what the last task upsert found wrong with the requirements, not cached so it is always current
+#[utoipa::path(
+    get,
+    path = "/task_graph_issues",
+    tag = "admin",
+    responses(
+        (status = 200, body = Vec<TaskGraphIssue>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_task_graph_issues(
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<TaskGraphIssue>>, AppError> {
+    let issues = sqlx::query_as!(
+        TaskGraphIssue,
+        "SELECT kind, task_id, related_task_ids, detected_at FROM TaskGraphIssue ORDER BY kind, task_id"
+    )
+    .fetch_all(&app_state.pgpool)
+    .instrument(db_span("Task Graph Issues"))
+    .await
+    .bad_sql("Task Graph Issues")?;
+
+    Ok(Json(issues))
+}
+
+pub async fn get_completed_task_by_device_id(
     pgpool: &PgPool,
     device_id: Uuid,
 ) -> Result<Vec<String>, AppError> {
@@ -461,6 +908,7 @@ async fn get_completed_task_by_device_id(
         device_id
     )
     .execute(&mut *txn)
+    .instrument(db_span("Device Preferences"))
     .await
     .bad_sql("Device Preferences")?;
 
@@ -471,6 +919,7 @@ async fn get_completed_task_by_device_id(
         device_id
     )
     .fetch_one(&mut *txn)
+    .instrument(db_span("Device Preferences"))
     .await
     .bad_sql("Device Preferences")?
     .completed_tasks;
@@ -486,12 +935,22 @@ struct GetOnlyCompletedTasks {
 }
 
 // get completed tasks using device id
+#[utoipa::path(
+    get,
+    path = "/get_completed",
+    tag = "tasks",
+    params(Device),
+    responses(
+        (status = 200, body = Vec<TaskBase>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
 pub async fn get_completed_tasks(
     device: Device,
     State(app_state): State<AppState>,
 ) -> Result<Json<Vec<TaskBase>>, AppError> {
     if device.0.is_none() {
-        return Err(BadRequest("Endpoint Requires a device id".into()));
+        return Err(Unauthorized("Endpoint Requires a device id".into()));
     }
 
     let completed_tasks =
@@ -500,45 +959,88 @@ pub async fn get_completed_tasks(
     Ok(Json(fetch_page_by_ids(&app_state, completed_tasks).await?))
 }
 
-#[derive(serde::Deserialize)]
+/*
+the tasks a completion change spreads to, completing a task completes the prerequisites it needed
+finished but one that only had to be accepted or failed was never completed so it is left alone,
+the search still goes through it since accepting it needed everything before it to be done.
+uncompleting a task leaves the tasks that only needed it accepted or failed since it can still be
+in that state, anything that needed it finished is locked now and a locked task cannot be accepted
+either so everything after those is uncompleted no matter the requirement
+*/
+fn affected_tasks(adj_list: &AdjList, task_id: &str, direction: bool) -> HashSet<String> {
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut marked_tasks: HashSet<String> = HashSet::new();
+
+    let mut st = vec![(task_id.to_string(), true)];
+    while let Some((top, marked)) = st.pop() {
+        if marked {
+            marked_tasks.insert(top.clone());
+        }
+        if !visited.insert(top.clone()) {
+            continue;
+        }
+
+        for (adj_task_id, unlocks, kind) in adj_list.get(&top).into_iter().flatten() {
+            if *unlocks != direction {
+                continue;
+            }
+            let finished = *kind == RequirementKind::Finished;
+            if direction && top == task_id && !finished {
+                continue;
+            }
+            st.push((adj_task_id.clone(), direction || finished));
+        }
+    }
+
+    marked_tasks
+}
+
+#[derive(serde::Deserialize, ToSchema)]
 pub struct AffectedTask {
     task_id: String,
     direction: bool,
 }
 
 // performs a dfs completing all tasks either before or after depending on AffectedTask.direction
+#[utoipa::path(
+    post,
+    path = "/set_complete",
+    tag = "tasks",
+    params(Device),
+    request_body = AffectedTask,
+    responses(
+        (status = 200),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
 pub async fn set_completed_task(
     device: Device,
     State(app_state): State<AppState>,
     Json(task): Json<AffectedTask>,
 ) -> Result<(), AppError> {
     if device.0.is_none() {
-        return Err(BadRequest("Endpoint Requires a device id".into()));
+        return Err(Unauthorized("Endpoint Requires a device id".into()));
     }
     let device_id = device.0.unwrap();
     let task_id = task.task_id;
 
-    // perform a dfs on adj_list
-    let adj_list = fetch_adj_list(&app_state).await?;
-
-    let mut visited: HashSet<String> = HashSet::new();
-
-    let mut st = vec![task_id.clone()];
-    let mut marked_tasks: HashSet<String> = HashSet::new();
-    while let Some(top) = st.pop() {
-        if visited.contains(&top) {
-            continue;
-        }
-        marked_tasks.insert(top.clone());
-        visited.insert(top.clone());
+    let task_exists = sqlx::query_scalar!(
+        "SELECT EXISTS(SELECT 1 FROM Task WHERE _id = $1) AS \"exists!\"",
+        task_id
+    )
+    .fetch_one(&app_state.pgpool)
+    .instrument(db_span("Tasks"))
+    .await
+    .bad_sql("Tasks")?;
 
-        if let Some(x) = adj_list.get(&top) {
-            x.iter()
-                .filter(|(_, status)| *status == task.direction)
-                .for_each(|(adj_task_id, _)| st.push(adj_task_id.clone()));
-        }
+    if !task_exists {
+        return Err(NotFound(format!("Task {task_id} does not exist")));
     }
 
+    // perform a dfs on adj_list
+    let adj_list = fetch_adj_list(&app_state).await?;
+    let marked_tasks = affected_tasks(&adj_list, &task_id, task.direction);
+
     let mut completed_tasks: HashSet<String> =
         get_completed_task_by_device_id(&app_state.pgpool, device_id)
             .await?
@@ -568,6 +1070,7 @@ pub async fn set_completed_task(
         device_id,
     )
     .fetch_all(&app_state.pgpool)
+    .instrument(db_span("Device Preferences"))
     .await
     .bad_sql("Device Preferences")?;
 
@@ -575,12 +1078,22 @@ pub async fn set_completed_task(
 }
 
 // sets the device completed tasks to empty
+#[utoipa::path(
+    get,
+    path = "/clear_completed_tasks",
+    tag = "tasks",
+    params(Device),
+    responses(
+        (status = 200),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
 pub async fn clear_completed_tasks(
     device: Device,
     State(app_state): State<AppState>,
 ) -> Result<(), AppError> {
     if device.0.is_none() {
-        return Err(BadRequest("Endpoint Requires a device id".into()));
+        return Err(Unauthorized("Endpoint Requires a device id".into()));
     }
 
     sqlx::query!(
@@ -589,19 +1102,38 @@ pub async fn clear_completed_tasks(
         device.0.unwrap()
     )
     .execute(&app_state.pgpool)
+    .instrument(db_span("Device Preferences"))
     .await
     .bad_sql("Device Preferences")?;
 
     Ok(())
 }
 
+#[utoipa::path(
+    get,
+    path = "/help",
+    tag = "tasks",
+    params(TaskQueryParams),
+    responses((status = 200, body = TaskQueryParams))
+)]
 pub async fn get_tasks_help(Query(query_parms): Query<TaskQueryParams>) -> Json<TaskQueryParams> {
     Json(query_parms)
 }
 
+#[utoipa::path(
+    get,
+    path = "/get_required_items",
+    tag = "tasks",
+    params(Device, TaskQueryParams),
+    responses(
+        (status = 200, body = Vec<(ItemBase, i32)>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
 pub async fn get_required_items(
     device: Device,
     Query(query_parms): Query<TaskQueryParams>,
+    RawQuery(raw_query): RawQuery,
     State(app_state): State<AppState>,
 ) -> Result<Json<Vec<(ItemBase, i32)>>, AppError> {
     let TaskQueryParams {
@@ -614,17 +1146,20 @@ pub async fn get_required_items(
         player_lvl,
         limit: _,
         offset: _,
-        include_completed,
+        cursor: _,
+        include_completed: _,
+        available_only: _,
+        strict,
     } = query_parms.clone();
 
+    if strict {
+        validate_strict(raw_query.as_deref(), &query_parms, TASK_FIELD_RULES)?;
+    }
+
     #[allow(clippy::cast_possible_wrap)]
     let player_lvl = player_lvl as i32;
 
-    let ids = if include_completed && let Some(device_id) = device.0 {
-        get_completed_task_by_device_id(&app_state.pgpool, device_id).await?
-    } else {
-        vec![]
-    };
+    let ids = excluded_task_ids(&app_state, &query_parms, device.0).await?;
 
     let count_cache_key = format!(
         "{}r{}{}{}{}{}{}",
@@ -673,6 +1208,7 @@ pub async fn get_required_items(
         format!("%{obj_type}%"),
     )
     .fetch_all(&app_state.pgpool)
+    .instrument(db_span("NeededItems"))
     .await
     .bad_sql("NeededItems")?;
 
@@ -711,3 +1247,184 @@ pub async fn get_required_items(
 
     Ok(Json(items.into_iter().zip(counts.into_iter()).collect()))
 }
+
+// the task and every prerequisite before it, a completed task ends the search along that branch
+// since everything before it has to have been done already
+fn collect_prerequisites(
+    adj_list: &AdjList,
+    task_id: &str,
+    completed: &HashSet<String>,
+) -> HashSet<String> {
+    let mut visited: HashSet<String> = HashSet::new();
+    let mut st = vec![task_id.to_string()];
+    while let Some(top) = st.pop() {
+        if completed.contains(&top) || !visited.insert(top.clone()) {
+            continue;
+        }
+
+        if let Some(x) = adj_list.get(&top) {
+            x.iter()
+                .filter(|(_, unlocks, _)| !unlocks)
+                .for_each(|(adj_task_id, _, _)| st.push(adj_task_id.clone()));
+        }
+    }
+
+    visited
+}
+
+/*
+kahn's algorithm over the prerequisites, when several tasks are ready the lowest level one goes
+first so the order doubles as a plan, any tasks left over are part of a cycle in the api data
+and are put at the end instead of failing the whole path
+*/
+fn topological_order(adj_list: &AdjList, tasks: Vec<Task>) -> Vec<Task> {
+    let ids: HashSet<&str> = tasks.iter().map(|x| x._id.as_str()).collect();
+
+    let mut remaining: HashMap<&str, usize> = HashMap::new();
+    for id in &ids {
+        let count = adj_list.get(*id).map_or(0, |x| {
+            x.iter()
+                .filter(|(adj_task_id, unlocks, _)| !unlocks && ids.contains(adj_task_id.as_str()))
+                .count()
+        });
+        remaining.insert(id, count);
+    }
+
+    let level: HashMap<&str, i32> = tasks
+        .iter()
+        .map(|x| (x._id.as_str(), x.min_player_level))
+        .collect();
+    let mut ready: BinaryHeap<Reverse<(i32, &str)>> = remaining
+        .iter()
+        .filter(|(_, count)| **count == 0)
+        .map(|(id, _)| Reverse((level[id], *id)))
+        .collect();
+
+    let mut order: Vec<String> = vec![];
+    while let Some(Reverse((_, id))) = ready.pop() {
+        order.push(id.to_string());
+
+        for (adj_task_id, _, _) in adj_list
+            .get(id)
+            .into_iter()
+            .flatten()
+            .filter(|(_, unlocks, _)| *unlocks)
+        {
+            let adj_task_id = adj_task_id.as_str();
+            if let Some(count) = remaining.get_mut(adj_task_id) {
+                *count -= 1;
+                if *count == 0 {
+                    ready.push(Reverse((level[adj_task_id], adj_task_id)));
+                }
+            }
+        }
+    }
+
+    let mut cycle: Vec<&str> = remaining
+        .iter()
+        .filter(|(_, count)| **count > 0)
+        .map(|(id, _)| *id)
+        .collect();
+    cycle.sort_by_key(|id| (level[id], *id));
+    order.extend(cycle.into_iter().map(ToString::to_string));
+
+    let mut tasks: HashMap<String, Task> = tasks.into_iter().map(|x| (x._id.clone(), x)).collect();
+    order
+        .into_iter()
+        .filter_map(|id| tasks.remove(&id))
+        .collect()
+}
+
+// everything that still has to be done to unlock a task, in an order it can be done in
+#[utoipa::path(
+    get,
+    path = "/{id}/path",
+    tag = "tasks",
+    params(("id" = String, Path, description = "task id"), Device, TaskPathQueryParams),
+    responses(
+        (status = 200, body = TaskPath),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_task_path(
+    device: Device,
+    Path(task_id): Path<String>,
+    Query(query_parms): Query<TaskPathQueryParams>,
+    RawQuery(raw_query): RawQuery,
+    State(app_state): State<AppState>,
+) -> Result<Json<TaskPath>, AppError> {
+    if query_parms.strict {
+        validate_strict(raw_query.as_deref(), &query_parms, TASK_PATH_FIELD_RULES)?;
+    }
+
+    let completed: HashSet<String> = match device.0 {
+        Some(device_id) => get_completed_task_by_device_id(&app_state.pgpool, device_id)
+            .await?
+            .into_iter()
+            .collect(),
+        None => HashSet::new(),
+    };
+
+    // paths only change with the task data so they are cached for devices without completed tasks
+    let cache_key = format!(
+        "{}path{}{}",
+        TASKS_UNIQUE_CACHE_PREFIX,
+        task_id,
+        query_parms.obj_type.to_lowercase()
+    );
+    let use_cache = completed.is_empty();
+    if use_cache && let Some(path) = app_state.cache.get(&cache_key) {
+        return Ok(Json(path));
+    }
+
+    let adj_list = fetch_adj_list(&app_state).await?;
+    let ids: Vec<String> = collect_prerequisites(&adj_list, &task_id, &completed)
+        .into_iter()
+        .collect();
+    let tasks: Vec<Task> = fetch_page_by_ids(&app_state, ids).await?;
+
+    if !completed.contains(&task_id) && !tasks.iter().any(|x| x._id == task_id) {
+        return Err(NotFound(format!("Task {task_id} does not exist")));
+    }
+
+    let tasks = topological_order(&adj_list, tasks);
+
+    let mut item_to_count: HashMap<String, i32> = HashMap::new();
+    for objective in tasks.iter().flat_map(|x| &x.objectives) {
+        if !query_parms.obj_type.is_empty()
+            && !objective
+                .obj_type
+                .eq_ignore_ascii_case(&query_parms.obj_type)
+        {
+            continue;
+        }
+        for item_id in &objective.needed_item_ids {
+            *item_to_count.entry(item_id.clone()).or_insert(0) += objective.count;
+        }
+    }
+
+    let item_ids: Vec<String> = item_to_count.keys().cloned().collect();
+    let required_items = fetch_page_by_ids::<ItemBase>(&app_state, item_ids)
+        .await?
+        .into_iter()
+        .map(|item| RequiredItem {
+            count: item_to_count.get(&item._id).copied().unwrap_or(0),
+            item,
+        })
+        .collect();
+
+    let path = TaskPath {
+        min_player_level: tasks.iter().map(|x| x.min_player_level).max().unwrap_or(0),
+        tasks,
+        required_items,
+    };
+
+    if use_cache {
+        let (cache, tokio_path) = (app_state.cache.clone(), path.clone());
+        tokio::spawn(async move {
+            cache.insert(cache_key, tokio_path, TASKS_UNIQUE_CACHE_PREFIX);
+        });
+    }
+
+    Ok(Json(path))
+}
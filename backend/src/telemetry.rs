@@ -0,0 +1,103 @@
+use anyhow::Result;
+use axum::http::HeaderName;
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{Layer, fmt};
+
+pub const REQUEST_ID_HEADER: HeaderName = HeaderName::from_static("x-request-id");
+
+// dropping this flushes the buffered logs and any spans that have not been exported yet
+pub struct TelemetryGuard {
+    _log_guard: WorkerGuard,
+    #[cfg(feature = "otel")]
+    tracer_provider: Option<opentelemetry_sdk::trace::SdkTracerProvider>,
+}
+
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Some(provider) = self.tracer_provider.take()
+            && let Err(e) = provider.shutdown()
+        {
+            eprintln!("failed to flush otel spans with error {e}");
+        }
+    }
+}
+
+// LOG_FORMAT=json switches stdout logs to one json object per line
+// with the otel feature enabled spans are also exported when OTEL_EXPORTER_OTLP_ENDPOINT is set
+pub fn init_tracing(writer: NonBlocking, log_guard: WorkerGuard) -> Result<TelemetryGuard> {
+    let json_logs = std::env::var("LOG_FORMAT").is_ok_and(|x| x.eq_ignore_ascii_case("json"));
+    let fmt_layer = if json_logs {
+        fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_writer(writer)
+            .boxed()
+    } else {
+        fmt::layer().with_writer(writer).boxed()
+    };
+
+    let registry = tracing_subscriber::registry()
+        .with(LevelFilter::INFO)
+        .with(fmt_layer);
+
+    #[cfg(feature = "otel")]
+    {
+        let tracer_provider = otel::tracer_provider()?;
+        let otel_layer = tracer_provider.as_ref().map(otel::layer);
+        registry.with(otel_layer).try_init()?;
+
+        Ok(TelemetryGuard {
+            _log_guard: log_guard,
+            tracer_provider,
+        })
+    }
+
+    #[cfg(not(feature = "otel"))]
+    {
+        registry.try_init()?;
+
+        Ok(TelemetryGuard {
+            _log_guard: log_guard,
+        })
+    }
+}
+
+// wraps a sqlx query so it shows up as its own span under the request
+pub fn db_span(name: &'static str) -> tracing::Span {
+    tracing::info_span!("db_query", db.system = "postgresql", query = name)
+}
+
+#[cfg(feature = "otel")]
+mod otel {
+    use anyhow::Result;
+    use opentelemetry::trace::TracerProvider;
+    use opentelemetry_otlp::SpanExporter;
+    use opentelemetry_sdk::{Resource, trace::SdkTracerProvider};
+    use tracing_subscriber::Layer;
+
+    // the exporter reads the endpoint and headers from the standard OTEL_EXPORTER_OTLP_* env vars
+    pub fn tracer_provider() -> Result<Option<SdkTracerProvider>> {
+        if std::env::var("OTEL_EXPORTER_OTLP_ENDPOINT").is_err() {
+            return Ok(None);
+        }
+
+        let exporter = SpanExporter::builder().with_http().build()?;
+        Ok(Some(
+            SdkTracerProvider::builder()
+                .with_batch_exporter(exporter)
+                .with_resource(Resource::builder().with_service_name("backend").build())
+                .build(),
+        ))
+    }
+
+    pub fn layer<S>(provider: &SdkTracerProvider) -> impl Layer<S> + use<S>
+    where
+        S: tracing::Subscriber + for<'span> tracing_subscriber::registry::LookupSpan<'span>,
+    {
+        tracing_opentelemetry::layer().with_tracer(provider.tracer("backend"))
+    }
+}
@@ -1,10 +1,16 @@
 use crate::{
+    app_metrics::{record_ingestion, record_ingestion_failure, record_task_graph_issues},
     caching::AppCache,
-    deserialize_json_types::{AMMO_QUERY, Ammo, ITEMS_QUERY, Item, TASKS_QUERY, Task},
+    deserialize_json_types::{
+        AMMO_QUERY, ARMOR_QUERY, Ammo, Armor, ArmorProperties, ITEMS_QUERY, Item, TASKS_QUERY,
+        Task, WEAPONS_QUERY, Weapon, WeaponProperties,
+    },
     init_app_state::{
-        AMMO_UNIQUE_CACHE_PREFIX, ITEM_HISTORY_SIZE, ITEMS_UNIQUE_CACHE_PREFIX,
-        TASKS_UNIQUE_CACHE_PREFIX,
+        AMMO_UNIQUE_CACHE_PREFIX, ARMOR_UNIQUE_CACHE_PREFIX, ITEM_HISTORY_SIZE,
+        ITEMS_UNIQUE_CACHE_PREFIX, TASKS_UNIQUE_CACHE_PREFIX, WEAPONS_UNIQUE_CACHE_PREFIX,
     },
+    snapshots::{archive_snapshot, snapshot_dir},
+    task_graph::{CYCLE, DANGLING_REQUIREMENT, ORPHANED_TASK, find_issues},
 };
 use chrono::Utc;
 use reqwest::Client;
@@ -13,13 +19,83 @@ use serde_json::Value;
 use sqlx::PgPool;
 use std::{
     error::Error,
+    fmt::Display,
     io::Write,
     sync::Arc,
     time::{Duration, Instant},
 };
 use tokio::sync::RwLock;
+use tokio_util::sync::CancellationToken;
+
+// a single entry from the errors array of a graphql response
+#[derive(Deserialize, Debug)]
+pub struct GraphQLError {
+    pub message: String,
+    #[serde(default)]
+    pub path: Vec<Value>,
+}
+
+impl GraphQLError {
+    // turns a path like ["items", 12, "buyFor"] into items.12.buyFor
+    pub fn path_string(&self) -> String {
+        if self.path.is_empty() {
+            return String::from("<root>");
+        }
+
+        self.path
+            .iter()
+            .map(|x| match x {
+                Value::String(s) => s.clone(),
+                v => v.to_string(),
+            })
+            .collect::<Vec<String>>()
+            .join(".")
+    }
+}
+
+#[derive(Debug)]
+pub enum QueryError {
+    BadStatus(reqwest::StatusCode),
+    GraphQL {
+        page: &'static str,
+        errors: Vec<GraphQLError>,
+    },
+}
 
-async fn run_query(query: &str) -> Result<Value, Box<dyn Error>> {
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::BadStatus(status) => write!(f, "Query failed with status: {status}"),
+            Self::GraphQL { page, errors } => {
+                write!(f, "{page} query returned {} graphql errors", errors.len())?;
+                for e in errors.iter().take(5) {
+                    write!(f, "; {}: {}", e.path_string(), e.message)?;
+                }
+                if errors.len() > 5 {
+                    write!(f, "; and {} more", errors.len() - 5)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Error for QueryError {}
+
+// what to do when the api responds with both data and an errors array
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PartialDataPolicy {
+    // any graphql error fails the upsert and the db keeps the previous data
+    Reject,
+    // errors get logged and whatever data came back is used, entries that no longer deserialize are dropped
+    Accept,
+}
+
+async fn run_query<T: DeserializeOwned>(
+    query: &str,
+    page: &'static str,
+    policy: PartialDataPolicy,
+) -> Result<Vec<T>, Box<dyn Error>> {
     let res = Client::new()
         .post("https://api.tarkov.dev/graphql")
         .header("Content-Type", "applicaion/json")
@@ -28,27 +104,138 @@ async fn run_query(query: &str) -> Result<Value, Box<dyn Error>> {
         .send()
         .await?;
 
-    if res.status().is_success() {
-        return Ok(res.json().await?);
+    if !res.status().is_success() {
+        return Err(QueryError::BadStatus(res.status()).into());
     }
-    Err(format!("Query failed with status: {}", res.status()).into())
+
+    let json: Value = res.json().await?;
+
+    // keep the raw response around so a bad refresh can be debugged or replayed later,
+    // gzipping the items response takes long enough that it should not run on the runtime
+    let (json, archived) = tokio::task::spawn_blocking(move || {
+        let archived = archive_snapshot(&snapshot_dir(), page, &json).map_err(|e| e.to_string());
+        (json, archived)
+    })
+    .await?;
+    if let Err(e) = archived {
+        tracing::warn!("failed to archive {} snapshot with error {}", page, e);
+    }
+
+    parse_response(json, page, policy)
+}
+
+// pulls the page out of a graphql response, with errors present an entry the api nulled out
+// or nulled a field of fails to deserialize so under Accept only that entry gets dropped
+pub fn parse_response<T: DeserializeOwned>(
+    mut json: Value,
+    page: &'static str,
+    policy: PartialDataPolicy,
+) -> Result<Vec<T>, Box<dyn Error>> {
+    let errors = match json.get_mut("errors").map(Value::take) {
+        Some(errors @ Value::Array(_)) => Vec::<GraphQLError>::deserialize(errors)?,
+        _ => vec![],
+    };
+
+    if errors.is_empty() {
+        return Ok(Vec::<T>::deserialize(&json["data"][page])?);
+    }
+
+    for e in &errors {
+        tracing::warn!(
+            "{} graphql error at {}: {}",
+            page,
+            e.path_string(),
+            e.message
+        );
+    }
+
+    let Some(entries) = json["data"][page].as_array() else {
+        return Err(QueryError::GraphQL { page, errors }.into());
+    };
+    if policy == PartialDataPolicy::Reject {
+        return Err(QueryError::GraphQL { page, errors }.into());
+    }
+
+    let mut values = Vec::with_capacity(entries.len());
+    for (i, entry) in entries.iter().enumerate() {
+        match T::deserialize(entry) {
+            Ok(value) => values.push(value),
+            Err(e) => {
+                // errors are reported against paths like items.12.buyFor so find the ones for this entry
+                let paths: Vec<String> = errors
+                    .iter()
+                    .filter(|x| {
+                        x.path.first().and_then(Value::as_str) == Some(page)
+                            && x.path.get(1).and_then(Value::as_u64) == u64::try_from(i).ok()
+                    })
+                    .map(GraphQLError::path_string)
+                    .collect();
+                tracing::warn!(
+                    "dropping {} entry {} with graphql errors at [{}]: {}",
+                    page,
+                    i,
+                    paths.join(", "),
+                    e
+                );
+            }
+        }
+    }
+
+    tracing::warn!(
+        "accepting partial {} data with {} errors, dropped {} entries",
+        page,
+        errors.len(),
+        entries.len() - values.len()
+    );
+
+    Ok(values)
 }
 
-pub trait Upsert: DeserializeOwned + Serialize {
+// keeps track of when each dataset was last upserted and where the data came from
+pub async fn record_refresh(
+    page: &str,
+    source: &str,
+    row_count: usize,
+    pgpool: &PgPool,
+) -> Result<(), Box<dyn Error>> {
+    sqlx::query!(
+        "INSERT INTO DatasetRefresh (page, source, row_count, refreshed_at) VALUES ($1, $2, $3, $4)
+        ON CONFLICT (page) DO UPDATE SET source = EXCLUDED.source, row_count = EXCLUDED.row_count, refreshed_at = EXCLUDED.refreshed_at",
+        page,
+        source,
+        i32::try_from(row_count)?,
+        Utc::now()
+    )
+    .execute(pgpool)
+    .await?;
+
+    Ok(())
+}
+
+// the refresh futures are declared Send so spawn_refresh can spawn them for any dataset
+pub trait Upsert: DeserializeOwned + Serialize + Send + Sync {
     fn get_page() -> &'static str;
     fn get_query() -> &'static str;
-    async fn upsert_data(
+
+    fn partial_data_policy() -> PartialDataPolicy {
+        PartialDataPolicy::Reject
+    }
+
+    fn upsert_data(
         values: &[Self],
         pgpool: &PgPool,
         is_api_call: bool,
-    ) -> Result<(), Box<dyn Error>>;
+    ) -> impl Future<Output = Result<(), Box<dyn Error>>> + Send;
 
     async fn file_upsert(file_name: &str, pgpool: &PgPool) -> Result<(), Box<dyn Error>> {
         let page = Self::get_page();
+        let start = Instant::now();
         let file = std::fs::File::open(file_name)?;
         let json: Value = serde_json::from_reader(file)?;
         let values = Vec::<Self>::deserialize(&json)?;
         Self::upsert_data(&values, pgpool, false).await?;
+        record_refresh(page, "file", values.len(), pgpool).await?;
+        record_ingestion(page, "file", start.elapsed(), values.len());
 
         tracing::info!(
             "finished {} upsert via file with {} entries",
@@ -59,21 +246,29 @@ pub trait Upsert: DeserializeOwned + Serialize {
         Ok(())
     }
 
-    async fn api_upsert(file_name: &str, pgpool: &PgPool) -> Result<(), Box<dyn Error>> {
-        let page = Self::get_page();
-        let json = run_query(Self::get_query()).await?;
-        let values = Vec::<Self>::deserialize(&json["data"][page])?;
-        Self::upsert_data(&values, pgpool, true).await?;
-
-        let json_string = serde_json::to_string_pretty(&serde_json::json!(values))?;
-        let mut file = std::fs::File::create(file_name)?;
-        file.write_all(json_string.as_bytes())?;
-        tracing::info!(
-            "finished {} upsert via api with {} entries",
-            page,
-            values.len()
-        );
-        Ok(())
+    fn api_upsert(
+        file_name: &str,
+        pgpool: &PgPool,
+    ) -> impl Future<Output = Result<(), Box<dyn Error>>> + Send {
+        async move {
+            let page = Self::get_page();
+            let start = Instant::now();
+            let values: Vec<Self> =
+                run_query(Self::get_query(), page, Self::partial_data_policy()).await?;
+            Self::upsert_data(&values, pgpool, true).await?;
+            record_refresh(page, "api", values.len(), pgpool).await?;
+            record_ingestion(page, "api", start.elapsed(), values.len());
+
+            let json_string = serde_json::to_string_pretty(&serde_json::json!(values))?;
+            let mut file = std::fs::File::create(file_name)?;
+            file.write_all(json_string.as_bytes())?;
+            tracing::info!(
+                "finished {} upsert via api with {} entries",
+                page,
+                values.len()
+            );
+            Ok(())
+        }
     }
 
     async fn init(file: &'static str, pgpool: PgPool) {
@@ -88,22 +283,35 @@ pub trait Upsert: DeserializeOwned + Serialize {
 
     fn unique_cache_prefix() -> char;
 
-    async fn background_task(
+    fn background_task(
         file: &'static str,
         timer: &Arc<RwLock<Instant>>,
         refresh_time_seconds: u64,
         cache: &mut AppCache,
         pgpool: &PgPool,
-    ) {
-        let refresh_time = Duration::from_secs(refresh_time_seconds);
-        (*timer.write().await) = Instant::now() + refresh_time;
+        shutdown: &CancellationToken,
+    ) -> impl Future<Output = ()> + Send {
+        async move {
+            let refresh_time = Duration::from_secs(refresh_time_seconds);
+            (*timer.write().await) = Instant::now() + refresh_time;
+
+            // only the sleep is cancelled so a shutdown never interrupts an upsert transaction
+            tokio::select! {
+                () = shutdown.cancelled() => return,
+                () = tokio::time::sleep(refresh_time) => {}
+            }
 
-        tokio::time::sleep(refresh_time).await;
-        if Self::api_upsert(file, pgpool).await.is_err() {
-            tracing::error!("UPSERT {} VIA API FAILED", Self::get_page());
-        }
+            if let Err(e) = Self::api_upsert(file, pgpool).await {
+                record_ingestion_failure(Self::get_page());
+                tracing::error!(
+                    "UPSERT {} VIA API FAILED WITH ERROR {}",
+                    Self::get_page(),
+                    e
+                );
+            }
 
-        cache.invalidate_cache_prefix(Self::unique_cache_prefix());
+            cache.invalidate_cache_prefix(Self::unique_cache_prefix());
+        }
     }
 }
 
@@ -116,6 +324,11 @@ impl Upsert for Item {
         ITEMS_QUERY
     }
 
+    // a handful of items missing prices should not stop the prices of every other item from refreshing
+    fn partial_data_policy() -> PartialDataPolicy {
+        PartialDataPolicy::Accept
+    }
+
     async fn upsert_data(
         values: &[Self],
         pgpool: &PgPool,
@@ -143,7 +356,14 @@ impl Upsert for Task {
         pgpool: &PgPool,
         _is_api_call: bool,
     ) -> Result<(), Box<dyn Error>> {
-        upsert_tasks(values, pgpool).await
+        upsert_tasks(values, pgpool).await?;
+
+        // the tasks are already in so a failed check is only logged
+        if let Err(e) = check_task_graph(values, pgpool).await {
+            tracing::warn!("task graph check failed with error {}", e);
+        }
+
+        Ok(())
     }
 
     fn unique_cache_prefix() -> char {
@@ -173,6 +393,50 @@ impl Upsert for Ammo {
     }
 }
 
+impl Upsert for Weapon {
+    fn get_page() -> &'static str {
+        "weapons"
+    }
+
+    fn get_query() -> &'static str {
+        WEAPONS_QUERY
+    }
+
+    async fn upsert_data(
+        values: &[Self],
+        pgpool: &PgPool,
+        _is_api_call: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        upsert_weapons(values, pgpool).await
+    }
+
+    fn unique_cache_prefix() -> char {
+        WEAPONS_UNIQUE_CACHE_PREFIX
+    }
+}
+
+impl Upsert for Armor {
+    fn get_page() -> &'static str {
+        "armor"
+    }
+
+    fn get_query() -> &'static str {
+        ARMOR_QUERY
+    }
+
+    async fn upsert_data(
+        values: &[Self],
+        pgpool: &PgPool,
+        _is_api_call: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        upsert_armor(values, pgpool).await
+    }
+
+    fn unique_cache_prefix() -> char {
+        ARMOR_UNIQUE_CACHE_PREFIX
+    }
+}
+
 // inserts all of the input items into the db
 #[allow(clippy::too_many_lines)]
 async fn upsert_items(
@@ -527,8 +791,8 @@ async fn upsert_tasks(
         }
 
         sqlx::query!(
-            "INSERT INTO TaskRequirement (status, req_task_id, task_id) SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[]) ON CONFLICT DO NOTHING;",
-            &task.task_requirements.iter().map(|x| x.status.join(", ")).collect::<Vec<String>>(),
+            "INSERT INTO TaskRequirement (statuses, req_task_id, task_id) SELECT string_to_array(s, '|'), r, t FROM UNNEST($1::text[], $2::text[], $3::text[]) AS x(s, r, t) ON CONFLICT DO NOTHING;",
+            &task.task_requirements.iter().map(|x| x.status.iter().map(|x| x.to_lowercase()).collect::<Vec<String>>().join("|")).collect::<Vec<String>>(),
             &task.task_requirements.iter().map(|x| x.req_task_id.id.clone()).collect::<Vec<String>>(),
             &vec![task._id.clone();task.task_requirements.len()],
         ).execute(&mut *txn)
@@ -539,6 +803,51 @@ async fn upsert_tasks(
     Ok(())
 }
 
+// looks for requirements on missing tasks, requirement cycles and tasks cut off from every other task
+// and replaces the recorded issues with what was found, see TaskGraphIssue
+async fn check_task_graph(
+    tasks: &[Task],
+    pool: &sqlx::Pool<sqlx::Postgres>,
+) -> Result<(), Box<dyn Error>> {
+    let task_ids: Vec<String> = tasks.iter().map(|x| x._id.clone()).collect();
+    let requirements: Vec<(String, String)> = tasks
+        .iter()
+        .flat_map(|task| {
+            task.task_requirements
+                .iter()
+                .map(|x| (x.req_task_id.id.clone(), task._id.clone()))
+        })
+        .collect();
+
+    let issues = find_issues(&task_ids, &requirements);
+    for kind in [DANGLING_REQUIREMENT, CYCLE, ORPHANED_TASK] {
+        let count = issues.iter().filter(|x| x.kind == kind).count();
+        record_task_graph_issues(kind, count);
+        if count > 0 && kind != ORPHANED_TASK {
+            tracing::warn!("task graph has {} {} issues", count, kind);
+        }
+    }
+
+    let mut txn = pool.begin().await?;
+    sqlx::query!("TRUNCATE TABLE TaskGraphIssue")
+        .execute(&mut *txn)
+        .await?;
+
+    sqlx::query!(
+        "INSERT INTO TaskGraphIssue (kind, task_id, related_task_ids, detected_at)
+        SELECT k, t, string_to_array(r, '|'), d FROM UNNEST($1::text[], $2::text[], $3::text[], $4::timestamptz[]) AS x(k, t, r, d)",
+        &issues.iter().map(|x| x.kind.clone()).collect::<Vec<String>>(),
+        &issues.iter().map(|x| x.task_id.clone()).collect::<Vec<String>>(),
+        &issues.iter().map(|x| x.related_task_ids.join("|")).collect::<Vec<String>>(),
+        &issues.iter().map(|x| x.detected_at).collect::<Vec<_>>(),
+    )
+    .execute(&mut *txn)
+    .await?;
+
+    txn.commit().await?;
+    Ok(())
+}
+
 async fn upsert_ammo(
     ammos: &[Ammo],
     pool: &sqlx::Pool<sqlx::Postgres>,
@@ -583,3 +892,113 @@ async fn upsert_ammo(
     txn.commit().await?;
     Ok(())
 }
+
+// weapons without a caliber can not be matched to ammo so they are left out
+async fn upsert_weapons(
+    weapons: &[Weapon],
+    pool: &sqlx::Pool<sqlx::Postgres>,
+) -> Result<(), Box<dyn Error>> {
+    let weapons: Vec<(&Weapon, &WeaponProperties, &String)> = weapons
+        .iter()
+        .filter_map(|x| {
+            let properties = x.properties.as_ref()?;
+            Some((x, properties, properties.caliber.as_ref()?))
+        })
+        .collect();
+
+    let mut txn = pool.begin().await?;
+    sqlx::query!("TRUNCATE TABLE Weapon")
+        .execute(&mut *txn)
+        .await?;
+
+    sqlx::query!(
+        "INSERT INTO Weapon (_id, weapon_name, short_name, caliber, fire_rate, ergonomics, recoil_vertical, recoil_horizontal)
+        SELECT * FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[], $5::int[], $6::real[], $7::int[], $8::int[])
+        ON CONFLICT (_id) DO NOTHING",
+        &weapons.iter().map(|(x, _, _)| x._id.clone()).collect::<Vec<String>>(),
+        &weapons.iter().map(|(x, _, _)| x.weapon_name.clone()).collect::<Vec<String>>(),
+        &weapons.iter().map(|(x, _, _)| x.short_name.clone()).collect::<Vec<String>>(),
+        &weapons.iter().map(|(_, _, caliber)| (*caliber).clone()).collect::<Vec<String>>(),
+        &weapons.iter().map(|(_, p, _)| p.fire_rate.unwrap_or(0)).collect::<Vec<i32>>(),
+        &weapons.iter().map(|(_, p, _)| p.ergonomics.unwrap_or(0.0)).collect::<Vec<f32>>(),
+        &weapons.iter().map(|(_, p, _)| p.recoil_vertical.unwrap_or(0)).collect::<Vec<i32>>(),
+        &weapons.iter().map(|(_, p, _)| p.recoil_horizontal.unwrap_or(0)).collect::<Vec<i32>>(),
+    )
+    .execute(&mut *txn)
+    .await?;
+
+    txn.commit().await?;
+    Ok(())
+}
+
+// plates are also typed as armor and some helmets as armor too so the most specific type wins
+fn armor_type(types: &[String]) -> Option<&'static str> {
+    ["helmet", "armorPlate", "armor"]
+        .into_iter()
+        .find(|x| types.iter().any(|t| t == x))
+        .map(|x| if x == "armorPlate" { "armorplate" } else { x })
+}
+
+// ArmoredSteel to armored_steel while UHMWPE stays in one piece
+fn material_name(id: &str) -> String {
+    let mut name = String::new();
+    let mut prev_lowercase = false;
+    for c in id.chars() {
+        if c.is_ascii_uppercase() && prev_lowercase {
+            name.push('_');
+        }
+        prev_lowercase = c.is_ascii_lowercase();
+        name.push(c.to_ascii_lowercase());
+    }
+
+    name
+}
+
+// zones are joined with | since UNNEST can not take an array per row, split again in the insert
+async fn upsert_armor(
+    armor: &[Armor],
+    pool: &sqlx::Pool<sqlx::Postgres>,
+) -> Result<(), Box<dyn Error>> {
+    let armor: Vec<(&Armor, &ArmorProperties, &str)> = armor
+        .iter()
+        .filter_map(|x| Some((x, x.properties.as_ref()?, armor_type(&x.types)?)))
+        .collect();
+
+    let mut txn = pool.begin().await?;
+    sqlx::query!("TRUNCATE TABLE Armor")
+        .execute(&mut *txn)
+        .await?;
+
+    sqlx::query!(
+        "INSERT INTO Armor (_id, armor_name, short_name, armor_type, armor_class, durability, material, blunt_throughput, ricochet_chance, zones, speed_penalty, turn_penalty, ergo_penalty, weight)
+        SELECT _id, armor_name, short_name, armor_type, armor_class, durability, material, blunt_throughput, ricochet_chance, string_to_array(zones, '|'), speed_penalty, turn_penalty, ergo_penalty, weight
+        FROM UNNEST($1::text[], $2::text[], $3::text[], $4::text[], $5::int[], $6::real[], $7::text[], $8::real[], $9::real[], $10::text[], $11::real[], $12::real[], $13::real[], $14::real[])
+        AS x(_id, armor_name, short_name, armor_type, armor_class, durability, material, blunt_throughput, ricochet_chance, zones, speed_penalty, turn_penalty, ergo_penalty, weight)
+        ON CONFLICT (_id) DO NOTHING",
+        &armor.iter().map(|(x, _, _)| x._id.clone()).collect::<Vec<String>>(),
+        &armor.iter().map(|(x, _, _)| x.armor_name.clone()).collect::<Vec<String>>(),
+        &armor.iter().map(|(x, _, _)| x.short_name.clone()).collect::<Vec<String>>(),
+        &armor.iter().map(|(_, _, t)| (*t).to_string()).collect::<Vec<String>>(),
+        &armor.iter().map(|(_, p, _)| p.class.unwrap_or(0)).collect::<Vec<i32>>(),
+        &armor.iter().map(|(_, p, _)| p.durability.unwrap_or(0.0)).collect::<Vec<f32>>(),
+        &armor
+            .iter()
+            .map(|(_, p, _)| p.material.as_ref().map(|m| material_name(&m.id)).unwrap_or_default())
+            .collect::<Vec<String>>(),
+        &armor.iter().map(|(_, p, _)| p.blunt_throughput.unwrap_or(0.0)).collect::<Vec<f32>>(),
+        &armor.iter().map(|(_, p, _)| p.ricochet_x.unwrap_or(0.0)).collect::<Vec<f32>>(),
+        &armor
+            .iter()
+            .map(|(_, p, _)| p.zones.as_ref().or(p.head_zones.as_ref()).map(|x| x.join("|")).unwrap_or_default())
+            .collect::<Vec<String>>(),
+        &armor.iter().map(|(_, p, _)| p.speed_penalty.unwrap_or(0.0)).collect::<Vec<f32>>(),
+        &armor.iter().map(|(_, p, _)| p.turn_penalty.unwrap_or(0.0)).collect::<Vec<f32>>(),
+        &armor.iter().map(|(_, p, _)| p.ergo_penalty.unwrap_or(0.0)).collect::<Vec<f32>>(),
+        &armor.iter().map(|(x, _, _)| x.weight.unwrap_or(0.0)).collect::<Vec<f32>>(),
+    )
+    .execute(&mut *txn)
+    .await?;
+
+    txn.commit().await?;
+    Ok(())
+}
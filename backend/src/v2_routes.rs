@@ -0,0 +1,879 @@
+// v2 keeps the v1 handlers as the source of truth and only reshapes what they return
+use crate::ammo_routes::{
+    ammo_filters_cache_key, ammo_stats, count_ammo, effectiveness_filters_cache_key,
+    effectiveness_sort_keys, get_ammo, get_ammo_effectiveness, get_device_ammo_query_parms,
+};
+use crate::api_routers::{Device, fetch_page_by_ids};
+use crate::armor::AmmoEffectiveness;
+use crate::armor_routes::{armor_filters_cache_key, count_armor, get_armor};
+use crate::database_types::{
+    Ammo, ArmorPiece, DeviceAmmoQueryParams, DeviceItemQueryParams, DeviceTaskQueryParams, Item,
+    Keyset, Task, TaskBase, Weapon,
+};
+use crate::init_app_state::AppState;
+use crate::item_routes::{
+    count_items, get_device_item_query_parms, get_item_history, get_items, item_filters_cache_key,
+    item_stats,
+};
+use crate::pagination::{Cursor, SortKey, task_sort_keys};
+use crate::query_types::{
+    AdjacentTask, AmmoQueryParams, AmmoStats, AppError, ArmorPieceQueryParams, ArmorQueryParams,
+    AvailableTask, Caliber, ErrorBody, IdsQueryParams, ItemHistory, ItemHistoryQueryParams,
+    ItemQueryParams, ItemStats, ListResponse, Paginated, RequiredItem, TaskGraphQueryParams,
+    TaskPath, TaskPathQueryParams, TaskQueryParams, TaskRelation, TaskStats, WeaponQueryParams,
+};
+use crate::task_graph::TaskGraph;
+use crate::task_routes::{
+    AffectedTask, clear_completed_tasks, count_tasks, excluded_task_ids, fetch_adj_list,
+    get_available_tasks, get_completed_tasks, get_device_task_query_parms, get_required_items,
+    get_task_graph, get_task_path, get_tasks, get_tasks_base, set_completed_task,
+    task_filters_cache_key, task_stats,
+};
+use crate::weapon_routes::{
+    count_weapons, get_calibers, get_weapon_ammo, get_weapons, weapon_ammo_parms,
+    weapon_filters_cache_key,
+};
+use ahash::AHashMap as HashMap;
+use axum::{
+    extract::{Path, RawQuery, State},
+    response::{Json, Response},
+};
+use axum_extra::extract::Query;
+use sqlx::types::Uuid;
+
+// v1 items silently caps the limit so v2 caps every page the same way to keep next_cursor honest
+const MAX_PAGE_LIMIT: u32 = 500;
+
+// the offset a page actually starts at since a cursor takes over from offset when both are sent
+fn page_offset(
+    cursor: Option<&str>,
+    offset: u32,
+    sort: &[SortKey],
+    filters_cache_key: &str,
+) -> Result<u32, AppError> {
+    match cursor {
+        Some(cursor) if !cursor.is_empty() => {
+            Ok(Cursor::decode(cursor, sort, filters_cache_key)?.offset)
+        }
+        _ => Ok(offset),
+    }
+}
+
+// completed tasks are left out of the v1 task lists so the count has to leave them out too
+async fn count_tasks_v2(
+    app_state: &AppState,
+    query_parms: &TaskQueryParams,
+    device_id: Option<Uuid>,
+) -> Result<i64, AppError> {
+    let excluded_ids = excluded_task_ids(app_state, query_parms, device_id).await?;
+
+    count_tasks(app_state, query_parms, &excluded_ids).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "items",
+    params(Device, ItemQueryParams),
+    responses(
+        (status = 200, body = Paginated<Item>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_items_v2(
+    device: Device,
+    Query(mut query_parms): Query<ItemQueryParams>,
+    raw_query: RawQuery,
+    app_state: State<AppState>,
+) -> Result<Json<Paginated<Item>>, AppError> {
+    query_parms.limit = query_parms.limit.min(MAX_PAGE_LIMIT);
+    let sort = query_parms.sort_keys();
+    let filters_cache_key = item_filters_cache_key(&query_parms);
+    let offset = page_offset(
+        query_parms.cursor.as_deref(),
+        query_parms.offset,
+        &sort,
+        &filters_cache_key,
+    )?;
+    let (limit, count_parms) = (query_parms.limit, query_parms.clone());
+
+    let state = app_state.0.clone();
+    let Json(items) = get_items(device, Query(query_parms), raw_query, app_state).await?;
+    let total = count_items(&state, &count_parms).await?;
+
+    let keyset = items.last().and_then(|item| item.get_keyset_offset(&sort));
+    let next_cursor = Cursor::next(
+        &sort,
+        &filters_cache_key,
+        offset,
+        items.len(),
+        total,
+        keyset,
+    );
+
+    Ok(Json(Paginated {
+        items,
+        total,
+        limit,
+        offset,
+        next_cursor,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats",
+    tag = "items",
+    responses(
+        (status = 200, body = ItemStats),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_item_stats_v2(app_state: State<AppState>) -> Result<Json<ItemStats>, AppError> {
+    item_stats(app_state).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/ids",
+    tag = "items",
+    params(IdsQueryParams),
+    responses(
+        (status = 200, body = ListResponse<Item>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_items_by_ids_v2(
+    Query(query_parms): Query<IdsQueryParams>,
+    State(app_state): State<AppState>,
+) -> Result<Json<ListResponse<Item>>, AppError> {
+    let ids = query_parms.ids.unwrap_or_default();
+
+    Ok(Json(ListResponse {
+        items: fetch_page_by_ids(&app_state, ids).await?,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/history",
+    tag = "items",
+    params(("id" = String, Path, description = "item id")),
+    responses(
+        (status = 200, body = ItemHistory),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_item_history_v2(
+    Path(item_id): Path<String>,
+    app_state: State<AppState>,
+) -> Result<Json<ItemHistory>, AppError> {
+    let Json(history) = get_item_history(
+        Query(ItemHistoryQueryParams {
+            item_id: Some(item_id.clone()),
+        }),
+        app_state,
+    )
+    .await?;
+
+    Ok(Json(ItemHistory { item_id, history }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/query_params",
+    tag = "items",
+    params(Device),
+    responses(
+        (status = 200, body = DeviceItemQueryParams),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_item_query_params_v2(
+    device: Device,
+    app_state: State<AppState>,
+) -> Result<Json<DeviceItemQueryParams>, AppError> {
+    get_device_item_query_parms(device, app_state).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "tasks",
+    params(Device, TaskQueryParams),
+    responses(
+        (status = 200, body = Paginated<Task>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_tasks_v2(
+    device: Device,
+    Query(mut query_parms): Query<TaskQueryParams>,
+    raw_query: RawQuery,
+    app_state: State<AppState>,
+) -> Result<Json<Paginated<Task>>, AppError> {
+    query_parms.limit = query_parms.limit.min(MAX_PAGE_LIMIT);
+    let sort = task_sort_keys();
+    let filters_cache_key = task_filters_cache_key(&query_parms);
+    let offset = page_offset(
+        query_parms.cursor.as_deref(),
+        query_parms.offset,
+        &sort,
+        &filters_cache_key,
+    )?;
+    let (limit, count_parms, device_id) = (query_parms.limit, query_parms.clone(), device.0);
+
+    let state = app_state.0.clone();
+    let Json(tasks) = get_tasks(device, Query(query_parms), raw_query, app_state).await?;
+    let total = count_tasks_v2(&state, &count_parms, device_id).await?;
+    let keyset = tasks.last().and_then(|task| task.get_keyset_offset(&sort));
+    let next_cursor = Cursor::next(
+        &sort,
+        &filters_cache_key,
+        offset,
+        tasks.len(),
+        total,
+        keyset,
+    );
+
+    Ok(Json(Paginated {
+        items: tasks,
+        total,
+        limit,
+        offset,
+        next_cursor,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/available",
+    tag = "tasks",
+    params(Device, TaskQueryParams),
+    responses(
+        (status = 200, body = Paginated<AvailableTask>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_available_tasks_v2(
+    device: Device,
+    Query(mut query_parms): Query<TaskQueryParams>,
+    raw_query: RawQuery,
+    app_state: State<AppState>,
+) -> Result<Json<Paginated<AvailableTask>>, AppError> {
+    query_parms.limit = query_parms.limit.min(MAX_PAGE_LIMIT);
+    query_parms.available_only = true;
+    let sort = task_sort_keys();
+    let filters_cache_key = task_filters_cache_key(&query_parms);
+    let offset = page_offset(
+        query_parms.cursor.as_deref(),
+        query_parms.offset,
+        &sort,
+        &filters_cache_key,
+    )?;
+    let (limit, count_parms, device_id) = (query_parms.limit, query_parms.clone(), device.0);
+
+    let state = app_state.0.clone();
+    let Json(tasks) = get_available_tasks(device, Query(query_parms), raw_query, app_state).await?;
+    let total = count_tasks_v2(&state, &count_parms, device_id).await?;
+    let keyset = tasks
+        .last()
+        .and_then(|task| task.task.get_keyset_offset(&sort));
+    let next_cursor = Cursor::next(
+        &sort,
+        &filters_cache_key,
+        offset,
+        tasks.len(),
+        total,
+        keyset,
+    );
+
+    Ok(Json(Paginated {
+        items: tasks,
+        total,
+        limit,
+        offset,
+        next_cursor,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/base",
+    tag = "tasks",
+    params(Device, TaskQueryParams),
+    responses(
+        (status = 200, body = Paginated<TaskBase>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_tasks_base_v2(
+    device: Device,
+    Query(mut query_parms): Query<TaskQueryParams>,
+    raw_query: RawQuery,
+    app_state: State<AppState>,
+) -> Result<Json<Paginated<TaskBase>>, AppError> {
+    query_parms.limit = query_parms.limit.min(MAX_PAGE_LIMIT);
+    let sort = task_sort_keys();
+    let filters_cache_key = task_filters_cache_key(&query_parms);
+    let offset = page_offset(
+        query_parms.cursor.as_deref(),
+        query_parms.offset,
+        &sort,
+        &filters_cache_key,
+    )?;
+    let (limit, count_parms, device_id) = (query_parms.limit, query_parms.clone(), device.0);
+
+    let state = app_state.0.clone();
+    let Json(tasks) = get_tasks_base(device, Query(query_parms), raw_query, app_state).await?;
+    let total = count_tasks_v2(&state, &count_parms, device_id).await?;
+    let keyset = tasks.last().and_then(|task| task.get_keyset_offset(&sort));
+    let next_cursor = Cursor::next(
+        &sort,
+        &filters_cache_key,
+        offset,
+        tasks.len(),
+        total,
+        keyset,
+    );
+
+    Ok(Json(Paginated {
+        items: tasks,
+        total,
+        limit,
+        offset,
+        next_cursor,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats",
+    tag = "tasks",
+    params(Device),
+    responses(
+        (status = 200, body = TaskStats),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_task_stats_v2(
+    device: Device,
+    app_state: State<AppState>,
+) -> Result<Json<TaskStats>, AppError> {
+    task_stats(device, app_state).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/ids",
+    tag = "tasks",
+    params(IdsQueryParams),
+    responses(
+        (status = 200, body = ListResponse<Task>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_tasks_by_ids_v2(
+    Query(query_parms): Query<IdsQueryParams>,
+    State(app_state): State<AppState>,
+) -> Result<Json<ListResponse<Task>>, AppError> {
+    let ids = query_parms.ids.unwrap_or_default();
+
+    Ok(Json(ListResponse {
+        items: fetch_page_by_ids(&app_state, ids).await?,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/required_items",
+    tag = "tasks",
+    params(Device, TaskQueryParams),
+    responses(
+        (status = 200, body = ListResponse<RequiredItem>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_required_items_v2(
+    device: Device,
+    query_parms: Query<TaskQueryParams>,
+    raw_query: RawQuery,
+    app_state: State<AppState>,
+) -> Result<Json<ListResponse<RequiredItem>>, AppError> {
+    let Json(required_items) =
+        get_required_items(device, query_parms, raw_query, app_state).await?;
+
+    Ok(Json(ListResponse {
+        items: required_items
+            .into_iter()
+            .map(|(item, count)| RequiredItem { item, count })
+            .collect(),
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/path",
+    tag = "tasks",
+    params(("id" = String, Path, description = "task id"), Device, TaskPathQueryParams),
+    responses(
+        (status = 200, body = TaskPath),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_task_path_v2(
+    device: Device,
+    task_id: Path<String>,
+    query_parms: Query<TaskPathQueryParams>,
+    raw_query: RawQuery,
+    app_state: State<AppState>,
+) -> Result<Json<TaskPath>, AppError> {
+    get_task_path(device, task_id, query_parms, raw_query, app_state).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/graph",
+    tag = "tasks",
+    params(Device, TaskGraphQueryParams),
+    responses(
+        (status = 200, content(
+            (TaskGraph = "application/json"),
+            (String = "text/vnd.graphviz"),
+            (String = "text/plain")
+        )),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_task_graph_v2(
+    device: Device,
+    query_parms: Query<TaskGraphQueryParams>,
+    raw_query: RawQuery,
+    app_state: State<AppState>,
+) -> Result<Response, AppError> {
+    get_task_graph(device, query_parms, raw_query, app_state).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/adj_list",
+    tag = "tasks",
+    responses(
+        (status = 200, body = HashMap<String, Vec<AdjacentTask>>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_adj_list_v2(
+    State(app_state): State<AppState>,
+) -> Result<Json<HashMap<String, Vec<AdjacentTask>>>, AppError> {
+    let adj_list = fetch_adj_list(&app_state).await?;
+
+    Ok(Json(
+        adj_list
+            .into_iter()
+            .map(|(task_id, adjacent)| {
+                let adjacent = adjacent
+                    .into_iter()
+                    .map(|(task_id, unlocks, requirement)| AdjacentTask {
+                        task_id,
+                        relation: if unlocks {
+                            TaskRelation::Unlocks
+                        } else {
+                            TaskRelation::Prerequisite
+                        },
+                        requirement,
+                    })
+                    .collect();
+                (task_id, adjacent)
+            })
+            .collect(),
+    ))
+}
+
+#[utoipa::path(
+    get,
+    path = "/completed",
+    tag = "tasks",
+    params(Device),
+    responses(
+        (status = 200, body = ListResponse<TaskBase>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_completed_tasks_v2(
+    device: Device,
+    app_state: State<AppState>,
+) -> Result<Json<ListResponse<TaskBase>>, AppError> {
+    let Json(items) = get_completed_tasks(device, app_state).await?;
+
+    Ok(Json(ListResponse { items }))
+}
+
+#[utoipa::path(
+    post,
+    path = "/completed",
+    tag = "tasks",
+    params(Device),
+    request_body = AffectedTask,
+    responses(
+        (status = 200),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn set_completed_task_v2(
+    device: Device,
+    app_state: State<AppState>,
+    task: Json<AffectedTask>,
+) -> Result<(), AppError> {
+    set_completed_task(device, app_state, task).await
+}
+
+#[utoipa::path(
+    delete,
+    path = "/completed",
+    tag = "tasks",
+    params(Device),
+    responses(
+        (status = 200),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn clear_completed_tasks_v2(
+    device: Device,
+    app_state: State<AppState>,
+) -> Result<(), AppError> {
+    clear_completed_tasks(device, app_state).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/query_params",
+    tag = "tasks",
+    params(Device),
+    responses(
+        (status = 200, body = DeviceTaskQueryParams),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_task_query_params_v2(
+    device: Device,
+    app_state: State<AppState>,
+) -> Result<Json<DeviceTaskQueryParams>, AppError> {
+    get_device_task_query_parms(device, app_state).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "ammo",
+    params(Device, AmmoQueryParams),
+    responses(
+        (status = 200, body = Paginated<Ammo>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_ammo_v2(
+    device: Device,
+    Query(mut query_parms): Query<AmmoQueryParams>,
+    raw_query: RawQuery,
+    app_state: State<AppState>,
+) -> Result<Json<Paginated<Ammo>>, AppError> {
+    query_parms.limit = query_parms.limit.min(MAX_PAGE_LIMIT);
+    let sort = query_parms.sort_keys();
+    let filters_cache_key = ammo_filters_cache_key(&query_parms);
+    let offset = page_offset(
+        query_parms.cursor.as_deref(),
+        query_parms.offset,
+        &sort,
+        &filters_cache_key,
+    )?;
+    let (limit, count_parms) = (query_parms.limit, query_parms.clone());
+
+    let state = app_state.0.clone();
+    let Json(ammo) = get_ammo(device, Query(query_parms), raw_query, app_state).await?;
+    let total = count_ammo(&state, &count_parms).await?;
+    let keyset = ammo.last().and_then(|ammo| ammo.get_keyset_offset(&sort));
+    let next_cursor = Cursor::next(&sort, &filters_cache_key, offset, ammo.len(), total, keyset);
+
+    Ok(Json(Paginated {
+        items: ammo,
+        total,
+        limit,
+        offset,
+        next_cursor,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/effectiveness",
+    tag = "ammo",
+    params(AmmoQueryParams, ArmorQueryParams),
+    responses(
+        (status = 200, body = Paginated<AmmoEffectiveness>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_ammo_effectiveness_v2(
+    Query(mut query_parms): Query<AmmoQueryParams>,
+    Query(armor_parms): Query<ArmorQueryParams>,
+    raw_query: RawQuery,
+    app_state: State<AppState>,
+) -> Result<Json<Paginated<AmmoEffectiveness>>, AppError> {
+    query_parms.limit = query_parms.limit.min(MAX_PAGE_LIMIT);
+    let sort = effectiveness_sort_keys(&query_parms, &armor_parms);
+    let filters_cache_key = effectiveness_filters_cache_key(&query_parms, &armor_parms);
+    let offset = page_offset(
+        query_parms.cursor.as_deref(),
+        query_parms.offset,
+        &sort,
+        &filters_cache_key,
+    )?;
+    let (limit, count_parms) = (query_parms.limit, query_parms.clone());
+
+    let state = app_state.0.clone();
+    let Json(rows) =
+        get_ammo_effectiveness(Query(query_parms), Query(armor_parms), raw_query, app_state)
+            .await?;
+    // every ammo row gets a result so the total is the ammo count for the same filters
+    let total = count_ammo(&state, &count_parms).await?;
+    let next_cursor = Cursor::next(&sort, &filters_cache_key, offset, rows.len(), total, None);
+
+    Ok(Json(Paginated {
+        items: rows,
+        total,
+        limit,
+        offset,
+        next_cursor,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/stats",
+    tag = "ammo",
+    responses(
+        (status = 200, body = AmmoStats),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_ammo_stats_v2(app_state: State<AppState>) -> Result<Json<AmmoStats>, AppError> {
+    ammo_stats(app_state).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/ids",
+    tag = "ammo",
+    params(IdsQueryParams),
+    responses(
+        (status = 200, body = ListResponse<Ammo>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_ammo_by_ids_v2(
+    Query(query_parms): Query<IdsQueryParams>,
+    State(app_state): State<AppState>,
+) -> Result<Json<ListResponse<Ammo>>, AppError> {
+    let ids = query_parms.ids.unwrap_or_default();
+
+    Ok(Json(ListResponse {
+        items: fetch_page_by_ids(&app_state, ids).await?,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/query_params",
+    tag = "ammo",
+    params(Device),
+    responses(
+        (status = 200, body = DeviceAmmoQueryParams),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_ammo_query_params_v2(
+    device: Device,
+    app_state: State<AppState>,
+) -> Result<Json<DeviceAmmoQueryParams>, AppError> {
+    get_device_ammo_query_parms(device, app_state).await
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "weapons",
+    params(WeaponQueryParams),
+    responses(
+        (status = 200, body = Paginated<Weapon>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_weapons_v2(
+    Query(mut query_parms): Query<WeaponQueryParams>,
+    raw_query: RawQuery,
+    app_state: State<AppState>,
+) -> Result<Json<Paginated<Weapon>>, AppError> {
+    query_parms.limit = query_parms.limit.min(MAX_PAGE_LIMIT);
+    let sort = query_parms.sort_keys();
+    let filters_cache_key = weapon_filters_cache_key(&query_parms);
+    let offset = page_offset(
+        query_parms.cursor.as_deref(),
+        query_parms.offset,
+        &sort,
+        &filters_cache_key,
+    )?;
+    let (limit, count_parms) = (query_parms.limit, query_parms.clone());
+
+    let state = app_state.0.clone();
+    let Json(weapons) = get_weapons(Query(query_parms), raw_query, app_state).await?;
+    let total = count_weapons(&state, &count_parms).await?;
+    let next_cursor = Cursor::next(
+        &sort,
+        &filters_cache_key,
+        offset,
+        weapons.len(),
+        total,
+        None,
+    );
+
+    Ok(Json(Paginated {
+        items: weapons,
+        total,
+        limit,
+        offset,
+        next_cursor,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/{id}/ammo",
+    tag = "weapons",
+    params(("id" = String, Path, description = "weapon id"), AmmoQueryParams),
+    responses(
+        (status = 200, body = Paginated<Ammo>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_weapon_ammo_v2(
+    Path(id): Path<String>,
+    Query(mut query_parms): Query<AmmoQueryParams>,
+    raw_query: RawQuery,
+    app_state: State<AppState>,
+) -> Result<Json<Paginated<Ammo>>, AppError> {
+    query_parms.limit = query_parms.limit.min(MAX_PAGE_LIMIT);
+    let sort = query_parms.sort_keys();
+    let limit = query_parms.limit;
+    let count_parms = weapon_ammo_parms(&app_state, &id, query_parms.clone()).await?;
+    // get_ammo checks the cursor against the calibers the weapon fills in so this has to as well
+    let filters_cache_key = ammo_filters_cache_key(&count_parms);
+    let offset = page_offset(
+        query_parms.cursor.as_deref(),
+        query_parms.offset,
+        &sort,
+        &filters_cache_key,
+    )?;
+
+    let state = app_state.0.clone();
+    let Json(ammo) = get_weapon_ammo(Path(id), Query(query_parms), raw_query, app_state).await?;
+    let total = count_ammo(&state, &count_parms).await?;
+    let keyset = ammo.last().and_then(|ammo| ammo.get_keyset_offset(&sort));
+    let next_cursor = Cursor::next(&sort, &filters_cache_key, offset, ammo.len(), total, keyset);
+
+    Ok(Json(Paginated {
+        items: ammo,
+        total,
+        limit,
+        offset,
+        next_cursor,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/calibers",
+    tag = "weapons",
+    responses(
+        (status = 200, body = ListResponse<Caliber>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_calibers_v2(
+    app_state: State<AppState>,
+) -> Result<Json<ListResponse<Caliber>>, AppError> {
+    let Json(calibers) = get_calibers(app_state).await?;
+
+    Ok(Json(ListResponse { items: calibers }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "armor",
+    params(ArmorPieceQueryParams),
+    responses(
+        (status = 200, body = Paginated<ArmorPiece>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_armor_v2(
+    Query(mut query_parms): Query<ArmorPieceQueryParams>,
+    raw_query: RawQuery,
+    app_state: State<AppState>,
+) -> Result<Json<Paginated<ArmorPiece>>, AppError> {
+    query_parms.limit = query_parms.limit.min(MAX_PAGE_LIMIT);
+    let sort = query_parms.sort_keys();
+    let filters_cache_key = armor_filters_cache_key(&query_parms);
+    let offset = page_offset(
+        query_parms.cursor.as_deref(),
+        query_parms.offset,
+        &sort,
+        &filters_cache_key,
+    )?;
+    let (limit, count_parms) = (query_parms.limit, query_parms.clone());
+
+    let state = app_state.0.clone();
+    let Json(armor) = get_armor(Query(query_parms), raw_query, app_state).await?;
+    let total = count_armor(&state, &count_parms).await?;
+    let keyset = armor
+        .last()
+        .and_then(|armor| armor.get_keyset_offset(&sort));
+    let next_cursor = Cursor::next(
+        &sort,
+        &filters_cache_key,
+        offset,
+        armor.len(),
+        total,
+        keyset,
+    );
+
+    Ok(Json(Paginated {
+        items: armor,
+        total,
+        limit,
+        offset,
+        next_cursor,
+    }))
+}
+
+#[utoipa::path(
+    get,
+    path = "/ids",
+    tag = "armor",
+    params(IdsQueryParams),
+    responses(
+        (status = 200, body = ListResponse<ArmorPiece>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_armor_by_ids_v2(
+    Query(query_parms): Query<IdsQueryParams>,
+    State(app_state): State<AppState>,
+) -> Result<Json<ListResponse<ArmorPiece>>, AppError> {
+    let ids = query_parms.ids.unwrap_or_default();
+
+    Ok(Json(ListResponse {
+        items: fetch_page_by_ids(&app_state, ids).await?,
+    }))
+}
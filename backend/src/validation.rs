@@ -0,0 +1,160 @@
+use crate::armor::VALID_ARMOR_MATERIALS;
+use crate::query_types::{
+    AppError, InvalidParam, VALID_AMMO_SORT_BY, VALID_AMMO_TYPE, VALID_ARMOR_SORT_BY,
+    VALID_ARMOR_TYPES, VALID_EFFECTIVENESS_RANK_BY, VALID_GRAPH_FORMATS, VALID_ITEM_SORT_BY,
+    VALID_ITEM_TYPES, VALID_OBJ_TYPES, VALID_TRADERS, VALID_WEAPON_SORT_BY,
+};
+use serde::Serialize;
+
+// the field name and the values it accepts for every param that gets coerced when it is invalid
+pub type FieldRules = &'static [(&'static str, &'static [&'static str])];
+
+pub const ITEM_FIELD_RULES: FieldRules = &[
+    ("sort_by", VALID_ITEM_SORT_BY),
+    ("sort", VALID_ITEM_SORT_BY),
+    ("item_type", VALID_ITEM_TYPES),
+    ("sellable_to", VALID_TRADERS),
+];
+
+pub const TASK_FIELD_RULES: FieldRules =
+    &[("obj_type", VALID_OBJ_TYPES), ("trader", VALID_TRADERS)];
+
+pub const TASK_PATH_FIELD_RULES: FieldRules = &[("obj_type", VALID_OBJ_TYPES)];
+
+pub const TASK_GRAPH_FIELD_RULES: FieldRules =
+    &[("format", VALID_GRAPH_FORMATS), ("trader", VALID_TRADERS)];
+
+pub const AMMO_FIELD_RULES: FieldRules = &[
+    ("sort_by", VALID_AMMO_SORT_BY),
+    ("sort", VALID_AMMO_SORT_BY),
+    ("ammo_type", VALID_AMMO_TYPE),
+];
+
+pub const ARMOR_FIELD_RULES: FieldRules = &[
+    ("sort_by", VALID_ARMOR_SORT_BY),
+    ("sort", VALID_ARMOR_SORT_BY),
+    ("armor_type", VALID_ARMOR_TYPES),
+    ("material", VALID_ARMOR_MATERIALS),
+];
+
+pub const WEAPON_FIELD_RULES: FieldRules = &[("sort_by", VALID_WEAPON_SORT_BY)];
+
+pub const AMMO_EFFECTIVENESS_FIELD_RULES: FieldRules = &[
+    ("sort_by", VALID_AMMO_SORT_BY),
+    ("sort", VALID_AMMO_SORT_BY),
+    ("ammo_type", VALID_AMMO_TYPE),
+    ("material", VALID_ARMOR_MATERIALS),
+    ("rank_by", VALID_EFFECTIVENESS_RANK_BY),
+];
+
+const MAX_NEAREST_MATCHES: usize = 3;
+
+const SORT_DIRECTIONS: &[&str] = &["asc", "desc"];
+
+// closest candidates by edit distance that are close enough to plausibly be a typo
+fn nearest_matches<'a>(value: &str, candidates: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let value = value.to_lowercase();
+    let max_distance = (value.len() / 3).max(2);
+
+    let mut matches: Vec<(usize, &str)> = candidates
+        .map(|c| (strsim::levenshtein(&value, c), c))
+        .filter(|(distance, c)| {
+            *distance <= max_distance || (!value.is_empty() && c.contains(value.as_str()))
+        })
+        .collect();
+
+    matches.sort_unstable();
+    matches
+        .into_iter()
+        .take(MAX_NEAREST_MATCHES)
+        .map(|(_, c)| c.to_string())
+        .collect()
+}
+
+// sort is a list of column:direction entries so every entry is checked on its own
+fn invalid_sort_entries(sort: &str, columns: &[&str]) -> Vec<InvalidParam> {
+    let mut invalid = vec![];
+    for entry in sort.split(',').map(str::trim).filter(|x| !x.is_empty()) {
+        let (column, direction) = entry.split_once(':').unwrap_or((entry, "asc"));
+        let (column, direction) = (
+            column.trim().to_lowercase(),
+            direction.trim().to_lowercase(),
+        );
+
+        if !columns.contains(&column.as_str()) {
+            invalid.push(InvalidParam {
+                field: "sort".to_string(),
+                value: entry.to_string(),
+                allowed: columns.iter().map(ToString::to_string).collect(),
+                nearest_matches: nearest_matches(&column, columns.iter().copied()),
+            });
+        } else if !SORT_DIRECTIONS.contains(&direction.as_str()) {
+            invalid.push(InvalidParam {
+                field: "sort".to_string(),
+                value: entry.to_string(),
+                allowed: SORT_DIRECTIONS.iter().map(ToString::to_string).collect(),
+                nearest_matches: nearest_matches(&direction, SORT_DIRECTIONS.iter().copied()),
+            });
+        }
+    }
+
+    invalid
+}
+
+// only runs when strict=true since by default invalid values are quietly replaced with defaults
+// the raw query is needed because the deserializers have already thrown the bad values away
+pub fn validate_strict<T: Serialize>(
+    raw_query: Option<&str>,
+    query_parms: &T,
+    rules: FieldRules,
+) -> Result<(), AppError> {
+    // every field the params struct serializes is a known param name
+    let known_fields: Vec<String> = serde_json::to_value(query_parms)
+        .ok()
+        .and_then(|v| v.as_object().map(|obj| obj.keys().cloned().collect()))
+        .unwrap_or_default();
+
+    let mut invalid = vec![];
+    for (field, value) in form_urlencoded::parse(raw_query.unwrap_or_default().as_bytes()) {
+        if !known_fields.iter().any(|known| *known == field) {
+            invalid.push(InvalidParam {
+                nearest_matches: nearest_matches(&field, known_fields.iter().map(String::as_str)),
+                field: field.into_owned(),
+                value: value.into_owned(),
+                allowed: known_fields.clone(),
+            });
+            continue;
+        }
+
+        let Some((_, allowed)) = rules.iter().find(|(name, _)| *name == field) else {
+            continue;
+        };
+
+        if field == "sort" {
+            invalid.extend(invalid_sort_entries(&value, allowed));
+            continue;
+        }
+
+        // empty and any both mean no filter and are what the frontend sends by default
+        let lowercase = value.to_lowercase();
+        if allowed.contains(&lowercase.as_str())
+            || lowercase.is_empty()
+            || (lowercase == "any" && field != "sort_by")
+        {
+            continue;
+        }
+
+        invalid.push(InvalidParam {
+            nearest_matches: nearest_matches(&value, allowed.iter().copied()),
+            field: field.into_owned(),
+            value: value.into_owned(),
+            allowed: allowed.iter().map(ToString::to_string).collect(),
+        });
+    }
+
+    if invalid.is_empty() {
+        Ok(())
+    } else {
+        Err(AppError::InvalidQueryParams(invalid))
+    }
+}
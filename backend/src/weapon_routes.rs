@@ -0,0 +1,300 @@
+use crate::ammo_routes::get_ammo;
+use crate::api_routers::Device;
+use crate::database_types::{Ammo, Weapon};
+use crate::filters::{list_cache_key, text_cache_key};
+use crate::init_app_state::{AppState, WEAPONS_UNIQUE_CACHE_PREFIX};
+use crate::pagination::{Cursor, SortKey, cached_count, push_order_by, sort_to_string};
+use crate::query_types::{
+    AmmoQueryParams, AppError, AppErrorHandling, Caliber, ErrorBody, WeaponQueryParams,
+};
+use crate::telemetry::db_span;
+use crate::validation::{WEAPON_FIELD_RULES, validate_strict};
+use axum::{
+    Json,
+    extract::{Path, RawQuery, State},
+};
+use axum_extra::extract::Query;
+use sqlx::{Postgres, QueryBuilder};
+use tracing::Instrument;
+
+// the raw calibers from the api along with how people actually call them
+const CALIBER_NAMES: &[(&str, &str)] = &[
+    ("Caliber556x45NATO", "5.56x45mm NATO"),
+    ("Caliber545x39", "5.45x39mm"),
+    ("Caliber762x39", "7.62x39mm"),
+    ("Caliber762x51", "7.62x51mm NATO"),
+    ("Caliber762x54R", "7.62x54mmR"),
+    ("Caliber762x35", ".300 Blackout"),
+    ("Caliber762x25TT", "7.62x25mm Tokarev"),
+    ("Caliber9x19PARA", "9x19mm Parabellum"),
+    ("Caliber9x18PM", "9x18mm Makarov"),
+    ("Caliber9x18PMM", "9x18mm Makarov"),
+    ("Caliber9x21", "9x21mm Gyurza"),
+    ("Caliber9x33R", ".357 Magnum"),
+    ("Caliber9x39", "9x39mm"),
+    ("Caliber366TKM", ".366 TKM"),
+    ("Caliber1143x23ACP", ".45 ACP"),
+    ("Caliber46x30", "4.6x30mm HK"),
+    ("Caliber57x28", "5.7x28mm FN"),
+    ("Caliber68x51", "6.8x51mm"),
+    ("Caliber86x70", ".338 Lapua Magnum"),
+    ("Caliber93x64", "9.3x64mm Brenneke"),
+    ("Caliber127x55", "12.7x55mm STs-130"),
+    ("Caliber127x99", ".50 BMG"),
+    ("Caliber127x33", ".50 Action Express"),
+    ("Caliber12g", "12/70 gauge"),
+    ("Caliber20g", "20/70 gauge"),
+    ("Caliber23x75", "23x75mm KS-23"),
+    ("Caliber26x75", "26x75mm flare"),
+    ("Caliber30x29", "30x29mm grenade"),
+    ("Caliber40x46", "40x46mm grenade"),
+    ("Caliber40mmRU", "40mm VOG grenade"),
+];
+
+// unknown calibers still read better without the prefix so new ones do not show up as Caliber...
+pub fn caliber_name(caliber: &str) -> String {
+    CALIBER_NAMES
+        .iter()
+        .find(|(raw, _)| raw.eq_ignore_ascii_case(caliber))
+        .map_or_else(
+            || {
+                caliber
+                    .strip_prefix("Caliber")
+                    .filter(|x| !x.is_empty())
+                    .unwrap_or(caliber)
+                    .to_string()
+            },
+            |(_, name)| (*name).to_string(),
+        )
+}
+
+fn with_caliber_names(mut weapons: Vec<Weapon>) -> Vec<Weapon> {
+    for weapon in &mut weapons {
+        weapon.caliber_name = caliber_name(&weapon.caliber);
+    }
+
+    weapons
+}
+
+pub fn weapon_filters_cache_key(query_parms: &WeaponQueryParams) -> String {
+    format!(
+        "c{}s{}",
+        list_cache_key(&query_parms.caliber),
+        text_cache_key(&query_parms.search)
+    )
+}
+
+// the WHERE shared by the weapons query and its count
+fn push_weapon_filters(qb: &mut QueryBuilder<'_, Postgres>, query_parms: &WeaponQueryParams) {
+    let search = &query_parms.search;
+    qb.push("WHERE (")
+        .push_bind(search.clone())
+        .push(" = '' OR weapon_name ILIKE ")
+        .push_bind(format!("%{search}%"))
+        .push(" OR short_name ILIKE ")
+        .push_bind(format!("%{search}%"))
+        .push(") ");
+
+    if !query_parms.caliber.is_empty() {
+        qb.push("AND caliber = ANY(")
+            .push_bind(query_parms.caliber.clone())
+            .push(") ");
+    }
+}
+
+impl WeaponQueryParams {
+    pub fn sort_keys(&self) -> Vec<SortKey> {
+        vec![SortKey::new(&self.sort_by, self.sort_asc)]
+    }
+}
+
+// total weapons for a filter set so pages can show how many there are
+pub async fn count_weapons(
+    app_state: &AppState,
+    query_parms: &WeaponQueryParams,
+) -> Result<i64, AppError> {
+    let cache_key = format!(
+        "{}count{}",
+        WEAPONS_UNIQUE_CACHE_PREFIX,
+        weapon_filters_cache_key(query_parms),
+    );
+
+    let mut qb: QueryBuilder<'_, Postgres> = QueryBuilder::new("SELECT COUNT(*) FROM Weapon ");
+    push_weapon_filters(&mut qb, query_parms);
+
+    cached_count(
+        app_state,
+        cache_key,
+        WEAPONS_UNIQUE_CACHE_PREFIX,
+        qb,
+        "Weapon Count",
+    )
+    .await
+}
+
+#[utoipa::path(
+    get,
+    path = "/",
+    tag = "weapons",
+    params(WeaponQueryParams),
+    responses(
+        (status = 200, body = Vec<Weapon>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_weapons(
+    Query(query_parms): Query<WeaponQueryParams>,
+    RawQuery(raw_query): RawQuery,
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<Weapon>>, AppError> {
+    if query_parms.strict {
+        validate_strict(raw_query.as_deref(), &query_parms, WEAPON_FIELD_RULES)?;
+    }
+
+    let sort = query_parms.sort_keys();
+    let offset = query_parms
+        .cursor
+        .as_deref()
+        .filter(|c| !c.is_empty())
+        .map_or(Ok(query_parms.offset), |c| {
+            Cursor::decode(c, &sort, &weapon_filters_cache_key(&query_parms)).map(|c| c.offset)
+        })?;
+
+    let cache_key = format!(
+        "{}{}{}l{}o{}",
+        WEAPONS_UNIQUE_CACHE_PREFIX,
+        sort_to_string(&sort),
+        weapon_filters_cache_key(&query_parms),
+        query_parms.limit,
+        offset,
+    );
+
+    if let Some(values) = app_state.cache.get_vec(&cache_key) {
+        return Ok(Json(values));
+    }
+
+    let mut qb: QueryBuilder<'_, Postgres> = QueryBuilder::new("SELECT * FROM Weapon ");
+    push_weapon_filters(&mut qb, &query_parms);
+    push_order_by(&mut qb, &sort, str::to_string, "_id");
+    qb.push("LIMIT ")
+        .push_bind(i64::from(query_parms.limit))
+        .push(" OFFSET ")
+        .push_bind(i64::from(offset));
+
+    let weapons: Vec<Weapon> = qb
+        .build_query_as()
+        .fetch_all(&app_state.pgpool)
+        .instrument(db_span("Weapons"))
+        .await
+        .bad_sql("Weapons")?;
+    let weapons = with_caliber_names(weapons);
+
+    let tokio_values = weapons.clone();
+    tokio::spawn(async move {
+        app_state
+            .cache
+            .insert_vec(cache_key, tokio_values, WEAPONS_UNIQUE_CACHE_PREFIX);
+    });
+
+    Ok(Json(weapons))
+}
+
+pub async fn get_weapon(app_state: &AppState, id: &str) -> Result<Weapon, AppError> {
+    let cache_key = format!("{WEAPONS_UNIQUE_CACHE_PREFIX}{id}");
+    if let Some(weapon) = app_state.cache.get(&cache_key) {
+        return Ok(weapon);
+    }
+
+    let mut weapon = sqlx::query_as!(
+        Weapon,
+        r#"SELECT *, ''::text AS "caliber_name!" FROM Weapon WHERE _id = $1"#,
+        id
+    )
+    .fetch_optional(&app_state.pgpool)
+    .instrument(db_span("Weapon"))
+    .await
+    .bad_sql("Weapon")?
+    .ok_or_else(|| AppError::NotFound(format!("Weapon {id} does not exist")))?;
+    weapon.caliber_name = caliber_name(&weapon.caliber);
+
+    let (cache, tokio_weapon) = (app_state.cache.clone(), weapon.clone());
+    tokio::spawn(async move {
+        cache.insert(cache_key, tokio_weapon, WEAPONS_UNIQUE_CACHE_PREFIX);
+    });
+
+    Ok(weapon)
+}
+
+// the ammo params with the caliber pinned to the weapon's so every ammo filter and sort still applies
+pub async fn weapon_ammo_parms(
+    app_state: &AppState,
+    id: &str,
+    mut query_parms: AmmoQueryParams,
+) -> Result<AmmoQueryParams, AppError> {
+    let weapon = get_weapon(app_state, id).await?;
+    query_parms.caliber = vec![weapon.caliber];
+    query_parms.save = false;
+
+    Ok(query_parms)
+}
+
+// the ammo a weapon can fire ranked by the ammo sort like penetration_power or price
+#[utoipa::path(
+    get,
+    path = "/{id}/ammo",
+    tag = "weapons",
+    params(("id" = String, Path, description = "weapon id"), AmmoQueryParams),
+    responses(
+        (status = 200, body = Vec<Ammo>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_weapon_ammo(
+    Path(id): Path<String>,
+    Query(query_parms): Query<AmmoQueryParams>,
+    raw_query: RawQuery,
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<Ammo>>, AppError> {
+    let query_parms = weapon_ammo_parms(&app_state, &id, query_parms).await?;
+
+    get_ammo(
+        Device(None),
+        Query(query_parms),
+        raw_query,
+        State(app_state),
+    )
+    .await
+}
+
+// not cached since it spans the weapon and ammo datasets which refresh separately
+#[utoipa::path(
+    get,
+    path = "/calibers",
+    tag = "weapons",
+    responses(
+        (status = 200, body = Vec<Caliber>),
+        (status = "default", description = "Error", body = ErrorBody)
+    )
+)]
+pub async fn get_calibers(
+    State(app_state): State<AppState>,
+) -> Result<Json<Vec<Caliber>>, AppError> {
+    let mut calibers: Vec<Caliber> = sqlx::query_as(
+        "SELECT c.caliber, COUNT(DISTINCT w._id) AS weapon_count, COUNT(DISTINCT a.item_id) AS ammo_count
+        FROM (SELECT caliber FROM Weapon UNION SELECT caliber FROM Ammo) c
+        LEFT JOIN Weapon w ON w.caliber = c.caliber
+        LEFT JOIN Ammo a ON a.caliber = c.caliber
+        GROUP BY c.caliber
+        ORDER BY c.caliber",
+    )
+    .fetch_all(&app_state.pgpool)
+    .instrument(db_span("Calibers"))
+    .await
+    .bad_sql("Calibers")?;
+
+    for caliber in &mut calibers {
+        caliber.caliber_name = caliber_name(&caliber.caliber);
+    }
+
+    Ok(Json(calibers))
+}
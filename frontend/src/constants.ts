@@ -2,6 +2,7 @@ export const DEFAULT_ITEM_QUERY_PARAMS = {
   search: "",
   sort_asc: false,
   sort_by: "flea_market",
+  sort: "",
   item_type: "any",
   limit: 40,
   offset: 0,
@@ -22,6 +23,7 @@ export const DEFAULT_AMMO_QUERY_PARAMS = {
   search: "",
   sort_asc: false,
   sort_by: "any",
+  sort: "",
   damage: 0,
   penetration_power: 0,
   initial_speed: 0,
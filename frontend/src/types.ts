@@ -50,7 +50,24 @@ export type ItemQueryParams = {
   search: string;
   sort_asc: boolean;
   sort_by: string;
+  sort: string;
   item_type: string;
+  min_base_price?: number;
+  max_base_price?: number;
+  min_flea_price?: number;
+  max_flea_price?: number;
+  min_per_slot?: number;
+  max_per_slot?: number;
+  min_buy_from_flea_instant_profit?: number;
+  max_buy_from_flea_instant_profit?: number;
+  min_buy_from_trader_instant_profit?: number;
+  max_buy_from_trader_instant_profit?: number;
+  min_slots?: number;
+  max_slots?: number;
+  min_avg_24h_price?: number;
+  max_avg_24h_price?: number;
+  is_flea?: boolean;
+  sellable_to?: string;
   limit: number;
   offset: number;
 };
@@ -148,21 +165,107 @@ export type Ammo = {
   tracer_color: string;
   weight: number;
   item_id: string;
+  item?: AmmoItem;
+};
+
+export type AmmoItem = {
+  item_name: string;
+  short_name: string;
+  cheapest_buy: Buy | null;
+  flea_price: number | null;
 };
 
 export type AmmoQueryParams = {
   search: string;
   sort_by: string;
   sort_asc: boolean;
+  sort: string;
   damage: number;
   penetration_power: number;
   initial_speed: number;
   ammo_type: string;
+  min_accuracy_modifier?: number;
+  max_accuracy_modifier?: number;
+  min_armor_damage?: number;
+  max_armor_damage?: number;
+  min_fragmentation_chance?: number;
+  max_fragmentation_chance?: number;
+  max_damage?: number;
+  min_heavy_bleed_modifier?: number;
+  max_heavy_bleed_modifier?: number;
+  max_initial_speed?: number;
+  min_light_bleed_modifier?: number;
+  max_light_bleed_modifier?: number;
+  min_penetration_chance?: number;
+  max_penetration_chance?: number;
+  max_penetration_power?: number;
+  min_penetration_power_deviation?: number;
+  max_penetration_power_deviation?: number;
+  min_projectile_count?: number;
+  max_projectile_count?: number;
+  min_recoil_modifier?: number;
+  max_recoil_modifier?: number;
+  min_ricochet_chance?: number;
+  max_ricochet_chance?: number;
+  min_stack_max_size?: number;
+  max_stack_max_size?: number;
+  min_stamina_burn_per_damage?: number;
+  max_stamina_burn_per_damage?: number;
+  min_weight?: number;
+  max_weight?: number;
+  tracer?: boolean;
+  caliber?: string[];
+  embed_item?: boolean;
+  trader_level?: number;
   limit: number;
   offset: number;
 };
 
+export type Weapon = {
+  _id: string;
+  weapon_name: string;
+  short_name: string;
+  caliber: string;
+  caliber_name: string;
+  fire_rate: number;
+  ergonomics: number;
+  recoil_vertical: number;
+  recoil_horizontal: number;
+};
+
+export type ArmorPiece = {
+  _id: string;
+  armor_name: string;
+  short_name: string;
+  armor_type: "armor" | "armorplate" | "helmet";
+  armor_class: number;
+  durability: number;
+  material: string;
+  blunt_throughput: number;
+  ricochet_chance: number;
+  zones: string[];
+  speed_penalty: number;
+  turn_penalty: number;
+  ergo_penalty: number;
+  weight: number;
+};
+
 export type ItemBase = {
   _id: string;
   item_name: string;
 };
+
+export type ApiErrorCode =
+  | "database_error"
+  | "bad_request"
+  | "not_found"
+  | "conflict"
+  | "rate_limited"
+  | "unauthorized";
+
+export type ApiError = {
+  code: ApiErrorCode;
+  message: string;
+  details: unknown;
+  request_id: string | null;
+};